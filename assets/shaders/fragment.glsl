@@ -1,10 +1,16 @@
 #version 330 core
 
 in vec2 v_uv;
+in vec4 v_color;
+flat in vec4 v_region;
+flat in vec4 v_effects_a;
+flat in vec4 v_effects_b;
 out vec4 frag_color;
 
 uniform sampler2D u_texture;
 uniform vec4 u_color;
+// Batched sprites bring their atlas region and their first 8 effect values with the vertices
+uniform bool u_batched;
 
 uniform int u_effects[32];
 uniform float u_effect_values[32];
@@ -34,8 +40,16 @@ struct Effect {
 	float value;
 };
 
+float effect_value(int index) {
+	if (u_batched && index < 4)
+		return v_effects_a[index];
+	if (u_batched && index < 8)
+		return v_effects_b[index - 4];
+	return u_effect_values[index];
+}
+
 Effect get_effect(int index) {
-	return Effect(u_effects[index], u_effect_values[index]);
+	return Effect(u_effects[index], effect_value(index));
 }
 
 vec3 rgb2hsv(vec3 c) {
@@ -92,7 +106,9 @@ vec3 apply_grayscale_weighted(vec3 color, float amount) {
 }
 
 void main() {
-	vec4 tex_color = texture(u_texture, v_uv);
+	// UVs past 0 and 1 repeat the costume, not the rest of its atlas page
+	vec2 uv = u_batched ? v_region.xy + fract(v_uv) * v_region.zw : v_uv;
+	vec4 tex_color = texture(u_texture, uv);
 
 	for (int i = 0; i < u_effects_count; ++i) {
 		Effect effect = get_effect(i);
@@ -138,5 +154,5 @@ void main() {
 		discard;
 	}
 
-	frag_color = tex_color * u_color;
+	frag_color = tex_color * u_color * v_color;
 }
@@ -2,14 +2,27 @@
 
 layout(location = 0) in vec2 a_position;
 layout(location = 1) in vec2 a_uv;
+layout(location = 2) in vec4 a_color;
+layout(location = 3) in vec4 a_region;
+layout(location = 4) in vec4 a_effects_a;
+layout(location = 5) in vec4 a_effects_b;
 
 out vec2 v_uv;
+out vec4 v_color;
+flat out vec4 v_region;
+flat out vec4 v_effects_a;
+flat out vec4 v_effects_b;
 
 uniform mat4 u_projection;
 uniform mat4 u_model;
+uniform bool u_vertex_color;
 
 void main() {
 	gl_Position = u_projection * u_model * vec4(a_position, 0.0, 1.0);
 
 	v_uv = a_uv;
+	v_color = u_vertex_color ? a_color : vec4(1.0);
+	v_region = a_region;
+	v_effects_a = a_effects_a;
+	v_effects_b = a_effects_b;
 }
@@ -19,9 +19,12 @@ use glfw::Context;
 const VERT_SHADER: &str = include_str!("../assets/shaders/vertex.glsl");
 const FRAG_SHADER: &str = include_str!("../assets/shaders/fragment.glsl");
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use glfw::WindowHint;
 
+use kira::backend::mock::MockBackend;
+use kira::{AudioManager, AudioManagerSettings, DefaultBackend};
+
 use crate::utils::core::ShaderProgram;
 
 mod utils;
@@ -29,20 +32,82 @@ mod utils;
 #[derive(Parser)]
 #[command(name = "Crust", version, about)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Create a new Crust project with the given name.
     #[arg(short, long)]
     new: Option<String>,
     /// The path to the Crust project file. If not provided, a file dialog will open to select one.
     #[arg(short, long)]
     project: Option<String>,
+    /// Run the project without opening a window or an audio device.
+    #[arg(long)]
+    headless: bool,
+    /// Stop a headless run after this many frames.
+    #[arg(long, requires = "headless")]
+    frames: Option<u32>,
+    /// A file with scripted input events to feed a headless run.
+    #[arg(long, requires = "headless")]
+    input: Option<String>,
+    /// Wait for a debugger (Debug Adapter Protocol) to attach on this port before running.
+    #[arg(long, num_args = 0..=1, default_missing_value = "4711", value_name = "PORT")]
+    debug: Option<u16>,
     /// Additional arguments to pass to the Crust runtime.
     #[arg(last = true)]
     additional_args: Vec<String>,
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// Run the `test` blocks and `*_test.crst` files of a project.
+    Test {
+        /// The path to the Crust project file.
+        #[arg(short, long)]
+        project: String,
+        /// Only run tests whose `sprite::name` contains this text.
+        filter: Option<String>,
+        /// How to report the results.
+        #[arg(short, long, value_enum, default_value = "pretty")]
+        format: utils::TestFormat,
+        /// Write the report to this file instead of the terminal.
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
+// Attached before the project is loaded, so the code is parsed with the locations the debugger
+// breaks on
+fn attach_debugger(port: Option<u16>) -> Option<utils::Debugger> {
+    port.map(|port| {
+        utils::Debugger::listen(port).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })
+    })
+}
+
 fn main() {
     let args = Args::parse();
 
+    if let Some(Command::Test {
+        project,
+        filter,
+        format,
+        output,
+    }) = args.command
+    {
+        // Tests always run headless, so this program is never actually compiled
+        let shader_program = ShaderProgram::new(VERT_SHADER, FRAG_SHADER);
+        let code = utils::run_tests(
+            project.trim_matches('"'),
+            format,
+            filter.as_deref(),
+            output.as_deref(),
+            &shader_program,
+        );
+        std::process::exit(code);
+    }
+
     if let Some(new_project_name) = args.new {
         let toml_path = utils::create_new_project(&new_project_name);
         println!("Created new project: {}", new_project_name);
@@ -64,6 +129,41 @@ fn main() {
     });
     let project_file = project_file.trim_matches('"');
 
+    if args.headless {
+        let mut window = utils::HeadlessWindow::new(1024, 576, args.frames);
+        if let Some(input_file) = args.input {
+            let source = std::fs::read_to_string(&input_file)
+                .unwrap_or_else(|e| panic!("Failed to read input script {}: {}", input_file, e));
+            if let Err(e) = window.load_input_script(&source) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        let audio_manager = AudioManager::<MockBackend>::new(AudioManagerSettings::default())
+            .expect("Failed to create audio manager");
+
+        let debugger = attach_debugger(args.debug);
+        let mut runtime = utils::Runtime::new(
+            project_file,
+            args.additional_args,
+            &window,
+            Box::new(audio_manager),
+            debugger,
+        );
+        println!("Loaded project: {}", project_file);
+
+        // No GL functions are loaded, so this program is never compiled and all the drawing
+        // done by the runtime only gets counted
+        let shader_program = ShaderProgram::new(VERT_SHADER, FRAG_SHADER);
+        let code = runtime.run(&mut window, &shader_program);
+        println!(
+            "Ran {} frames, {} draw calls",
+            window.frame(),
+            utils::core::draw_calls()
+        );
+        std::process::exit(code);
+    }
+
     use glfw::fail_on_errors;
     let mut glfw = glfw::init(fail_on_errors!()).expect("Failed to initialize GLFW");
     glfw.window_hint(WindowHint::ContextVersion(3, 3));
@@ -75,27 +175,37 @@ fn main() {
         .expect("Failed to create GLFW window");
 
     window.set_key_polling(true);
+    window.set_char_polling(true);
     window.set_mouse_button_polling(true);
     window.set_cursor_pos_polling(true);
     window.make_current();
-    gl::load_with(|symbol| window.get_proc_address(symbol) as *const std::os::raw::c_void);
-
-    let mut runtime = utils::Runtime::new(&project_file, args.additional_args, &window);
+    utils::core::set_renderer(Box::new(utils::core::GlRenderer::new(|symbol| {
+        window.get_proc_address(symbol) as *const std::os::raw::c_void
+    })));
+
+    let audio_manager = AudioManager::<DefaultBackend>::new(AudioManagerSettings::default())
+        .expect("Failed to create audio manager");
+    let mut window = utils::GlfwWindow {
+        glfw,
+        window,
+        events,
+    };
+
+    let debugger = attach_debugger(args.debug);
+    let mut runtime = utils::Runtime::new(
+        project_file,
+        args.additional_args,
+        &window,
+        Box::new(audio_manager),
+        debugger,
+    );
     println!("Loaded project: {}", project_file);
 
-    glfw.set_swap_interval(if runtime.vsync {
+    window.glfw.set_swap_interval(if runtime.vsync {
         glfw::SwapInterval::Sync(1)
     } else {
         glfw::SwapInterval::None
     });
-    unsafe {
-        gl::Enable(gl::CULL_FACE);
-        gl::CullFace(gl::BACK);
-        gl::FrontFace(gl::CCW);
-        gl::Enable(gl::BLEND);
-        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
-    }
-
     {
         let icon = image::load_from_memory(include_bytes!("../assets/logo_background.png"))
             .expect("Failed to load icon image")
@@ -103,7 +213,7 @@ fn main() {
         let width = icon.width();
         let height = icon.height();
         let pixels = icon.into_raw();
-        window.set_icon_from_pixels(vec![glfw::PixelImage {
+        window.window.set_icon_from_pixels(vec![glfw::PixelImage {
             width,
             height,
             pixels: pixels
@@ -120,5 +230,6 @@ fn main() {
     }
 
     let shader_program = ShaderProgram::new(VERT_SHADER, FRAG_SHADER);
-    runtime.run(&mut window, &events, &shader_program, &mut glfw);
+    let code = runtime.run(&mut window, &shader_program);
+    std::process::exit(code);
 }
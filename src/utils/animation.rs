@@ -0,0 +1,369 @@
+use glam::*;
+use image::codecs::{gif::GifDecoder, png::PngDecoder};
+use image::{AnimationDecoder, DynamicImage};
+use serde_json::Value as Json;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::utils::core::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnimationMode {
+    Loop,
+    Once,
+    PingPong,
+}
+
+impl AnimationMode {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "loop" => Ok(Self::Loop),
+            "once" => Ok(Self::Once),
+            "ping-pong" => Ok(Self::PingPong),
+            _ => Err(format!(
+                "Unknown animation mode '{}', expected \"loop\", \"once\" or \"ping-pong\"",
+                name
+            )),
+        }
+    }
+}
+
+// A list of costumes shown one after another, each for its own number of seconds
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub frames: Vec<usize>,
+    pub delays: Vec<f32>,
+    pub mode: AnimationMode,
+}
+
+impl Animation {
+    pub fn new(frames: Vec<usize>, fps: f32, mode: AnimationMode) -> Self {
+        let delays = vec![1.0 / fps.max(0.001); frames.len()];
+        Self {
+            frames,
+            delays,
+            mode,
+        }
+    }
+
+    // Frames `first` to `first + delays.len()`, for the frames of an animated image
+    pub fn from_delays(first: usize, delays: Vec<f32>) -> Self {
+        Self {
+            frames: (first..first + delays.len()).collect(),
+            delays,
+            mode: AnimationMode::Loop,
+        }
+    }
+
+    // The same animation with every costume index moved along by `offset`
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.frames.iter_mut().for_each(|f| *f += offset);
+        self
+    }
+
+    // Leaves out a costume that was removed and moves the ones after it down by one
+    pub fn remove_costume(&mut self, costume: usize) {
+        let kept: Vec<(usize, f32)> = self
+            .frames
+            .iter()
+            .zip(&self.delays)
+            .filter(|(f, _)| **f != costume)
+            .map(|(f, delay)| (if *f > costume { f - 1 } else { *f }, *delay))
+            .collect();
+        (self.frames, self.delays) = kept.into_iter().unzip();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationPlayback {
+    pub name: String,
+    // The position in the animation's frame list, not a costume index
+    pub frame: usize,
+    pub elapsed: f32,
+    pub forward: bool,
+    pub finished: bool,
+}
+
+impl AnimationPlayback {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            frame: 0,
+            elapsed: 0.0,
+            forward: true,
+            finished: false,
+        }
+    }
+
+    // Moves along by `dt` and returns the costume to show
+    pub fn advance(&mut self, animation: &Animation, dt: f32) -> Option<usize> {
+        let last = animation.frames.len().checked_sub(1)?;
+        self.frame = self.frame.min(last);
+        if self.finished {
+            return animation.frames.get(self.frame).copied();
+        }
+        self.elapsed += dt;
+        // A long frame can skip several short ones, but never loops forever on zero delays
+        for _ in 0..=animation.frames.len() * 2 {
+            let delay = animation.delays.get(self.frame).copied().unwrap_or(0.0);
+            if self.elapsed < delay {
+                break;
+            }
+            self.elapsed -= delay;
+            match animation.mode {
+                AnimationMode::Loop => self.frame = (self.frame + 1) % (last + 1),
+                AnimationMode::Once if self.frame == last => {
+                    self.finished = true;
+                    self.elapsed = 0.0;
+                    break;
+                }
+                AnimationMode::Once => self.frame += 1,
+                AnimationMode::PingPong if last == 0 => {}
+                AnimationMode::PingPong => {
+                    if self.forward && self.frame == last || !self.forward && self.frame == 0 {
+                        self.forward = !self.forward;
+                    }
+                    if self.forward {
+                        self.frame += 1;
+                    } else {
+                        self.frame -= 1;
+                    }
+                }
+            }
+        }
+        animation.frames.get(self.frame).copied()
+    }
+}
+
+// Every frame of a GIF or PNG, with how many seconds it is shown. A still PNG is decoded by the
+// same decoder that found it isn't animated, as its only frame. Other images are `None`.
+pub fn load_animated(path: &Path) -> Result<Option<Vec<(CPUTexture, f32)>>, String> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase);
+    let open = || {
+        File::open(path)
+            .map(BufReader::new)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))
+    };
+    let error = |e: image::ImageError| format!("Failed to decode {}: {}", path.display(), e);
+    let frames = match extension.as_deref() {
+        Some("gif") => GifDecoder::new(open()?).map_err(error)?.into_frames(),
+        Some("png" | "apng") => {
+            let decoder = PngDecoder::new(open()?).map_err(error)?;
+            if !decoder.is_apng().map_err(error)? {
+                let image = DynamicImage::from_decoder(decoder).map_err(error)?;
+                return Ok(Some(vec![(CPUTexture::from_image(image.to_rgba8()), 0.0)]));
+            }
+            decoder.apng().map_err(error)?.into_frames()
+        }
+        _ => return Ok(None),
+    };
+    let frames = frames.collect_frames().map_err(error)?;
+    if frames.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(
+        frames
+            .into_iter()
+            .map(|frame| {
+                let (numerator, denominator) = frame.delay().numer_denom_ms();
+                let delay = numerator as f32 / denominator.max(1) as f32 / 1000.0;
+                // Like browsers, very short delays are slowed down to 10 frames per second
+                let delay = if delay < 0.02 { 0.1 } else { delay };
+                (CPUTexture::from_image(frame.into_buffer()), delay)
+            })
+            .collect(),
+    ))
+}
+
+// Cuts a sheet into equally sized frames, left to right and then top to bottom
+pub fn slice_grid(
+    sheet: &CPUTexture,
+    frame: UVec2,
+    margin: u32,
+    spacing: u32,
+    count: Option<usize>,
+) -> Result<Vec<CPUTexture>, String> {
+    if frame.x == 0 || frame.y == 0 {
+        return Err("A sprite sheet's frames must be at least 1 pixel big".to_string());
+    }
+    // Margins and spacings can be anything the project says, so they are added up in 64 bits.
+    // Every position of a frame that fits is inside the sheet, so it fits back into 32 bits.
+    let fits = |size: u32, frame: u32| {
+        let inner = u64::from(size).saturating_sub(u64::from(margin) * 2);
+        ((inner + u64::from(spacing)) / (u64::from(frame) + u64::from(spacing))) as u32
+    };
+    let offset = |cell: u32, frame: u32| {
+        (u64::from(margin) + u64::from(cell) * (u64::from(frame) + u64::from(spacing))) as u32
+    };
+    let columns = fits(sheet.width, frame.x);
+    let rows = fits(sheet.height, frame.y);
+    let fitting = (columns as usize).saturating_mul(rows as usize);
+    let count = count.unwrap_or(fitting);
+    if count > fitting {
+        return Err(format!(
+            "A sprite sheet of {}x{} only fits {} frames of {}x{}",
+            sheet.width, sheet.height, fitting, frame.x, frame.y
+        ));
+    }
+    Ok((0..count)
+        .map(|i| {
+            let (column, row) = ((i % columns as usize) as u32, (i / columns as usize) as u32);
+            let corner = uvec2(offset(column, frame.x), offset(row, frame.y));
+            sheet.crop(corner, frame)
+        })
+        .collect())
+}
+
+// The image of a packed sheet, relative to its JSON file
+pub fn packed_image(json: &Json) -> Option<&str> {
+    json.pointer("/meta/image").and_then(Json::as_str)
+}
+
+// Compares names with the numbers in them compared by value, so "walk 2" comes before "walk 10"
+fn natural_order(a: &str, b: &str) -> std::cmp::Ordering {
+    fn chunks(s: &str) -> Vec<(bool, &str)> {
+        let mut chunks = vec![];
+        let mut start = 0;
+        for (i, c) in s.char_indices().skip(1) {
+            let previous = s[..i]
+                .chars()
+                .next_back()
+                .is_some_and(|p| p.is_ascii_digit());
+            if c.is_ascii_digit() != previous {
+                chunks.push((previous, &s[start..i]));
+                start = i;
+            }
+        }
+        if start < s.len() {
+            chunks.push((
+                s[start..].starts_with(|c: char| c.is_ascii_digit()),
+                &s[start..],
+            ));
+        }
+        chunks
+    }
+    for (a, b) in chunks(a).into_iter().zip(chunks(b)) {
+        let order = match (a, b) {
+            ((true, a), (true, b)) => {
+                let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+                a.len().cmp(&b.len()).then(a.cmp(b))
+            }
+            ((_, a), (_, b)) => a.cmp(b),
+        };
+        if order.is_ne() {
+            return order;
+        }
+    }
+    a.len().cmp(&b.len()).then(a.cmp(b))
+}
+
+fn json_rect(json: &Json, key: &str) -> Option<(UVec2, UVec2)> {
+    let rect = json.get(key)?;
+    let number = |k: &str| rect.get(k).and_then(Json::as_u64).map(|n| n as u32);
+    let position = uvec2(number("x").unwrap_or(0), number("y").unwrap_or(0));
+    Some((position, uvec2(number("w")?, number("h")?)))
+}
+
+// Cuts the frames of a sheet packed by TexturePacker or Aseprite out of `sheet`, named after
+// their file names without the extension, along with the animations of Aseprite's tags. Their
+// frames are counted from the sheet's first frame.
+pub fn slice_packed(
+    json: &Json,
+    sheet: &CPUTexture,
+) -> Result<(Vec<(String, CPUTexture)>, HashMap<String, Animation>), String> {
+    // TexturePacker and Aseprite can write the frames as a list or as an object
+    let entries: Vec<(String, &Json)> = match json.get("frames") {
+        Some(Json::Array(frames)) => frames
+            .iter()
+            .enumerate()
+            .map(|(i, f)| {
+                let name = f.get("filename").and_then(Json::as_str);
+                (name.map_or_else(|| i.to_string(), str::to_string), f)
+            })
+            .collect(),
+        Some(Json::Object(frames)) => {
+            // Objects don't keep their order when read, so the frames are sorted by name instead
+            let mut frames: Vec<_> = frames.iter().map(|(k, f)| (k.clone(), f)).collect();
+            frames.sort_by(|(a, _), (b, _)| natural_order(a, b));
+            frames
+        }
+        _ => return Err("A packed sprite sheet needs a 'frames' list or object".to_string()),
+    };
+
+    let mut frames = vec![];
+    let mut delays = vec![];
+    for (name, entry) in entries {
+        if entry.get("rotated").and_then(Json::as_bool) == Some(true) {
+            return Err(format!(
+                "The frame '{}' is rotated, which isn't supported. Turn off rotation when packing.",
+                name
+            ));
+        }
+        let (position, size) = json_rect(entry, "frame")
+            .ok_or_else(|| format!("The frame '{}' has no 'frame' rectangle", name))?;
+        let mut texture = sheet.crop(position, size);
+        // Trimmed frames are put back where they were in the untrimmed image, so they line up
+        if entry.get("trimmed").and_then(Json::as_bool) == Some(true) {
+            let source = json_rect(entry, "sourceSize").map(|(_, size)| size);
+            let offset = json_rect(entry, "spriteSourceSize").map(|(offset, _)| offset);
+            if let (Some(source), Some(offset)) = (source, offset) {
+                let mut untrimmed = CPUTexture::new(source.x, source.y);
+                untrimmed.blit(&texture, offset);
+                texture = untrimmed;
+            }
+        }
+        let stem = Path::new(&name).with_extension("");
+        frames.push((stem.to_string_lossy().to_string(), texture));
+        // Aseprite writes how long each frame is shown, in milliseconds
+        delays.push(
+            entry
+                .get("duration")
+                .and_then(Json::as_f64)
+                .unwrap_or(100.0) as f32
+                / 1000.0,
+        );
+    }
+
+    let mut animations = HashMap::new();
+    let tags = json.pointer("/meta/frameTags").and_then(Json::as_array);
+    for tag in tags.into_iter().flatten() {
+        let name = tag.get("name").and_then(Json::as_str);
+        let from = tag.get("from").and_then(Json::as_u64);
+        let to = tag.get("to").and_then(Json::as_u64);
+        let (Some(name), Some(from), Some(to)) = (name, from, to) else {
+            continue;
+        };
+        // Tags pointing past the last frame only keep the frames that exist
+        let mut range: Vec<usize> = (from as usize..=to as usize)
+            .take_while(|f| *f < frames.len())
+            .collect();
+        if range.is_empty() {
+            continue;
+        }
+        let direction = tag.get("direction").and_then(Json::as_str).unwrap_or("");
+        if direction.ends_with("reverse") {
+            range.reverse();
+        }
+        let mode = if direction.starts_with("pingpong") {
+            AnimationMode::PingPong
+        } else {
+            AnimationMode::Loop
+        };
+        let frame_delays = range.iter().map(|f| delays[*f]).collect();
+        animations.insert(
+            name.to_string(),
+            Animation {
+                frames: range,
+                delays: frame_delays,
+                mode,
+            },
+        );
+    }
+
+    Ok((frames, animations))
+}
@@ -0,0 +1,274 @@
+use kira::sound::static_sound::StaticSoundData;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::utils::core::*;
+use crate::utils::{Animation, CostumeMask, VectorImage, load_animated, rasterize_svg};
+
+// A costume or backdrop: its texture on an atlas page for drawing, and its pixels for collisions.
+// Copies share both.
+#[derive(Debug, Clone)]
+pub struct Costume {
+    pub texture: AtlasRegion,
+    pub mask: Arc<CostumeMask>,
+    // What scripts call it instead of its number
+    pub name: String,
+    // Made or copied by a script, so its pixels belong to the sprite and are changed in place
+    pub editable: bool,
+}
+
+impl Costume {
+    pub fn new(texture: &CPUTexture, atlas: &mut Atlas) -> Self {
+        Self {
+            texture: atlas.add(texture),
+            mask: Arc::new(CostumeMask::from(texture)),
+            name: String::new(),
+            editable: false,
+        }
+    }
+
+    // A costume with a texture of its own, outside of any atlas
+    pub fn single(texture: &CPUTexture) -> Self {
+        Self {
+            texture: AtlasRegion::single(texture),
+            mask: Arc::new(CostumeMask::from(texture)),
+            name: String::new(),
+            editable: false,
+        }
+    }
+
+    // The costume with new pixels. They replace the old ones in place when the size is the same,
+    // so every copy draws them, but only the returned costume collides with them.
+    pub fn replaced(&self, texture: &CPUTexture, atlas: &mut Atlas) -> Self {
+        let mut region = self.texture.clone();
+        region.replace(texture, atlas);
+        Self {
+            texture: region,
+            mask: Arc::new(CostumeMask::from(texture)),
+            name: self.name.clone(),
+            editable: self.editable,
+        }
+    }
+
+    // A costume that scripts can change the pixels of. Its rectangle of the atlas isn't shared
+    // with any other costume.
+    pub fn editable(texture: &CPUTexture, atlas: &mut Atlas) -> Self {
+        Self {
+            editable: true,
+            ..Self::new(texture, atlas)
+        }
+    }
+
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+}
+
+// The costumes loaded from one file, or cut out of one sprite sheet
+#[derive(Debug, Default)]
+pub struct CostumeSet {
+    pub frames: Vec<Costume>,
+    // How many seconds each frame of an animated image is shown, empty for still images
+    pub delays: Vec<f32>,
+    // The tags of a packed sprite sheet, counted from its first frame
+    pub animations: HashMap<String, Animation>,
+    // The SVG the costume was rasterised from
+    pub vector: Option<VectorImage>,
+}
+
+impl CostumeSet {
+    pub fn still(costume: Costume) -> Self {
+        Self {
+            frames: vec![costume],
+            ..Self::default()
+        }
+    }
+
+    // Copies of the costumes named after `name`, numbered from 1 when there are several
+    pub fn named(&self, name: &str) -> Vec<Costume> {
+        match self.frames.as_slice() {
+            [frame] => vec![frame.clone().named(name)],
+            frames => frames
+                .iter()
+                .enumerate()
+                .map(|(i, frame)| frame.clone().named(&format!("{} {}", name, i + 1)))
+                .collect(),
+        }
+    }
+
+    // How many sprites and clones (or backdrops) still use any of the costumes
+    fn users(&self) -> usize {
+        self.frames
+            .iter()
+            .map(|frame| Arc::strong_count(&frame.mask) - 1)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+struct CostumeEntry {
+    // The canonical paths of the files the costumes come from
+    files: Vec<PathBuf>,
+    set: Arc<CostumeSet>,
+}
+
+// Every image, sound and font of the project, loaded once however many sprites use it and
+// handed out as shared handles. An asset stays cached while anything holds one of its handles,
+// and `prune` drops the ones nothing holds anymore.
+#[derive(Default)]
+pub struct Assets {
+    atlas: Atlas,
+    costumes: HashMap<String, CostumeEntry>,
+    sounds: HashMap<PathBuf, StaticSoundData>,
+    fonts: HashMap<PathBuf, Arc<BitmapFont>>,
+}
+
+// The path that every way of writing the same file's path comes down to
+pub fn canonical_path(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl Assets {
+    // For costumes made or changed by scripts, which aren't cached
+    pub fn atlas(&mut self) -> &mut Atlas {
+        &mut self.atlas
+    }
+
+    // The costumes cached under `key`, loaded with `load` the first time they are asked for.
+    // They are forgotten when any of `files` changes.
+    pub fn costumes(
+        &mut self,
+        key: &str,
+        files: &[PathBuf],
+        load: impl FnOnce(&mut Atlas) -> Result<CostumeSet, String>,
+    ) -> Result<Arc<CostumeSet>, String> {
+        if let Some(entry) = self.costumes.get(key) {
+            return Ok(entry.set.clone());
+        }
+        let set = Arc::new(load(&mut self.atlas)?);
+        self.costumes.insert(
+            key.to_string(),
+            CostumeEntry {
+                files: files.iter().map(|f| canonical_path(f)).collect(),
+                set: set.clone(),
+            },
+        );
+        Ok(set)
+    }
+
+    // Every frame of a still or animated image, or an SVG rasterised at its own size. They are
+    // named after the file.
+    pub fn image(&mut self, path: &Path) -> Result<Arc<CostumeSet>, String> {
+        let file = canonical_path(path);
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        self.costumes(
+            &file.to_string_lossy(),
+            std::slice::from_ref(&file),
+            |atlas| {
+                if path.extension().is_some_and(|e| e == "svg") {
+                    let vector = load_vector(path)?;
+                    let texture = vector.rasterize(vector.size().ceil().as_uvec2())?;
+                    return Ok(CostumeSet {
+                        vector: Some(vector),
+                        ..CostumeSet::still(Costume::new(&texture, atlas).named(&name))
+                    });
+                }
+                let frames = load_frames(path)?;
+                let delays = match frames.len() {
+                    1 => vec![],
+                    _ => frames.iter().map(|(_, delay)| *delay).collect(),
+                };
+                let set = CostumeSet {
+                    frames: frames
+                        .iter()
+                        .map(|(frame, _)| Costume::new(frame, atlas))
+                        .collect(),
+                    delays,
+                    ..CostumeSet::default()
+                };
+                Ok(CostumeSet {
+                    frames: set.named(&name),
+                    ..set
+                })
+            },
+        )
+    }
+
+    pub fn sound(&mut self, path: &Path) -> Result<StaticSoundData, String> {
+        let file = canonical_path(path);
+        if let Some(sound) = self.sounds.get(&file) {
+            return Ok(sound.clone());
+        }
+        let sound = StaticSoundData::from_file(&file)
+            .map_err(|e| format!("Failed to load sound {}: {}", path.display(), e))?;
+        self.sounds.insert(file, sound.clone());
+        Ok(sound)
+    }
+
+    // A font whose image is `path`. `make` builds it from the uploaded image the first time.
+    pub fn font(
+        &mut self,
+        path: &Path,
+        make: impl FnOnce(GPUTexture) -> BitmapFont,
+    ) -> Result<Arc<BitmapFont>, String> {
+        let file = canonical_path(path);
+        if let Some(font) = self.fonts.get(&file) {
+            return Ok(font.clone());
+        }
+        let texture = load_image(path)?;
+        let font = Arc::new(make(texture.upload_to_gpu()));
+        self.fonts.insert(file, font.clone());
+        Ok(font)
+    }
+
+    // Forgets everything loaded from the files, so they are loaded again the next time they are
+    // asked for. Handles that were already handed out keep the old version.
+    pub fn forget(&mut self, files: &[PathBuf]) {
+        let files: Vec<PathBuf> = files.iter().map(|f| canonical_path(f)).collect();
+        self.costumes
+            .retain(|_, entry| !entry.files.iter().any(|f| files.contains(f)));
+        self.sounds.retain(|file, _| !files.contains(file));
+        self.fonts.retain(|file, _| !files.contains(file));
+    }
+
+    // Drops the assets that nothing holds a handle to anymore
+    pub fn prune(&mut self) {
+        self.costumes.retain(|_, entry| entry.set.users() > 0);
+        self.sounds
+            .retain(|_, sound| Arc::strong_count(&sound.frames) > 1);
+        self.fonts.retain(|_, font| Arc::strong_count(font) > 1);
+    }
+
+    // How many files are loaded
+    pub fn count(&self) -> usize {
+        self.costumes.len() + self.sounds.len() + self.fonts.len()
+    }
+}
+
+// One frame for a still image, or every frame of an animated GIF or PNG, with how many seconds
+// each frame is shown
+pub fn load_frames(path: &Path) -> Result<Vec<(CPUTexture, f32)>, String> {
+    match load_animated(path)? {
+        Some(frames) => Ok(frames),
+        None => Ok(vec![(load_image(path)?, 0.0)]),
+    }
+}
+
+pub fn load_vector(path: &Path) -> Result<VectorImage, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    VectorImage::parse(&content).map_err(|e| format!("{} ({})", e, path.display()))
+}
+
+pub fn load_image(path: &Path) -> Result<CPUTexture, String> {
+    if path.extension().is_some_and(|e| e == "svg") {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        rasterize_svg(&content)
+    } else {
+        CPUTexture::load_from_file(&path.to_string_lossy())
+            .map_err(|e| format!("{} ({})", e, path.display()))
+    }
+}
@@ -0,0 +1,280 @@
+use glfw::{Context, WindowMode};
+use kira::AudioManager;
+use kira::sound::static_sound::{StaticSoundData, StaticSoundHandle};
+
+use crate::utils::{InputManager, string_to_keycode, string_to_mouse};
+
+// Everything the runtime needs from a window. Implemented by a real GLFW window, and by a
+// virtual one for headless runs (CI, batch simulations) where there is no display at all.
+pub trait WindowBackend {
+    fn get_size(&self) -> (i32, i32);
+    fn set_size(&mut self, width: i32, height: i32);
+    fn get_pos(&self) -> (i32, i32);
+    fn set_pos(&mut self, x: i32, y: i32);
+    fn get_framebuffer_size(&self) -> (i32, i32);
+    fn get_cursor_pos(&self) -> (f64, f64);
+    fn set_cursor_grabbed(&mut self, grabbed: bool);
+    fn set_fullscreen(&mut self, fullscreen: bool);
+    fn should_close(&self) -> bool;
+    fn set_should_close(&mut self, value: bool);
+    fn poll_events(&mut self, input_manager: &mut InputManager);
+    fn swap_buffers(&mut self);
+    fn is_headless(&self) -> bool;
+}
+
+pub struct GlfwWindow {
+    pub glfw: glfw::Glfw,
+    pub window: glfw::PWindow,
+    pub events: glfw::GlfwReceiver<(f64, glfw::WindowEvent)>,
+}
+
+impl WindowBackend for GlfwWindow {
+    fn get_size(&self) -> (i32, i32) {
+        self.window.get_size()
+    }
+
+    fn set_size(&mut self, width: i32, height: i32) {
+        self.window.set_size(width, height);
+    }
+
+    fn get_pos(&self) -> (i32, i32) {
+        self.window.get_pos()
+    }
+
+    fn set_pos(&mut self, x: i32, y: i32) {
+        self.window.set_pos(x, y);
+    }
+
+    fn get_framebuffer_size(&self) -> (i32, i32) {
+        self.window.get_framebuffer_size()
+    }
+
+    fn get_cursor_pos(&self) -> (f64, f64) {
+        self.window.get_cursor_pos()
+    }
+
+    fn set_cursor_grabbed(&mut self, grabbed: bool) {
+        self.window.set_cursor_mode(if grabbed {
+            glfw::CursorMode::Disabled
+        } else {
+            glfw::CursorMode::Normal
+        });
+    }
+
+    fn set_fullscreen(&mut self, fullscreen: bool) {
+        let (xpos, ypos) = self.window.get_pos();
+        let (width, height) = self.window.get_size();
+        let window = &mut self.window;
+        if fullscreen {
+            self.glfw.with_primary_monitor(|_, m| {
+                let mode = match m {
+                    Some(m) => WindowMode::FullScreen(m),
+                    None => WindowMode::Windowed,
+                };
+                window.set_monitor(mode, xpos, ypos, width as u32, height as u32, None)
+            });
+        } else {
+            window.set_monitor(
+                WindowMode::Windowed,
+                xpos,
+                ypos,
+                width as u32,
+                height as u32,
+                None,
+            );
+        }
+    }
+
+    fn should_close(&self) -> bool {
+        self.window.should_close()
+    }
+
+    fn set_should_close(&mut self, value: bool) {
+        self.window.set_should_close(value);
+    }
+
+    fn poll_events(&mut self, input_manager: &mut InputManager) {
+        self.glfw.poll_events();
+        input_manager.begin_frame();
+
+        for (_, event) in glfw::flush_messages(&self.events) {
+            match event {
+                glfw::WindowEvent::Key(key, _, action, _) => match action {
+                    glfw::Action::Press => input_manager.press_key(key),
+                    glfw::Action::Release => input_manager.release_key(key),
+                    _ => {}
+                },
+                glfw::WindowEvent::Char(c) => input_manager.type_char(c),
+                glfw::WindowEvent::MouseButton(button, action, _) => match action {
+                    glfw::Action::Press => input_manager.press_mouse_button(button),
+                    glfw::Action::Release => input_manager.release_mouse_button(button),
+                    _ => {}
+                },
+                glfw::WindowEvent::Close => {
+                    self.window.set_should_close(true);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn swap_buffers(&mut self) {
+        self.window.swap_buffers();
+    }
+
+    fn is_headless(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ScriptedInput {
+    KeyDown(glfw::Key),
+    KeyUp(glfw::Key),
+    MouseDown(glfw::MouseButton),
+    MouseUp(glfw::MouseButton),
+    MouseMove(f64, f64),
+}
+
+// A window that only exists in memory. Input comes from a script of `(frame, event)` pairs,
+// and the window asks to be closed once it has run for `max_frames` frames.
+pub struct HeadlessWindow {
+    size: (i32, i32),
+    pos: (i32, i32),
+    cursor: (f64, f64),
+    frame: u32,
+    max_frames: Option<u32>,
+    should_close: bool,
+    input_script: Vec<(u32, ScriptedInput)>,
+}
+
+impl HeadlessWindow {
+    pub fn new(width: i32, height: i32, max_frames: Option<u32>) -> Self {
+        Self {
+            size: (width, height),
+            pos: (0, 0),
+            cursor: (width as f64 / 2.0, height as f64 / 2.0),
+            frame: 0,
+            max_frames,
+            should_close: false,
+            input_script: vec![],
+        }
+    }
+
+    // Parses an input script. Every line is `<frame> <event> [args...]`, where the event is
+    // one of `key_down`, `key_up`, `mouse_down`, `mouse_up` or `mouse_move`. Lines starting
+    // with `#` are comments.
+    pub fn load_input_script(&mut self, source: &str) -> Result<(), String> {
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parts = line.split_whitespace().collect::<Vec<_>>();
+            let error = |message: &str| format!("Input script line {}: {}", i + 1, message);
+            let frame = parts[0]
+                .parse::<u32>()
+                .map_err(|_| error("expected a frame number"))?;
+            let input = match parts[1..] {
+                ["key_down", key] => ScriptedInput::KeyDown(
+                    string_to_keycode(key).ok_or_else(|| error("invalid key"))?,
+                ),
+                ["key_up", key] => ScriptedInput::KeyUp(
+                    string_to_keycode(key).ok_or_else(|| error("invalid key"))?,
+                ),
+                ["mouse_down", button] => ScriptedInput::MouseDown(
+                    string_to_mouse(button).ok_or_else(|| error("invalid mouse button"))?,
+                ),
+                ["mouse_up", button] => ScriptedInput::MouseUp(
+                    string_to_mouse(button).ok_or_else(|| error("invalid mouse button"))?,
+                ),
+                ["mouse_move", x, y] => ScriptedInput::MouseMove(
+                    x.parse().map_err(|_| error("invalid x position"))?,
+                    y.parse().map_err(|_| error("invalid y position"))?,
+                ),
+                _ => return Err(error("unknown event")),
+            };
+            self.input_script.push((frame, input));
+        }
+        Ok(())
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+}
+
+impl WindowBackend for HeadlessWindow {
+    fn get_size(&self) -> (i32, i32) {
+        self.size
+    }
+
+    fn set_size(&mut self, width: i32, height: i32) {
+        self.size = (width, height);
+    }
+
+    fn get_pos(&self) -> (i32, i32) {
+        self.pos
+    }
+
+    fn set_pos(&mut self, x: i32, y: i32) {
+        self.pos = (x, y);
+    }
+
+    fn get_framebuffer_size(&self) -> (i32, i32) {
+        self.size
+    }
+
+    fn get_cursor_pos(&self) -> (f64, f64) {
+        self.cursor
+    }
+
+    fn set_cursor_grabbed(&mut self, _grabbed: bool) {}
+
+    fn set_fullscreen(&mut self, _fullscreen: bool) {}
+
+    fn should_close(&self) -> bool {
+        self.should_close || self.max_frames.is_some_and(|max| self.frame >= max)
+    }
+
+    fn set_should_close(&mut self, value: bool) {
+        self.should_close = value;
+    }
+
+    fn poll_events(&mut self, input_manager: &mut InputManager) {
+        input_manager.begin_frame();
+
+        for (frame, input) in &self.input_script {
+            if *frame != self.frame {
+                continue;
+            }
+            match input {
+                ScriptedInput::KeyDown(key) => input_manager.press_key(*key),
+                ScriptedInput::KeyUp(key) => input_manager.release_key(*key),
+                ScriptedInput::MouseDown(button) => input_manager.press_mouse_button(*button),
+                ScriptedInput::MouseUp(button) => input_manager.release_mouse_button(*button),
+                ScriptedInput::MouseMove(x, y) => self.cursor = (*x, *y),
+            }
+        }
+
+        self.frame += 1;
+    }
+
+    fn swap_buffers(&mut self) {}
+
+    fn is_headless(&self) -> bool {
+        true
+    }
+}
+
+pub trait AudioBackend {
+    fn play(&mut self, sound: StaticSoundData) -> Result<StaticSoundHandle, String>;
+}
+
+// Covers both the real audio device and kira's mock backend, which is used when running
+// headless so no sound card is needed.
+impl<B: kira::backend::Backend> AudioBackend for AudioManager<B> {
+    fn play(&mut self, sound: StaticSoundData) -> Result<StaticSoundHandle, String> {
+        AudioManager::play(self, sound).map_err(|e| e.to_string())
+    }
+}
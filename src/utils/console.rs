@@ -0,0 +1,259 @@
+use glam::*;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::utils::{InputManager, core::*, draw_rectangle};
+
+const MAX_LOG_ENTRIES: usize = 1000;
+const FONT_SIZE: f32 = 16.0;
+const LINE_HEIGHT: f32 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub level: LogLevel,
+    pub source: Option<String>,
+    pub text: String,
+}
+
+// Everything the game prints goes through here, so it ends up both in the terminal and in the
+// in-game console. It's global because parse errors and such happen where no project is around.
+static LOG: Mutex<VecDeque<LogEntry>> = Mutex::new(VecDeque::new());
+// Errors logged since `capture_errors`, so the test runner can fail a test that ran into one
+static CAPTURED_ERRORS: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+pub fn log(level: LogLevel, source: Option<&str>, text: impl Into<String>) {
+    let text = text.into();
+    let line = match source {
+        Some(source) => format!("{} => {}", source, text),
+        None => text.clone(),
+    };
+    match level {
+        LogLevel::Info => println!("{}", line),
+        LogLevel::Warning | LogLevel::Error => eprintln!("{}", line),
+    }
+    if level == LogLevel::Error
+        && let Some(errors) = CAPTURED_ERRORS.lock().unwrap().as_mut()
+    {
+        errors.push(line);
+    }
+
+    let mut log = LOG.lock().unwrap();
+    for text in text.lines() {
+        if log.len() >= MAX_LOG_ENTRIES {
+            log.pop_front();
+        }
+        log.push_back(LogEntry {
+            level,
+            source: source.map(str::to_string),
+            text: text.replace('\t', "    "),
+        });
+    }
+}
+
+pub fn info(source: Option<&str>, text: impl Into<String>) {
+    log(LogLevel::Info, source, text);
+}
+
+pub fn warning(source: Option<&str>, text: impl Into<String>) {
+    log(LogLevel::Warning, source, text);
+}
+
+pub fn error(source: Option<&str>, text: impl Into<String>) {
+    log(LogLevel::Error, source, text);
+}
+
+pub fn clear_log() {
+    LOG.lock().unwrap().clear();
+}
+
+pub fn capture_errors() {
+    *CAPTURED_ERRORS.lock().unwrap() = Some(vec![]);
+}
+
+// Stops capturing, and returns the errors logged since `capture_errors`
+pub fn take_captured_errors() -> Vec<String> {
+    CAPTURED_ERRORS.lock().unwrap().take().unwrap_or_default()
+}
+
+// The in-game console, opened with the key left of `1`. It shows the log and evaluates Crust
+// expressions in one of the sprites.
+pub struct Console {
+    pub open: bool,
+    pub target: Option<String>,
+    input: String,
+    history: Vec<String>,
+    history_index: Option<usize>,
+    scroll: usize,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            target: None,
+            input: String::new(),
+            history: vec![],
+            history_index: None,
+            scroll: 0,
+        }
+    }
+
+    // Handles the console's keys and returns an expression to evaluate in the target sprite.
+    // While the console is open, the game doesn't see the keyboard.
+    pub fn update(&mut self, input_manager: &mut InputManager) -> Option<String> {
+        use glfw::Key;
+
+        if input_manager.is_key_pressed(Key::GraveAccent) {
+            self.open = !self.open;
+            input_manager.clear_keys();
+            return None;
+        }
+        if !self.open {
+            return None;
+        }
+
+        let mut command = None;
+        self.input
+            .extend(input_manager.typed().chars().filter(|c| *c != '`'));
+        if input_manager.is_key_pressed(Key::Backspace) {
+            self.input.pop();
+        }
+        if input_manager.is_key_pressed(Key::Escape) {
+            self.open = false;
+        }
+        if input_manager.is_key_pressed(Key::Up) && !self.history.is_empty() {
+            let index = self
+                .history_index
+                .map_or(self.history.len() - 1, |i| i.saturating_sub(1));
+            self.history_index = Some(index);
+            self.input = self.history[index].clone();
+        }
+        if input_manager.is_key_pressed(Key::Down) {
+            match self.history_index {
+                Some(i) if i + 1 < self.history.len() => {
+                    self.history_index = Some(i + 1);
+                    self.input = self.history[i + 1].clone();
+                }
+                _ => {
+                    self.history_index = None;
+                    self.input.clear();
+                }
+            }
+        }
+        if input_manager.is_key_pressed(Key::PageUp) {
+            self.scroll += 5;
+        }
+        if input_manager.is_key_pressed(Key::PageDown) {
+            self.scroll = self.scroll.saturating_sub(5);
+        }
+        if input_manager.is_key_pressed(Key::Enter) {
+            let input = std::mem::take(&mut self.input);
+            let input = input.trim();
+            if !input.is_empty() {
+                self.history.push(input.to_string());
+                self.history_index = None;
+                self.scroll = 0;
+                command = self.run_command(input);
+            }
+        }
+
+        input_manager.clear_keys();
+        command
+    }
+
+    fn run_command(&mut self, input: &str) -> Option<String> {
+        let target = self.target.as_deref();
+        match input.split_once(' ').unwrap_or((input, "")) {
+            (":sprite", "") => info(
+                None,
+                format!("Evaluating in {}", target.unwrap_or("nothing")),
+            ),
+            (":sprite", name) => {
+                self.target = Some(name.trim().to_string());
+                info(None, format!("Evaluating in {}", name.trim()));
+            }
+            (":clear", _) => clear_log(),
+            (":help", _) => info(
+                None,
+                "Type a Crust expression to evaluate it in the current sprite\n\
+                 :sprite NAME  evaluate in another sprite\n\
+                 :clear        clear the log\n\
+                 Up/Down go through the history, PageUp/PageDown scroll the log",
+            ),
+            _ if input.starts_with(':') => error(None, format!("Unknown command '{}'", input)),
+            _ => {
+                info(target, format!("> {}", input));
+                return Some(input.to_string());
+            }
+        }
+        None
+    }
+
+    pub fn draw(
+        &self,
+        font: &BitmapFont,
+        shader: &ShaderProgram,
+        projection: Mat4,
+        window_size: (i32, i32),
+    ) {
+        if !self.open {
+            return;
+        }
+        let width = window_size.0 as f32;
+        let height = (window_size.1 as f32 / 2.0).max(LINE_HEIGHT * 3.0);
+
+        shader.use_program();
+        shader.set_uniform("u_projection", projection);
+        // The projection is flipped (y points down), so the corners are swapped to keep the
+        // rectangle facing the camera
+        draw_rectangle(
+            vec2(0.0, height),
+            vec2(width, 0.0),
+            shader,
+            vec4(0.0, 0.0, 0.0, 0.8),
+        );
+
+        let log = LOG.lock().unwrap();
+        let rows = ((height - LINE_HEIGHT * 1.5) / LINE_HEIGHT).max(0.0) as usize;
+        let end = log.len().saturating_sub(self.scroll.min(log.len()));
+        let start = end.saturating_sub(rows);
+        for (row, entry) in log.range(start..end).enumerate() {
+            let text = match &entry.source {
+                Some(source) => format!("[{}] {}", source, entry.text),
+                None => entry.text.clone(),
+            };
+            let color = match entry.level {
+                LogLevel::Info => vec4(1.0, 1.0, 1.0, 1.0),
+                LogLevel::Warning => vec4(1.0, 0.85, 0.2, 1.0),
+                LogLevel::Error => vec4(1.0, 0.35, 0.35, 1.0),
+            };
+            draw_text(TextParams {
+                text: &text,
+                projection,
+                pos: vec2(8.0, 6.0 + row as f32 * LINE_HEIGHT),
+                font_size: FONT_SIZE,
+                down_positive: true,
+                color,
+                ..TextParams::default_params(font, shader)
+            });
+        }
+
+        let prompt = format!("{}> {}_", self.target.as_deref().unwrap_or(""), self.input);
+        draw_text(TextParams {
+            text: &prompt,
+            projection,
+            pos: vec2(8.0, height - LINE_HEIGHT),
+            font_size: FONT_SIZE,
+            down_positive: true,
+            color: vec4(0.6, 1.0, 0.6, 1.0),
+            ..TextParams::default_params(font, shader)
+        });
+    }
+}
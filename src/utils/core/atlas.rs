@@ -0,0 +1,115 @@
+use glam::*;
+use std::sync::Arc;
+
+use crate::utils::core::{CPUTexture, GPUTexture};
+
+// The largest width and height of an atlas page. Bigger textures get a page of their own.
+const PAGE_SIZE: u32 = 2048;
+// Transparent pixels left between the textures on a page
+const PADDING: u32 = 1;
+
+// A texture's rectangle on an atlas page. Everything on the same page can be drawn with one
+// draw call.
+#[derive(Debug, Clone)]
+pub struct AtlasRegion {
+    page: Arc<GPUTexture>,
+    corner: UVec2,
+    size: UVec2,
+}
+
+impl AtlasRegion {
+    // A texture on a page of its own
+    pub fn single(texture: &CPUTexture) -> Self {
+        Self {
+            page: Arc::new(GPUTexture::new(
+                texture.width,
+                texture.height,
+                &texture.bytes(),
+            )),
+            corner: UVec2::ZERO,
+            size: uvec2(texture.width, texture.height),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.size.x
+    }
+
+    pub fn height(&self) -> u32 {
+        self.size.y
+    }
+
+    pub fn page(&self) -> &GPUTexture {
+        &self.page
+    }
+
+    pub fn same_page(&self, other: &AtlasRegion) -> bool {
+        Arc::ptr_eq(&self.page, &other.page)
+    }
+
+    pub fn bind(&self) {
+        self.page.bind();
+    }
+
+    // The corner (xy) and size (zw) of the region in the page's texture coordinates
+    pub fn rect(&self) -> Vec4 {
+        let page = vec2(self.page.width() as f32, self.page.height() as f32).max(Vec2::ONE);
+        let corner = self.corner.as_vec2() / page;
+        let size = self.size.as_vec2() / page;
+        vec4(corner.x, corner.y, size.x, size.y)
+    }
+
+    // Replaces the pixels in place. A texture of another size doesn't fit, so it's packed into
+    // the atlas again, and still draws in the same batch as the rest.
+    pub fn replace(&mut self, texture: &CPUTexture, atlas: &mut Atlas) {
+        if uvec2(texture.width, texture.height) == self.size {
+            self.page
+                .update_region(self.corner, texture.width, texture.height, &texture.bytes());
+        } else {
+            *self = atlas.add(texture);
+        }
+    }
+}
+
+// Packs textures onto shared pages as they are added, in rows. A full page is freed once nothing
+// on it is used anymore. Space isn't reused before that, so a texture replaced by one of another
+// size leaves a hole behind.
+#[derive(Debug, Default)]
+pub struct Atlas {
+    page: Option<Arc<GPUTexture>>,
+    cursor: UVec2,
+    row_height: u32,
+}
+
+impl Atlas {
+    pub fn add(&mut self, texture: &CPUTexture) -> AtlasRegion {
+        let size = uvec2(texture.width, texture.height) + PADDING;
+        if size.x > PAGE_SIZE || size.y > PAGE_SIZE {
+            return AtlasRegion::single(texture);
+        }
+        if self.cursor.x + size.x > PAGE_SIZE {
+            self.cursor = uvec2(0, self.cursor.y + self.row_height);
+            self.row_height = 0;
+        }
+        let page = match &self.page {
+            Some(page) if self.cursor.y + size.y <= PAGE_SIZE => page.clone(),
+            _ => {
+                let empty = vec![0; (PAGE_SIZE * PAGE_SIZE * 4) as usize];
+                let page = Arc::new(GPUTexture::new(PAGE_SIZE, PAGE_SIZE, &empty));
+                self.page = Some(page.clone());
+                self.cursor = UVec2::ZERO;
+                self.row_height = 0;
+                page
+            }
+        };
+        page.update_region(self.cursor, texture.width, texture.height, &texture.bytes());
+        let region = AtlasRegion {
+            page,
+            corner: self.cursor,
+            size: uvec2(texture.width, texture.height),
+        };
+        self.cursor.x += size.x;
+        self.row_height = self.row_height.max(size.y);
+        region
+    }
+}
@@ -0,0 +1,13 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static DRAW_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+// Counted by `Mesh::draw` whatever the renderer, so headless runs can still report how many
+// draw calls a frame would take
+pub fn record_draw_call() {
+    DRAW_CALLS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn draw_calls() -> usize {
+    DRAW_CALLS.load(Ordering::Relaxed)
+}
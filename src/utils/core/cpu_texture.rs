@@ -3,6 +3,7 @@ use image::GenericImageView;
 
 use crate::utils::core::GPUTexture;
 
+#[derive(Debug, Clone)]
 pub struct CPUTexture {
     pub width: u32,
     pub height: u32,
@@ -43,6 +44,19 @@ impl CPUTexture {
         })
     }
 
+    pub fn from_image(image: image::RgbaImage) -> Self {
+        let (width, height) = image.dimensions();
+        let data = image
+            .pixels()
+            .map(|p| U8Vec4::new(p[0], p[1], p[2], p[3]))
+            .collect();
+        CPUTexture {
+            width,
+            height,
+            data,
+        }
+    }
+
     pub fn load_from_bytes(bytes: &[u8], width: u32, height: u32) -> Result<Self, String> {
         if bytes.len() != (width * height * 4) as usize {
             return Err("Byte length does not match width and height".to_string());
@@ -58,16 +72,43 @@ impl CPUTexture {
         })
     }
 
-    pub fn upload_to_gpu(self) -> GPUTexture {
-        let texture = GPUTexture::new(
-            self.width,
-            self.height,
-            self.data
-                .iter()
-                .flat_map(|c| [c.x, c.y, c.z, c.w])
-                .collect::<Vec<_>>()
-                .as_slice(),
-        );
+    // A copy of a rectangle of the texture. The parts outside of the texture are transparent.
+    pub fn crop(&self, corner: glam::UVec2, size: glam::UVec2) -> Self {
+        let mut texture = CPUTexture::new(size.x, size.y);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let (sx, sy) = (corner.x + x, corner.y + y);
+                if sx < self.width && sy < self.height {
+                    texture.data[(y * size.x + x) as usize] =
+                        self.data[(sy * self.width + sx) as usize];
+                }
+            }
+        }
         texture
     }
+
+    // Copies `other` onto the texture with its top-left corner at `corner`, replacing the pixels
+    pub fn blit(&mut self, other: &CPUTexture, corner: glam::UVec2) {
+        for y in 0..other.height {
+            for x in 0..other.width {
+                let (dx, dy) = (corner.x + x, corner.y + y);
+                if dx < self.width && dy < self.height {
+                    self.data[(dy * self.width + dx) as usize] =
+                        other.data[(y * other.width + x) as usize];
+                }
+            }
+        }
+    }
+
+    // The pixels as RGBA bytes, row by row, like OpenGL takes them
+    pub fn bytes(&self) -> Vec<u8> {
+        self.data
+            .iter()
+            .flat_map(|c| [c.x, c.y, c.z, c.w])
+            .collect()
+    }
+
+    pub fn upload_to_gpu(self) -> GPUTexture {
+        GPUTexture::new(self.width, self.height, &self.bytes())
+    }
 }
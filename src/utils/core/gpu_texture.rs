@@ -1,33 +1,21 @@
+use glam::UVec2;
+
+use crate::utils::core::{release, with_renderer};
+
 #[derive(Debug, Clone)]
 pub struct GPUTexture(pub(super) u32, pub(super) u32, pub(super) u32);
 
 impl GPUTexture {
     pub fn new(width: u32, height: u32, image: &[u8]) -> Self {
-        let mut id = 0;
-        unsafe {
-            gl::GenTextures(1, &mut id);
-            gl::BindTexture(gl::TEXTURE_2D, id);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
-            gl::TexImage2D(
-                gl::TEXTURE_2D,
-                0,
-                gl::RGBA as i32,
-                width as i32,
-                height as i32,
-                0,
-                gl::RGBA,
-                gl::UNSIGNED_BYTE,
-                image.as_ptr() as *const _,
-            );
-            gl::GenerateMipmap(gl::TEXTURE_2D);
-            gl::BindTexture(gl::TEXTURE_2D, 0);
-        }
+        let id = with_renderer(|r| r.create_texture(width, height, image));
         GPUTexture(width, height, id)
     }
 
+    // Replaces the pixels of a rectangle of the texture, which must fit inside it
+    pub fn update_region(&self, corner: UVec2, width: u32, height: u32, image: &[u8]) {
+        with_renderer(|r| r.update_texture(self.2, corner, width, height, image));
+    }
+
     pub fn width(&self) -> u32 {
         self.0
     }
@@ -37,16 +25,12 @@ impl GPUTexture {
     }
 
     pub fn bind(&self) {
-        unsafe {
-            gl::BindTexture(gl::TEXTURE_2D, self.2);
-        }
+        with_renderer(|r| r.bind_texture(self.2));
     }
 }
 
 impl Drop for GPUTexture {
     fn drop(&mut self) {
-        unsafe {
-            gl::DeleteTextures(1, &self.2);
-        }
+        release(|r| r.delete_texture(self.2));
     }
 }
@@ -1,17 +1,20 @@
 use std::marker::PhantomData;
+use std::mem::offset_of;
 
 use glam::*;
 
-macro_rules! offset_of {
-    ($ty:ty, $field:ident) => {{
-        let base = std::ptr::null::<$ty>();
-        let field = std::ptr::addr_of!((*base).$field);
-        field as usize - base as usize
-    }};
+use crate::utils::core::{MeshHandle, record_draw_call, release, with_renderer};
+
+// One attribute of a vertex format: its location in the shader, how many floats it has and
+// where in the vertex it starts
+pub struct VertexAttribute {
+    pub location: u32,
+    pub floats: i32,
+    pub offset: usize,
 }
 
 pub trait VertexFormat {
-    fn setup_attribs();
+    const ATTRIBUTES: &'static [VertexAttribute];
 }
 
 pub struct Vertex {
@@ -20,113 +23,131 @@ pub struct Vertex {
 }
 
 impl VertexFormat for Vertex {
-    fn setup_attribs() {
-        unsafe {
-            gl::VertexAttribPointer(
-                0,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                std::mem::size_of::<Self>() as i32,
-                offset_of!(Self, position) as *const _,
-            );
-            gl::VertexAttribPointer(
-                1,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                std::mem::size_of::<Self>() as i32,
-                offset_of!(Self, uv) as *const _,
-            );
-
-            gl::EnableVertexAttribArray(0);
-            gl::EnableVertexAttribArray(1);
-        }
-    }
+    const ATTRIBUTES: &'static [VertexAttribute] = &[
+        VertexAttribute {
+            location: 0,
+            floats: 2,
+            offset: offset_of!(Self, position),
+        },
+        VertexAttribute {
+            location: 1,
+            floats: 2,
+            offset: offset_of!(Self, uv),
+        },
+    ];
+}
+
+// A vertex with its own color, for batches where every quad is tinted differently. The shader
+// only uses the color while `u_vertex_color` is set.
+pub struct ColorVertex {
+    pub position: Vec2,
+    pub uv: Vec2,
+    pub color: Vec4,
+}
+
+impl VertexFormat for ColorVertex {
+    const ATTRIBUTES: &'static [VertexAttribute] = &[
+        VertexAttribute {
+            location: 0,
+            floats: 2,
+            offset: offset_of!(Self, position),
+        },
+        VertexAttribute {
+            location: 1,
+            floats: 2,
+            offset: offset_of!(Self, uv),
+        },
+        VertexAttribute {
+            location: 2,
+            floats: 4,
+            offset: offset_of!(Self, color),
+        },
+    ];
 }
 
+// A vertex of a batch of sprites, already moved into place. Every vertex of a sprite carries
+// the rectangle of its costume on the atlas page and the values of its effects, which the
+// shader only uses while `u_batched` is set.
+pub struct SpriteVertex {
+    pub position: Vec2,
+    pub uv: Vec2,
+    pub region: Vec4,
+    pub effects: [Vec4; 2],
+}
+
+impl VertexFormat for SpriteVertex {
+    const ATTRIBUTES: &'static [VertexAttribute] = &[
+        VertexAttribute {
+            location: 0,
+            floats: 2,
+            offset: offset_of!(Self, position),
+        },
+        VertexAttribute {
+            location: 1,
+            floats: 2,
+            offset: offset_of!(Self, uv),
+        },
+        VertexAttribute {
+            location: 3,
+            floats: 4,
+            offset: offset_of!(Self, region),
+        },
+        VertexAttribute {
+            location: 4,
+            floats: 4,
+            offset: offset_of!(Self, effects),
+        },
+        VertexAttribute {
+            location: 5,
+            floats: 4,
+            offset: offset_of!(Self, effects) + std::mem::size_of::<Vec4>(),
+        },
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum DrawMode {
-    Triangles = gl::TRIANGLES as isize,
-    // Lines = gl::LINES as isize,
+    Triangles,
 }
 
 pub struct Mesh<T: VertexFormat> {
-    vao: u32,
-    vbo: u32,
-    ebo: u32,
-    draw_mode: u32,
+    handle: MeshHandle,
+    draw_mode: DrawMode,
     vertex_count: usize,
     _marker: PhantomData<T>,
 }
 
 impl<T: VertexFormat> Mesh<T> {
     pub fn new(vertices: &[T], indices: &[u32], draw_mode: DrawMode) -> Self {
-        let mut vao = 0;
-        let mut vbo = 0;
-        let mut ebo = 0;
-
-        unsafe {
-            gl::GenVertexArrays(1, &mut vao);
-            gl::GenBuffers(1, &mut vbo);
-            gl::GenBuffers(1, &mut ebo);
-
-            gl::BindVertexArray(vao);
-
-            // Vertex Buffer
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                (vertices.len() * std::mem::size_of::<T>()) as isize,
-                vertices.as_ptr() as *const _,
-                gl::STATIC_DRAW,
-            );
-
-            // Element Buffer
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
-            gl::BufferData(
-                gl::ELEMENT_ARRAY_BUFFER,
-                (indices.len() * std::mem::size_of::<u32>()) as isize,
-                indices.as_ptr() as *const _,
-                gl::STATIC_DRAW,
-            );
-
-            // Setup vertex attributes
-            T::setup_attribs();
-
-            // Unbind VAO
-            gl::BindVertexArray(0);
-        }
-
-        Mesh::<T> {
-            vao,
-            vbo,
-            ebo,
-            draw_mode: draw_mode as u32,
-            vertex_count: indices.len(),
+        let handle = with_renderer(|r| r.create_mesh(T::ATTRIBUTES, std::mem::size_of::<T>()));
+        let mut mesh = Mesh::<T> {
+            handle,
+            draw_mode,
+            vertex_count: 0,
             _marker: PhantomData,
-        }
+        };
+        mesh.update(vertices, indices);
+        mesh
+    }
+
+    // Replaces the vertices and indices, for meshes that change every frame
+    pub fn update(&mut self, vertices: &[T], indices: &[u32]) {
+        self.vertex_count = indices.len();
+        // The renderer only needs the raw bytes, laid out as `T::ATTRIBUTES` describes
+        let bytes = unsafe {
+            std::slice::from_raw_parts(vertices.as_ptr() as *const u8, size_of_val(vertices))
+        };
+        with_renderer(|r| r.update_mesh(self.handle, bytes, indices));
     }
 
     pub fn draw(&self) {
-        unsafe {
-            gl::BindVertexArray(self.vao);
-            gl::DrawElements(
-                self.draw_mode,
-                self.vertex_count as i32,
-                gl::UNSIGNED_INT,
-                std::ptr::null(),
-            );
-            gl::BindVertexArray(0);
-        }
+        record_draw_call();
+        with_renderer(|r| r.draw_mesh(self.handle, self.draw_mode, self.vertex_count));
     }
 }
 
 impl<T: VertexFormat> Drop for Mesh<T> {
     fn drop(&mut self) {
-        unsafe {
-            gl::DeleteVertexArrays(1, &self.vao);
-            gl::DeleteBuffers(1, &self.vbo);
-            gl::DeleteBuffers(1, &self.ebo);
-        }
+        release(|r| r.delete_mesh(self.handle));
     }
 }
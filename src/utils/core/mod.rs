@@ -1,9 +1,18 @@
+pub mod context;
+pub use context::*;
+
+pub mod renderer;
+pub use renderer::*;
+
 pub mod cpu_texture;
 pub use cpu_texture::*;
 
 pub mod gpu_texture;
 pub use gpu_texture::*;
 
+pub mod atlas;
+pub use atlas::*;
+
 pub mod framebuffer;
 pub use framebuffer::*;
 
@@ -0,0 +1,488 @@
+use glam::*;
+use std::cell::RefCell;
+
+use crate::utils::BlendMode;
+use crate::utils::core::{DrawMode, VertexAttribute};
+
+// Everything the drawing code needs from the GPU. Implemented with OpenGL for a real window, and
+// by a renderer that does nothing for headless runs (CI, batch simulations) where there is no
+// GL context at all. The wrappers in `core` (textures, meshes, shaders, framebuffers) only talk
+// to the GPU through the installed renderer, so the exact same drawing code runs either way.
+pub trait RenderBackend {
+    fn create_texture(&mut self, width: u32, height: u32, pixels: &[u8]) -> u32;
+    // Replaces the pixels of a rectangle of the texture, which must fit inside it
+    fn update_texture(
+        &mut self,
+        texture: u32,
+        corner: UVec2,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    );
+    fn bind_texture(&mut self, texture: u32);
+    fn delete_texture(&mut self, texture: u32);
+
+    fn create_mesh(&mut self, attributes: &[VertexAttribute], stride: usize) -> MeshHandle;
+    fn update_mesh(&mut self, mesh: MeshHandle, vertices: &[u8], indices: &[u32]);
+    fn draw_mesh(&mut self, mesh: MeshHandle, mode: DrawMode, index_count: usize);
+    fn delete_mesh(&mut self, mesh: MeshHandle);
+
+    fn create_program(&mut self, vertex_source: &str, fragment_source: &str) -> u32;
+    fn use_program(&mut self, program: u32);
+    fn set_uniform(&mut self, program: u32, name: &str, value: Uniform);
+    fn delete_program(&mut self, program: u32);
+
+    // A framebuffer drawing into a new texture, which is returned with it
+    fn create_framebuffer(
+        &mut self,
+        width: u32,
+        height: u32,
+        depth: bool,
+    ) -> (FramebufferHandle, u32);
+    // `None` draws to the window again
+    fn bind_framebuffer(&mut self, framebuffer: Option<FramebufferHandle>);
+    fn delete_framebuffer(&mut self, framebuffer: FramebufferHandle);
+
+    fn set_viewport(&mut self, width: i32, height: i32);
+    fn clear(&mut self, color: Vec4);
+    fn set_blend(&mut self, mode: BlendMode);
+    // The RGB pixels of what was drawn, from the bottom row up. `None` if nothing is drawn.
+    fn read_pixels(&mut self, width: i32, height: i32) -> Option<Vec<u8>>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MeshHandle {
+    vao: u32,
+    vbo: u32,
+    ebo: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FramebufferHandle {
+    fbo: u32,
+    depth: Option<u32>,
+}
+
+pub enum Uniform<'a> {
+    Int(i32),
+    Ints(&'a [i32]),
+    Floats(&'a [f32]),
+    Vec4(Vec4),
+    Mat4(Mat4),
+}
+
+thread_local! {
+    static RENDERER: RefCell<Box<dyn RenderBackend>> = RefCell::new(Box::new(HeadlessRenderer));
+}
+
+// Installs the renderer everything is drawn with. Until then it's the headless one, so nothing
+// is drawn (only counted).
+pub fn set_renderer(renderer: Box<dyn RenderBackend>) {
+    RENDERER.with(|r| *r.borrow_mut() = renderer);
+}
+
+pub fn with_renderer<R>(f: impl FnOnce(&mut dyn RenderBackend) -> R) -> R {
+    RENDERER.with(|r| f(r.borrow_mut().as_mut()))
+}
+
+// For `Drop`, which can run at exit after the renderer is gone, with nothing left to free
+pub(super) fn release(f: impl FnOnce(&mut dyn RenderBackend)) {
+    let _ = RENDERER.try_with(|r| {
+        if let Ok(mut renderer) = r.try_borrow_mut() {
+            f(renderer.as_mut());
+        }
+    });
+}
+
+pub struct HeadlessRenderer;
+
+impl RenderBackend for HeadlessRenderer {
+    fn create_texture(&mut self, _width: u32, _height: u32, _pixels: &[u8]) -> u32 {
+        0
+    }
+
+    fn update_texture(&mut self, _: u32, _: UVec2, _: u32, _: u32, _: &[u8]) {}
+
+    fn bind_texture(&mut self, _texture: u32) {}
+
+    fn delete_texture(&mut self, _texture: u32) {}
+
+    fn create_mesh(&mut self, _attributes: &[VertexAttribute], _stride: usize) -> MeshHandle {
+        MeshHandle::default()
+    }
+
+    fn update_mesh(&mut self, _mesh: MeshHandle, _vertices: &[u8], _indices: &[u32]) {}
+
+    fn draw_mesh(&mut self, _mesh: MeshHandle, _mode: DrawMode, _index_count: usize) {}
+
+    fn delete_mesh(&mut self, _mesh: MeshHandle) {}
+
+    fn create_program(&mut self, _vertex_source: &str, _fragment_source: &str) -> u32 {
+        0
+    }
+
+    fn use_program(&mut self, _program: u32) {}
+
+    fn set_uniform(&mut self, _program: u32, _name: &str, _value: Uniform) {}
+
+    fn delete_program(&mut self, _program: u32) {}
+
+    fn create_framebuffer(&mut self, _: u32, _: u32, _: bool) -> (FramebufferHandle, u32) {
+        (FramebufferHandle::default(), 0)
+    }
+
+    fn bind_framebuffer(&mut self, _framebuffer: Option<FramebufferHandle>) {}
+
+    fn delete_framebuffer(&mut self, _framebuffer: FramebufferHandle) {}
+
+    fn set_viewport(&mut self, _width: i32, _height: i32) {}
+
+    fn clear(&mut self, _color: Vec4) {}
+
+    fn set_blend(&mut self, _mode: BlendMode) {}
+
+    fn read_pixels(&mut self, _width: i32, _height: i32) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+pub struct GlRenderer;
+
+impl GlRenderer {
+    // Loads the GL functions of the current context with `loader`, and sets up the state the
+    // rest of the drawing code expects
+    pub fn new(loader: impl FnMut(&str) -> *const std::os::raw::c_void) -> Self {
+        gl::load_with(loader);
+        unsafe {
+            gl::Enable(gl::CULL_FACE);
+            gl::CullFace(gl::BACK);
+            gl::FrontFace(gl::CCW);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+        Self
+    }
+
+    fn compile_shader(shader_type: u32, source: &str) -> u32 {
+        unsafe {
+            let shader = gl::CreateShader(shader_type);
+            let c_str = std::ffi::CString::new(source).unwrap();
+            gl::ShaderSource(shader, 1, &c_str.as_ptr(), std::ptr::null());
+            gl::CompileShader(shader);
+
+            let mut success = 0;
+            gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
+            if success == 0 {
+                let mut v = Vec::<u8>::with_capacity(1024);
+                let mut log_len = 0;
+                gl::GetShaderInfoLog(
+                    shader,
+                    v.capacity() as i32,
+                    &mut log_len,
+                    v.as_mut_ptr().cast(),
+                );
+                v.set_len(log_len as usize);
+                panic!("Shader compilation failed: {}", String::from_utf8_lossy(&v));
+            }
+            shader
+        }
+    }
+}
+
+impl RenderBackend for GlRenderer {
+    fn create_texture(&mut self, width: u32, height: u32, pixels: &[u8]) -> u32 {
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D, id);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA as i32,
+                width as i32,
+                height as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const _,
+            );
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        id
+    }
+
+    fn update_texture(
+        &mut self,
+        texture: u32,
+        corner: UVec2,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                corner.x as i32,
+                corner.y as i32,
+                width as i32,
+                height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const _,
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    fn bind_texture(&mut self, texture: u32) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+        }
+    }
+
+    fn delete_texture(&mut self, texture: u32) {
+        unsafe {
+            gl::DeleteTextures(1, &texture);
+        }
+    }
+
+    fn create_mesh(&mut self, attributes: &[VertexAttribute], stride: usize) -> MeshHandle {
+        let mut mesh = MeshHandle::default();
+        unsafe {
+            gl::GenVertexArrays(1, &mut mesh.vao);
+            gl::GenBuffers(1, &mut mesh.vbo);
+            gl::GenBuffers(1, &mut mesh.ebo);
+
+            gl::BindVertexArray(mesh.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, mesh.vbo);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, mesh.ebo);
+            for attribute in attributes {
+                gl::VertexAttribPointer(
+                    attribute.location,
+                    attribute.floats,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride as i32,
+                    attribute.offset as *const _,
+                );
+                gl::EnableVertexAttribArray(attribute.location);
+            }
+            gl::BindVertexArray(0);
+        }
+        mesh
+    }
+
+    fn update_mesh(&mut self, mesh: MeshHandle, vertices: &[u8], indices: &[u32]) {
+        unsafe {
+            gl::BindVertexArray(mesh.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, mesh.vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                vertices.len() as isize,
+                vertices.as_ptr() as *const _,
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, mesh.ebo);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                std::mem::size_of_val(indices) as isize,
+                indices.as_ptr() as *const _,
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BindVertexArray(0);
+        }
+    }
+
+    fn draw_mesh(&mut self, mesh: MeshHandle, mode: DrawMode, index_count: usize) {
+        let mode = match mode {
+            DrawMode::Triangles => gl::TRIANGLES,
+        };
+        unsafe {
+            gl::BindVertexArray(mesh.vao);
+            gl::DrawElements(mode, index_count as i32, gl::UNSIGNED_INT, std::ptr::null());
+            gl::BindVertexArray(0);
+        }
+    }
+
+    fn delete_mesh(&mut self, mesh: MeshHandle) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &mesh.vao);
+            gl::DeleteBuffers(1, &mesh.vbo);
+            gl::DeleteBuffers(1, &mesh.ebo);
+        }
+    }
+
+    fn create_program(&mut self, vertex_source: &str, fragment_source: &str) -> u32 {
+        let vertex_shader = Self::compile_shader(gl::VERTEX_SHADER, vertex_source);
+        let fragment_shader = Self::compile_shader(gl::FRAGMENT_SHADER, fragment_source);
+        unsafe {
+            let program = gl::CreateProgram();
+            gl::AttachShader(program, vertex_shader);
+            gl::AttachShader(program, fragment_shader);
+            gl::LinkProgram(program);
+            gl::DetachShader(program, vertex_shader);
+            gl::DetachShader(program, fragment_shader);
+            gl::DeleteShader(vertex_shader);
+            gl::DeleteShader(fragment_shader);
+            program
+        }
+    }
+
+    fn use_program(&mut self, program: u32) {
+        unsafe {
+            gl::UseProgram(program);
+        }
+    }
+
+    fn set_uniform(&mut self, program: u32, name: &str, value: Uniform) {
+        let c_str = std::ffi::CString::new(name).unwrap();
+        unsafe {
+            let location = gl::GetUniformLocation(program, c_str.as_ptr());
+            match value {
+                Uniform::Int(value) => gl::Uniform1i(location, value),
+                Uniform::Ints(values) => {
+                    gl::Uniform1iv(location, values.len() as i32, values.as_ptr())
+                }
+                Uniform::Floats(values) => {
+                    gl::Uniform1fv(location, values.len() as i32, values.as_ptr())
+                }
+                Uniform::Vec4(value) => gl::Uniform4f(location, value.x, value.y, value.z, value.w),
+                Uniform::Mat4(value) => {
+                    gl::UniformMatrix4fv(location, 1, gl::FALSE, value.to_cols_array().as_ptr())
+                }
+            }
+        }
+    }
+
+    fn delete_program(&mut self, program: u32) {
+        unsafe {
+            gl::DeleteProgram(program);
+        }
+    }
+
+    fn create_framebuffer(
+        &mut self,
+        width: u32,
+        height: u32,
+        depth: bool,
+    ) -> (FramebufferHandle, u32) {
+        let mut framebuffer = FramebufferHandle::default();
+        let mut texture = 0;
+        unsafe {
+            gl::GenFramebuffers(1, &mut framebuffer.fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer.fbo);
+
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA as i32,
+                width as i32,
+                height as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null(),
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                texture,
+                0,
+            );
+
+            if depth {
+                let mut depth_buffer = 0;
+                gl::GenRenderbuffers(1, &mut depth_buffer);
+                gl::BindRenderbuffer(gl::RENDERBUFFER, depth_buffer);
+                gl::RenderbufferStorage(
+                    gl::RENDERBUFFER,
+                    gl::DEPTH_COMPONENT24,
+                    width as i32,
+                    height as i32,
+                );
+                gl::FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    gl::DEPTH_ATTACHMENT,
+                    gl::RENDERBUFFER,
+                    depth_buffer,
+                );
+                framebuffer.depth = Some(depth_buffer);
+            }
+
+            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+                panic!("Framebuffer is not complete!");
+            }
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        (framebuffer, texture)
+    }
+
+    fn bind_framebuffer(&mut self, framebuffer: Option<FramebufferHandle>) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer.map_or(0, |f| f.fbo));
+        }
+    }
+
+    fn delete_framebuffer(&mut self, framebuffer: FramebufferHandle) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &framebuffer.fbo);
+            if let Some(depth) = framebuffer.depth {
+                gl::DeleteRenderbuffers(1, &depth);
+            }
+        }
+    }
+
+    fn set_viewport(&mut self, width: i32, height: i32) {
+        unsafe {
+            gl::Viewport(0, 0, width, height);
+        }
+    }
+
+    fn clear(&mut self, color: Vec4) {
+        unsafe {
+            gl::ClearColor(color.x, color.y, color.z, color.w);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+        }
+    }
+
+    fn set_blend(&mut self, mode: BlendMode) {
+        let (source, destination) = match mode {
+            BlendMode::Alpha => (gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Add => (gl::SRC_ALPHA, gl::ONE),
+            BlendMode::Multiply => (gl::DST_COLOR, gl::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Screen => (gl::ONE, gl::ONE_MINUS_SRC_COLOR),
+        };
+        unsafe {
+            gl::BlendFunc(source, destination);
+        }
+    }
+
+    fn read_pixels(&mut self, width: i32, height: i32) -> Option<Vec<u8>> {
+        let mut pixels = vec![0; (width * height * 3) as usize];
+        unsafe {
+            gl::ReadPixels(
+                0,
+                0,
+                width,
+                height,
+                gl::RGB,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut _,
+            );
+        }
+        Some(pixels)
+    }
+}
@@ -0,0 +1,593 @@
+use serde_json::{Value as Json, json};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError, channel};
+
+use crate::utils::*;
+
+// The interpreter runs on a single thread, so that's the only thread the editor ever sees
+const THREAD_ID: i64 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StepMode {
+    Run,
+    Pause,
+    StepIn,
+    StepOver(usize),
+    StepOut(usize),
+}
+
+struct Frame {
+    name: String,
+    file: String,
+    line: usize,
+    locals: Vec<(String, Value)>,
+}
+
+enum Variables {
+    Locals(usize),
+    Sprite,
+    Globals,
+    Value(Value),
+}
+
+// A Debug Adapter Protocol server. Editors (VS Code, ...) attach to it over a localhost TCP
+// socket. Requests are read on a separate thread and handled on the interpreter's thread, either
+// once per frame or, while paused, from inside the statement that paused.
+pub struct Debugger {
+    writer: TcpStream,
+    requests: Receiver<Json>,
+    seq: i64,
+    connected: bool,
+    configured: bool,
+    breakpoints: HashMap<String, HashSet<usize>>,
+    pause_on_errors: bool,
+    mode: StepMode,
+    frames: Vec<Frame>,
+    last_location: Option<(String, usize, usize)>,
+    next_call_name: Option<String>,
+    variables: Vec<Variables>,
+}
+
+impl Debugger {
+    // Waits for an editor to attach and finish setting up (breakpoints and such), so even
+    // breakpoints in `setup` are hit
+    pub fn listen(port: u16) -> std::result::Result<Self, String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+        println!("Waiting for a debugger to attach on 127.0.0.1:{}", port);
+        let (stream, _) = listener
+            .accept()
+            .map_err(|e| format!("Failed to accept debugger connection: {}", e))?;
+        let reader = stream.try_clone().map_err(|e| e.to_string())?;
+        let (sender, requests) = channel();
+        std::thread::spawn(move || read_messages(reader, sender));
+
+        let mut debugger = Self {
+            writer: stream,
+            requests,
+            seq: 0,
+            connected: true,
+            configured: false,
+            breakpoints: HashMap::new(),
+            pause_on_errors: true,
+            mode: StepMode::Run,
+            frames: vec![],
+            last_location: None,
+            next_call_name: None,
+            variables: vec![],
+        };
+        while debugger.connected && !debugger.configured {
+            match debugger.requests.recv() {
+                Ok(request) => {
+                    debugger.handle_request(&request, None);
+                }
+                Err(_) => debugger.connected = false,
+            }
+        }
+        println!("Debugger attached");
+        Ok(debugger)
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    // Handles the requests that came in while the game was running
+    pub fn poll(&mut self) {
+        loop {
+            match self.requests.try_recv() {
+                Ok(request) => {
+                    self.handle_request(&request, None);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.connected = false;
+                    break;
+                }
+            }
+        }
+    }
+
+    pub fn finish(&mut self, exit_code: i32) {
+        self.send_event("exited", json!({ "exitCode": exit_code }));
+        self.send_event("terminated", json!({}));
+    }
+
+    fn track(&mut self, state: &State, file: &str, line: usize, depth: usize) {
+        let entering = depth >= self.frames.len();
+        if entering {
+            while self.frames.len() <= depth {
+                let name = self
+                    .next_call_name
+                    .take()
+                    .unwrap_or_else(|| "<closure>".to_string());
+                self.frames.push(Frame {
+                    name,
+                    file: String::new(),
+                    line: 0,
+                    locals: vec![],
+                });
+            }
+        } else {
+            self.frames.truncate(depth + 1);
+            // Whatever was called last (a builtin, or a function without statements) is done
+            self.next_call_name = None;
+        }
+        if depth == 0 {
+            self.frames[0].name = format!(
+                "{} ({})",
+                state.sprite.name,
+                state.sprite.script_name(state.script_id)
+            );
+        }
+        let frame = &mut self.frames[depth];
+        frame.file = file.to_string();
+        frame.line = line;
+        frame.locals = state.local_vars.to_vec();
+    }
+
+    // A statement finished or a script starts, so the next location is hit even if it is the
+    // same as the last one
+    pub fn statement_finished(&mut self) {
+        self.last_location = None;
+    }
+
+    fn pause_reason(&self, file: &str, line: usize, depth: usize, moved: bool) -> Option<&str> {
+        let step = match self.mode {
+            StepMode::Run => false,
+            StepMode::Pause => true,
+            StepMode::StepIn => moved,
+            StepMode::StepOver(from) => moved && depth <= from,
+            StepMode::StepOut(from) => depth < from,
+        };
+        if step {
+            return Some(if self.mode == StepMode::Pause {
+                "pause"
+            } else {
+                "step"
+            });
+        }
+        if moved
+            && self
+                .breakpoints
+                .get(file)
+                .is_some_and(|l| l.contains(&line))
+        {
+            return Some("breakpoint");
+        }
+        None
+    }
+
+    fn pause(&mut self, state: &mut State, reason: &str, text: Option<&str>) {
+        self.mode = StepMode::Run;
+        let mut body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+        if let Some(text) = text {
+            body["text"] = json!(text);
+            body["description"] = json!(text);
+        }
+        self.send_event("stopped", body);
+
+        while self.connected {
+            match self.requests.recv() {
+                Ok(request) => {
+                    if self.handle_request(&request, Some(state)) {
+                        break;
+                    }
+                }
+                Err(_) => self.connected = false,
+            }
+        }
+
+        self.variables.clear();
+        // Time spent paused shouldn't count towards the script's time limit
+        state.project.watchdog.restart_clock();
+    }
+
+    // Returns true when the request resumes execution
+    fn handle_request(&mut self, request: &Json, state: Option<&mut State>) -> bool {
+        let arguments = &request["arguments"];
+        match request["command"].as_str().unwrap_or_default() {
+            "initialize" => {
+                self.respond(
+                    request,
+                    json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsEvaluateForHovers": true,
+                        "exceptionBreakpointFilters": [{
+                            "filter": "errors",
+                            "label": "Runtime errors",
+                            "default": true,
+                        }],
+                    }),
+                );
+                self.send_event("initialized", json!({}));
+            }
+            "launch" | "attach" => self.respond(request, json!({})),
+            "setBreakpoints" => {
+                let path = arguments["source"]["path"].as_str().unwrap_or_default();
+                let file = std::fs::canonicalize(path)
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or(path.to_string());
+                let lines = arguments["breakpoints"]
+                    .as_array()
+                    .map(|b| b.iter().filter_map(|b| b["line"].as_u64()).collect())
+                    .unwrap_or(vec![]);
+                let breakpoints = lines
+                    .iter()
+                    .map(|line| json!({ "verified": true, "line": line }))
+                    .collect::<Vec<_>>();
+                self.breakpoints
+                    .insert(file, lines.into_iter().map(|l| l as usize).collect());
+                self.respond(request, json!({ "breakpoints": breakpoints }));
+            }
+            "setExceptionBreakpoints" => {
+                self.pause_on_errors = arguments["filters"]
+                    .as_array()
+                    .is_some_and(|f| f.iter().any(|f| f == "errors"));
+                self.respond(request, json!({}));
+            }
+            "configurationDone" => {
+                self.configured = true;
+                self.respond(request, json!({}));
+            }
+            "threads" => self.respond(
+                request,
+                json!({ "threads": [{ "id": THREAD_ID, "name": "Crust" }] }),
+            ),
+            "stackTrace" => {
+                let frames = if state.is_some() {
+                    self.frames
+                        .iter()
+                        .enumerate()
+                        .rev()
+                        .map(|(id, frame)| {
+                            let name = std::path::Path::new(&frame.file)
+                                .file_name()
+                                .map(|n| n.to_string_lossy().to_string())
+                                .unwrap_or_default();
+                            json!({
+                                "id": id,
+                                "name": frame.name,
+                                "source": { "name": name, "path": frame.file },
+                                "line": frame.line,
+                                "column": 1,
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                } else {
+                    vec![]
+                };
+                let total = frames.len();
+                self.respond(
+                    request,
+                    json!({ "stackFrames": frames, "totalFrames": total }),
+                );
+            }
+            "scopes" => {
+                let frame = arguments["frameId"].as_u64().unwrap_or(0) as usize;
+                let locals = self.add_variables(Variables::Locals(frame));
+                let sprite = self.add_variables(Variables::Sprite);
+                let globals = self.add_variables(Variables::Globals);
+                self.respond(
+                    request,
+                    json!({ "scopes": [
+                        { "name": "Locals", "variablesReference": locals, "expensive": false },
+                        { "name": "Sprite", "variablesReference": sprite, "expensive": false },
+                        { "name": "Globals", "variablesReference": globals, "expensive": false },
+                    ]}),
+                );
+            }
+            "variables" => {
+                let reference = arguments["variablesReference"].as_u64().unwrap_or(0) as usize;
+                let variables = match state {
+                    Some(state) => self.variables(reference, state),
+                    None => vec![],
+                };
+                self.respond(request, json!({ "variables": variables }));
+            }
+            "evaluate" => {
+                let Some(state) = state else {
+                    self.respond_error(request, "Expressions can only be evaluated while paused");
+                    return false;
+                };
+                let expression = arguments["expression"].as_str().unwrap_or_default();
+                match evaluate(expression, state) {
+                    Ok(value) => {
+                        let reference = self.value_reference(&value);
+                        self.respond(
+                            request,
+                            json!({ "result": display(&value), "variablesReference": reference }),
+                        );
+                    }
+                    Err(e) => self.respond_error(request, &e),
+                }
+            }
+            "continue" => {
+                self.mode = StepMode::Run;
+                self.respond(request, json!({ "allThreadsContinued": true }));
+                return true;
+            }
+            "next" => {
+                self.mode = StepMode::StepOver(self.frames.len().saturating_sub(1));
+                self.respond(request, json!({}));
+                return true;
+            }
+            "stepIn" => {
+                self.mode = StepMode::StepIn;
+                self.respond(request, json!({}));
+                return true;
+            }
+            "stepOut" => {
+                self.mode = StepMode::StepOut(self.frames.len().saturating_sub(1));
+                self.respond(request, json!({}));
+                return true;
+            }
+            "pause" => {
+                if state.is_none() {
+                    self.mode = StepMode::Pause;
+                }
+                self.respond(request, json!({}));
+            }
+            "disconnect" => {
+                self.breakpoints.clear();
+                self.mode = StepMode::Run;
+                self.respond(request, json!({}));
+                self.connected = false;
+                return true;
+            }
+            command => {
+                self.respond_error(request, &format!("Unsupported request '{}'", command));
+            }
+        }
+        false
+    }
+
+    fn add_variables(&mut self, variables: Variables) -> usize {
+        self.variables.push(variables);
+        self.variables.len()
+    }
+
+    fn value_reference(&mut self, value: &Value) -> usize {
+        match value {
+            Value::List(l) if !l.is_empty() => self.add_variables(Variables::Value(value.clone())),
+            Value::Object(o) if !o.is_empty() => {
+                self.add_variables(Variables::Value(value.clone()))
+            }
+            _ => 0,
+        }
+    }
+
+    fn variables(&mut self, reference: usize, state: &State) -> Vec<Json> {
+        let mut values: Vec<(String, Value)> = match self.variables.get(reference.wrapping_sub(1)) {
+            Some(Variables::Locals(frame)) => self
+                .frames
+                .get(*frame)
+                .map(|f| f.locals.clone())
+                .unwrap_or_default(),
+            Some(Variables::Sprite) => {
+                let sprite = &state.sprite;
+                let mut values = vec![
+                    ("x".to_string(), Value::Number(sprite.center.x)),
+                    ("y".to_string(), Value::Number(sprite.center.y)),
+                    ("direction".to_string(), Value::Number(sprite.direction)),
+                    ("scale".to_string(), Value::Number(sprite.scale)),
+                    ("layer".to_string(), Value::Number(sprite.layer as f32)),
+                ];
+                let mut variables = sprite.variables.clone().into_iter().collect::<Vec<_>>();
+                variables.sort_by(|a, b| a.0.cmp(&b.0));
+                values.extend(variables);
+                values
+            }
+            Some(Variables::Globals) => {
+                let mut values = state
+                    .project
+                    .global_variables
+                    .clone()
+                    .into_iter()
+                    .collect::<Vec<_>>();
+                values.sort_by(|a, b| a.0.cmp(&b.0));
+                values
+            }
+            Some(Variables::Value(Value::List(list))) => list
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (format!("[{}]", i), v.clone()))
+                .collect(),
+            Some(Variables::Value(Value::Object(object))) => {
+                let mut values = object.clone().into_iter().collect::<Vec<_>>();
+                values.sort_by(|a, b| a.0.cmp(&b.0));
+                values
+            }
+            _ => vec![],
+        };
+
+        // Later locals shadow earlier ones with the same name (function arguments, loop
+        // variables, ...), so only show the innermost one
+        let mut seen = HashSet::new();
+        values.reverse();
+        values.retain(|(name, _)| seen.insert(name.clone()));
+        values.reverse();
+
+        values
+            .into_iter()
+            .map(|(name, value)| {
+                let reference = self.value_reference(&value);
+                json!({
+                    "name": name,
+                    "value": display(&value),
+                    "type": type_name(&value),
+                    "variablesReference": reference,
+                })
+            })
+            .collect()
+    }
+
+    fn send(&mut self, mut message: Json) {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        let body = message.to_string();
+        let result = write!(
+            self.writer,
+            "Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .and_then(|_| self.writer.flush());
+        if result.is_err() {
+            self.connected = false;
+        }
+    }
+
+    fn respond(&mut self, request: &Json, body: Json) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "success": true,
+            "command": request["command"],
+            "body": body,
+        }));
+    }
+
+    fn respond_error(&mut self, request: &Json, message: &str) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "success": false,
+            "command": request["command"],
+            "message": message,
+        }));
+    }
+
+    fn send_event(&mut self, event: &str, body: Json) {
+        self.send(json!({ "type": "event", "event": event, "body": body }));
+    }
+}
+
+// Called for every statement while a debugger is attached
+pub fn on_location(state: &mut State, file: &str, line: usize) {
+    let Some(mut debugger) = state.project.debugger.take() else {
+        return;
+    };
+    let depth = state.project.watchdog.call_depth();
+    let here = (file.to_string(), line, depth);
+    let moved = debugger.last_location.as_ref() != Some(&here);
+    debugger.track(state, file, line, depth);
+    debugger.last_location = Some(here);
+    if let Some(reason) = debugger.pause_reason(file, line, depth, moved) {
+        let reason = reason.to_string();
+        debugger.pause(state, &reason, None);
+    }
+    state.project.debugger = Some(debugger);
+}
+
+pub fn on_debugger_statement(state: &mut State) {
+    if let Some(mut debugger) = state.project.debugger.take() {
+        debugger.pause(state, "breakpoint", Some("debugger statement"));
+        state.project.debugger = Some(debugger);
+    }
+}
+
+pub fn on_call(state: &mut State, function: &Expression) {
+    if let Some(debugger) = &mut state.project.debugger {
+        debugger.next_call_name = Some(match function {
+            Expression::Identifier(name) => name.clone(),
+            _ => "<closure>".to_string(),
+        });
+    }
+}
+
+pub fn on_error(state: &mut State, message: &str) {
+    if let Some(mut debugger) = state.project.debugger.take() {
+        if debugger.pause_on_errors {
+            debugger.pause(state, "exception", Some(message));
+        }
+        state.project.debugger = Some(debugger);
+    }
+}
+
+// Evaluates a Crust expression in the context of a running script
+pub fn evaluate(expression: &str, state: &mut State) -> std::result::Result<Value, String> {
+    let mut tokenizer = Tokenizer::new(expression.to_string());
+    let tokens = tokenizer.tokenize_full();
+    let mut parser = Parser::new(tokens);
+    let expression = parser.parse_expression()?;
+    Ok(resolve_expression(&expression, state))
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        _ => value.to_string(),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Boolean(_) => "boolean",
+        Value::List(_) => "list",
+        Value::Object(_) => "object",
+        Value::Closure(_) => "closure",
+        Value::Null => "null",
+    }
+}
+
+fn read_messages(stream: TcpStream, sender: Sender<Json>) {
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            match reader.read_line(&mut header) {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+            let header = header.trim();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length:") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+        let Some(length) = length else {
+            continue;
+        };
+        let mut body = vec![0; length];
+        if reader.read_exact(&mut body).is_err() {
+            return;
+        }
+        if let Ok(message) = serde_json::from_slice(&body)
+            && sender.send(message).is_err()
+        {
+            return;
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use glam::*;
 use glfw::{Key, MouseButton};
 
-use crate::utils::{Callable, Expression, Function, State, Value, core::*};
+use crate::utils::{Callable, Expression, Function, State, Value, console, core::*, debugger};
 
 // Helper functions!
 
@@ -122,44 +122,7 @@ pub fn resolve_expression(expr: &Expression, state: &mut State) -> Value {
         } => {
             let left_value = resolve_expression(left, state);
             let right_value = resolve_expression(right, state);
-            match operator.as_str() {
-                "+" => Value::Number(left_value.to_number() + right_value.to_number()),
-                "-" => Value::Number(left_value.to_number() - right_value.to_number()),
-                "*" => Value::Number(left_value.to_number() * right_value.to_number()),
-                "/" => Value::Number(left_value.to_number() / right_value.to_number()),
-                "%" => Value::Number(left_value.to_number() % right_value.to_number()),
-                "**" => Value::Number(left_value.to_number().powf(right_value.to_number())),
-                "==" => Value::Boolean(left_value == right_value),
-                "!=" => Value::Boolean(left_value != right_value),
-                "<" => Value::Boolean(left_value.to_number() < right_value.to_number()),
-                ">" => Value::Boolean(left_value.to_number() > right_value.to_number()),
-                "<=" => Value::Boolean(left_value.to_number() <= right_value.to_number()),
-                ">=" => Value::Boolean(left_value.to_number() >= right_value.to_number()),
-                "&&" => Value::Boolean(left_value.to_boolean() && right_value.to_boolean()),
-                "||" => Value::Boolean(left_value.to_boolean() || right_value.to_boolean()),
-                "in" => Value::Boolean(right_value.to_list().contains(&left_value)),
-                ".." => Value::String(format!(
-                    "{}{}",
-                    left_value.to_string(),
-                    right_value.to_string()
-                )),
-                "^" => Value::Number(
-                    (left_value.to_number() as u32 ^ right_value.to_number() as u32) as f32,
-                ),
-                "&" => Value::Number(
-                    (left_value.to_number() as u32 & right_value.to_number() as u32) as f32,
-                ),
-                "|" => Value::Number(
-                    (left_value.to_number() as u32 | right_value.to_number() as u32) as f32,
-                ),
-                "<<" => Value::Number(
-                    ((left_value.to_number() as u32) << right_value.to_number() as u32) as f32,
-                ),
-                ">>" => Value::Number(
-                    (left_value.to_number() as u32 >> right_value.to_number() as u32) as f32,
-                ),
-                _ => panic!("Unknown operator: {}", operator),
-            }
+            binary_operation(&left_value, operator, &right_value)
         }
         Expression::Unary { operator, operand } => {
             let operand_value = resolve_expression(operand, state);
@@ -179,17 +142,49 @@ pub fn resolve_expression(expr: &Expression, state: &mut State) -> Value {
                 .map(|arg| resolve_expression(arg, state))
                 .collect::<Vec<_>>();
 
-            match func_val {
-                Value::Closure(callable) => callable.call(state, &args).unwrap_or_else(|e| {
-                    println!("Error calling function: {}", e);
-                    Value::Null
-                }),
-                _ => {
-                    println!("Attempted to call non-function: {:?}", func_val);
-                    Value::Null
-                }
-            }
+            call_value(function, &func_val, &args, state)
+        }
+    }
+}
+
+pub fn binary_operation(left_value: &Value, operator: &str, right_value: &Value) -> Value {
+    match operator {
+        "+" => Value::Number(left_value.to_number() + right_value.to_number()),
+        "-" => Value::Number(left_value.to_number() - right_value.to_number()),
+        "*" => Value::Number(left_value.to_number() * right_value.to_number()),
+        "/" => Value::Number(left_value.to_number() / right_value.to_number()),
+        "%" => Value::Number(left_value.to_number() % right_value.to_number()),
+        "**" => Value::Number(left_value.to_number().powf(right_value.to_number())),
+        "==" => Value::Boolean(left_value == right_value),
+        "!=" => Value::Boolean(left_value != right_value),
+        "<" => Value::Boolean(left_value.to_number() < right_value.to_number()),
+        ">" => Value::Boolean(left_value.to_number() > right_value.to_number()),
+        "<=" => Value::Boolean(left_value.to_number() <= right_value.to_number()),
+        ">=" => Value::Boolean(left_value.to_number() >= right_value.to_number()),
+        "&&" => Value::Boolean(left_value.to_boolean() && right_value.to_boolean()),
+        "||" => Value::Boolean(left_value.to_boolean() || right_value.to_boolean()),
+        "in" => Value::Boolean(right_value.to_list().contains(left_value)),
+        ".." => Value::String(format!(
+            "{}{}",
+            left_value.to_string(),
+            right_value.to_string()
+        )),
+        "^" => {
+            Value::Number((left_value.to_number() as u32 ^ right_value.to_number() as u32) as f32)
+        }
+        "&" => {
+            Value::Number((left_value.to_number() as u32 & right_value.to_number() as u32) as f32)
+        }
+        "|" => {
+            Value::Number((left_value.to_number() as u32 | right_value.to_number() as u32) as f32)
+        }
+        "<<" => Value::Number(
+            ((left_value.to_number() as u32) << right_value.to_number() as u32) as f32,
+        ),
+        ">>" => {
+            Value::Number((left_value.to_number() as u32 >> right_value.to_number() as u32) as f32)
         }
+        _ => panic!("Unknown operator: {}", operator),
     }
 }
 
@@ -625,18 +620,45 @@ pub fn percentage_to_decibels(percentage: f32) -> f32 {
 }
 
 pub fn rasterize_svg(content: &str) -> Result<CPUTexture, String> {
-    let svg_tree = resvg::usvg::Tree::from_str(content, &resvg::usvg::Options::default())
-        .map_err(|e| format!("Failed to parse SVG: {}", e))?;
-    let width = svg_tree.size().width() as u32;
-    let height = svg_tree.size().width() as u32;
-    let mut pixmap = resvg::tiny_skia::Pixmap::new(width, height)
-        .ok_or_else(|| "Failed to create pixmap".to_string())?;
-    resvg::render(
-        &svg_tree,
-        resvg::tiny_skia::Transform::default(),
-        &mut pixmap.as_mut(),
-    );
-    Ok(pixmap_to_cpu_texture(&pixmap)?)
+    let image = VectorImage::parse(content)?;
+    image.rasterize(image.size().ceil().as_uvec2())
+}
+
+// A parsed SVG, kept around so it can be rasterised again at whatever size it is drawn at
+#[derive(Clone)]
+pub struct VectorImage(std::sync::Arc<resvg::usvg::Tree>);
+
+impl std::fmt::Debug for VectorImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "VectorImage({:?})", self.size())
+    }
+}
+
+impl VectorImage {
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let tree = resvg::usvg::Tree::from_str(content, &resvg::usvg::Options::default())
+            .map_err(|e| format!("Failed to parse SVG: {}", e))?;
+        Ok(Self(std::sync::Arc::new(tree)))
+    }
+
+    pub fn size(&self) -> Vec2 {
+        let size = self.0.size();
+        vec2(size.width(), size.height())
+    }
+
+    // Stretches the image to fill `size` pixels
+    pub fn rasterize(&self, size: UVec2) -> Result<CPUTexture, String> {
+        let size = size.max(UVec2::ONE);
+        let mut pixmap = resvg::tiny_skia::Pixmap::new(size.x, size.y)
+            .ok_or_else(|| "Failed to create pixmap".to_string())?;
+        let scale = size.as_vec2() / self.size().max(Vec2::splat(f32::EPSILON));
+        resvg::render(
+            &self.0,
+            resvg::tiny_skia::Transform::from_scale(scale.x, scale.y),
+            &mut pixmap.as_mut(),
+        );
+        pixmap_to_cpu_texture(&pixmap)
+    }
 }
 
 // Helper functions that help other helper functions!!
@@ -702,7 +724,7 @@ fn get_mut_container<'a>(
     }
 }
 
-fn cubic_bezier(t: f32, p0: f32, p1: f32, p2: f32, p3: f32) -> f32 {
+pub fn cubic_bezier(t: f32, p0: f32, p1: f32, p2: f32, p3: f32) -> f32 {
     let u = 1.0 - t;
     u * u * u * p0 + 3.0 * u * u * t * p1 + 3.0 * u * t * t * p2 + t * t * t * p3
 }
@@ -716,3 +738,40 @@ fn trianglulate_polygon(vertices: &Vec<Vec2>) -> Vec<u32> {
     }
     indices
 }
+
+// Calls the value of a call expression's `function`, reporting errors instead of failing
+pub fn call_value(
+    function: &Expression,
+    func_val: &Value,
+    args: &[Value],
+    state: &mut State,
+) -> Value {
+    match func_val {
+        Value::Closure(callable) => {
+            debugger::on_call(state, function);
+            let category = match callable.as_ref() {
+                Callable::Builtin(_) => "builtin",
+                Callable::Function(_) => "function",
+            };
+            state.project.profile_begin(category, || match function {
+                Expression::Identifier(name) if category == "builtin" => name.clone(),
+                Expression::Identifier(name) => format!("{}::{}", state.sprite.name, name),
+                _ => format!("{}::<closure>", state.sprite.name),
+            });
+            let result = callable.call(state, args).unwrap_or_else(|e| {
+                let message = format!("Error calling function: {}", e);
+                console::error(Some(&state.sprite.name), message.clone());
+                debugger::on_error(state, &message);
+                Value::Null
+            });
+            state.project.profile_end();
+            result
+        }
+        _ => {
+            let message = format!("Attempted to call non-function: {:?}", func_val);
+            console::error(Some(&state.sprite.name), message.clone());
+            debugger::on_error(state, &message);
+            Value::Null
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+// Polls the modification times of a set of files. Polling is plenty fast for a few hundred
+// project files and doesn't need any platform specific APIs.
+pub struct FileWatcher {
+    files: HashMap<PathBuf, Option<SystemTime>>,
+    interval: Duration,
+    last_check: Instant,
+}
+
+impl FileWatcher {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            files: HashMap::new(),
+            interval,
+            last_check: Instant::now(),
+        }
+    }
+
+    pub fn watch(&mut self, path: PathBuf) {
+        let modified = modified_time(&path);
+        self.files.entry(path).or_insert(modified);
+    }
+
+    // Watches every file in `dir` (and its subdirectories) with the given extension
+    pub fn watch_dir(&mut self, dir: &Path, extension: &str) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                self.watch_dir(&path, extension);
+            } else if path.extension().is_some_and(|e| e == extension) {
+                self.watch(path);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.files.clear();
+    }
+
+    // Returns the files that changed since the last call. Files that disappear are not reported
+    // until they come back, since editors often delete and rewrite a file when saving it.
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        if self.last_check.elapsed() < self.interval {
+            return vec![];
+        }
+        self.last_check = Instant::now();
+
+        let mut changed = vec![];
+        for (path, last_modified) in &mut self.files {
+            let modified = modified_time(path);
+            if modified.is_some() && modified != *last_modified {
+                changed.push(path.clone());
+            }
+            *last_modified = modified;
+        }
+        changed
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -1,5 +1,7 @@
-use crate::utils::{Token, TokenType, Value};
+use crate::utils::{Token, TokenType, Value, console};
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
@@ -103,9 +105,20 @@ pub enum Statement {
         value: Expression,
     },
     Nop,
+    // Inserted by the parser before every statement while a debugger is attached, so it knows
+    // where it is. The file is shared, since the scripts are cloned every frame.
+    Location {
+        file: Arc<str>,
+        line: usize,
+    },
+    Debugger,
     Assert {
         condition: Expression,
     },
+    Test {
+        name: String,
+        body: Vec<Statement>,
+    },
     Match {
         value: Expression,
         cases: Vec<(Expression, Vec<Statement>)>,
@@ -173,7 +186,10 @@ impl std::fmt::Debug for Statement {
                 value.to_string()
             ),
             Statement::Nop => write!(f, "NOP"),
+            Statement::Location { file, line } => write!(f, "LOCATION[{}:{}]", file, line),
+            Statement::Debugger => write!(f, "DEBUGGER"),
             Statement::Assert { condition } => write!(f, "ASSERT[{}]", condition.to_string()),
+            Statement::Test { name, body } => write!(f, "TEST[{}] {{ {:?} }}", name, body),
             Statement::Match {
                 value,
                 cases,
@@ -273,11 +289,49 @@ impl std::fmt::Debug for Statement {
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    file: Option<Arc<str>>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+        Self {
+            tokens,
+            current: 0,
+            file: None,
+        }
+    }
+
+    // Sets the file the tokens came from, and marks where every statement is in it for the
+    // debugger to break on. Only done while a debugger is attached, since the markers double the
+    // statements every script walks through.
+    pub fn set_file(&mut self, path: &Path) {
+        self.file = Some(Arc::from(
+            std::fs::canonicalize(path)
+                .unwrap_or(path.to_path_buf())
+                .to_string_lossy()
+                .as_ref(),
+        ));
+    }
+
+    pub fn parse_expression(&mut self) -> Result<Expression, String> {
+        let expression = self.parse_binary(0)?;
+        while self.eat(&TokenType::Newline) {}
+        if self.peek().token_type != TokenType::EOF {
+            return Err(format!(
+                "Unexpected token: {:?} at {}:{}",
+                self.peek().token_type,
+                self.peek().line,
+                self.peek().column
+            ));
+        }
+        Ok(expression)
+    }
+
+    fn location(&self) -> Option<Statement> {
+        self.file.as_ref().map(|file| Statement::Location {
+            file: file.clone(),
+            line: self.peek().line,
+        })
     }
 
     fn peek(&self) -> &Token {
@@ -312,6 +366,32 @@ impl Parser {
     }
 
     pub fn parse(&mut self) -> Vec<Statement> {
+        let (statements, errors) = self.parse_with_errors();
+
+        if errors.len() > 0 {
+            let mut report = vec![];
+            if errors.len() == 1 {
+                report.push("There was a parsing error. Details are given below:".to_string());
+                report.push(format!("\t=> {}", errors[0]));
+            } else {
+                report.push(format!(
+                    "There were {} parsing errors. Details are given below:",
+                    errors.len()
+                ));
+                for (i, error) in errors.into_iter().enumerate() {
+                    report.push(format!("\t#{} => {}", i + 1, error));
+                }
+            }
+            report.push(String::new());
+            console::error(None, report.join("\n"));
+        }
+
+        statements
+    }
+
+    // Like `parse`, but hands the errors back instead of printing them. Statements that failed
+    // to parse are replaced with `nop`.
+    pub fn parse_with_errors(&mut self) -> (Vec<Statement>, Vec<String>) {
         let mut statements = vec![];
 
         let mut errors = vec![];
@@ -320,7 +400,13 @@ impl Parser {
             if self.eat(&TokenType::Newline) {
                 continue;
             }
-            let statement = self.parse_statement().unwrap_or_else(|e| {
+            statements.extend(self.location());
+            let statement = if self.at_test() {
+                self.parse_test()
+            } else {
+                self.parse_statement()
+            };
+            let statement = statement.unwrap_or_else(|e| {
                 // Just skip it ¯\_(ツ)_/¯ (also show the error)
                 errors.push(e.replace("\n", "\\n"));
                 self.advance();
@@ -329,23 +415,32 @@ impl Parser {
             statements.push(statement);
         }
 
-        if errors.len() > 0 {
-            if errors.len() == 1 {
-                eprintln!("There was a parsing error. Details are given below:");
-                eprintln!("\t=> {}", errors[0]);
-            } else {
-                eprintln!(
-                    "There were {} parsing errors. Details are given below:",
-                    errors.len()
-                );
-                for (i, error) in errors.into_iter().enumerate() {
-                    eprintln!("\t#{} => {}", i + 1, error);
-                }
-            }
-            eprintln!("");
-        }
+        (statements, errors)
+    }
 
-        statements
+    // `test` is only a keyword at the top level, before a test name, so it still works as a
+    // variable or function name everywhere else
+    fn at_test(&self) -> bool {
+        self.peek().token_type == TokenType::Identifier("test".to_string())
+            && matches!(
+                self.tokens.get(self.current + 1).map(|t| &t.token_type),
+                Some(TokenType::Value(Value::String(_)))
+            )
+    }
+
+    fn parse_test(&mut self) -> Result<Statement, String> {
+        self.advance();
+        if let TokenType::Value(Value::String(name)) = self.peek().clone().token_type {
+            self.advance();
+            let body = self.parse_block()?;
+            Ok(Statement::Test { name, body })
+        } else {
+            Err(format!(
+                "Expected test name after 'test' at {}:{}",
+                self.peek().line,
+                self.peek().column
+            ))
+        }
     }
 
     fn parse_statement(&mut self) -> Result<Statement, String> {
@@ -354,6 +449,10 @@ impl Parser {
                 self.advance();
                 Ok(Statement::Nop)
             }
+            TokenType::Keyword(ref k) if k == "debugger" => {
+                self.advance();
+                Ok(Statement::Debugger)
+            }
             TokenType::Keyword(ref k) if k == "assert" => {
                 self.advance();
                 let condition = self.parse_binary(0)?;
@@ -427,6 +526,7 @@ impl Parser {
             if self.eat(&TokenType::Newline) {
                 continue;
             }
+            statements.extend(self.location());
             let statement = self.parse_statement()?;
             statements.push(statement);
         }
@@ -100,11 +100,12 @@ impl Tokenizer {
             "when",
             "fn",
             "import",
+            "debugger",
         ];
 
         self.skip_whitespace();
 
-        let line = self.code[..self.pointer].lines().count();
+        let line = self.code[..self.pointer].matches('\n').count() + 1;
         let column = self.code[..self.pointer]
             .rfind('\n')
             .map_or(self.pointer, |pos| self.pointer - pos - 1)
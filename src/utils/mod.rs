@@ -12,6 +12,18 @@ pub use sprite::*;
 pub mod stage;
 pub use stage::Stage;
 
+pub mod tilemap;
+pub use tilemap::*;
+
+pub mod particles;
+pub use particles::*;
+
+pub mod animation;
+pub use animation::*;
+
+pub mod assets;
+pub use assets::*;
+
 pub mod project;
 pub use project::*;
 
@@ -20,3 +32,24 @@ pub use language::*;
 
 pub mod runtime;
 pub use runtime::*;
+
+pub mod watchdog;
+pub use watchdog::*;
+
+pub mod backend;
+pub use backend::*;
+
+pub mod test_runner;
+pub use test_runner::*;
+
+pub mod debugger;
+pub use debugger::Debugger;
+
+pub mod hot_reload;
+pub use hot_reload::*;
+
+pub mod profiler;
+pub use profiler::*;
+
+pub mod console;
+pub use console::Console;
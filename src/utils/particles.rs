@@ -0,0 +1,455 @@
+use glam::*;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::utils::core::*;
+use crate::utils::{Sprite, Value};
+
+// The size of the round dot used when an emitter has no texture
+const DOT_SIZE: u32 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    Alpha,
+    Add,
+    Multiply,
+    Screen,
+}
+
+impl BlendMode {
+    pub fn parse(blend: &str) -> Result<Self, String> {
+        match blend {
+            "alpha" => Ok(Self::Alpha),
+            "add" => Ok(Self::Add),
+            "multiply" => Ok(Self::Multiply),
+            "screen" => Ok(Self::Screen),
+            _ => Err(format!(
+                "Unknown blend mode '{}', expected 'alpha', 'add', 'multiply' or 'screen'",
+                blend
+            )),
+        }
+    }
+
+    fn apply(self) {
+        with_renderer(|r| r.set_blend(self));
+    }
+}
+
+pub enum ParticleTexture {
+    // A soft round dot, made on the first draw
+    Dot,
+    Image(GPUTexture),
+    // A costume of the emitter's sprite, looked up every time it's drawn so costume changes and
+    // hot reloads show up
+    Costume(usize),
+}
+
+// A value picked at random between the two ends for every particle
+#[derive(Debug, Clone, Copy)]
+pub struct Spread(pub f32, pub f32);
+
+impl Spread {
+    fn sample(self) -> f32 {
+        if self.0 == self.1 {
+            self.0
+        } else {
+            rand::random_range(self.0.min(self.1)..=self.0.max(self.1))
+        }
+    }
+}
+
+struct Particle {
+    position: Vec2,
+    velocity: Vec2,
+    rotation: f32,
+    spin: f32,
+    size: f32,
+    age: f32,
+    lifetime: f32,
+}
+
+pub struct Emitter {
+    pub name: String,
+    // With a sprite, `position` is an offset from its center
+    pub position: Vec2,
+    pub sprite: Option<String>,
+    // The sprite that created the emitter from script. Its costumes are used when the emitter
+    // isn't attached to a sprite.
+    pub owner: Option<String>,
+    pub layer: isize,
+    pub emitting: bool,
+    // Particles per second
+    pub rate: f32,
+    pub max_particles: usize,
+    pub lifetime: Spread,
+    pub speed: Spread,
+    // Degrees counter-clockwise from the right
+    pub direction: f32,
+    pub spread: f32,
+    pub radius: f32,
+    pub gravity: Vec2,
+    pub size: Spread,
+    // Degrees, and degrees per second
+    pub rotation: Spread,
+    pub spin: Spread,
+    // Keyframes spread evenly over a particle's life
+    pub colors: Vec<Vec4>,
+    pub scales: Vec<f32>,
+    pub blend: BlendMode,
+    pub texture: ParticleTexture,
+    particles: Vec<Particle>,
+    // Fractions of a particle carried over to the next frame
+    spawn_debt: f32,
+    burst: usize,
+    // The sprite it was attached to is gone, so it's removed once its particles are
+    orphaned: bool,
+    // Kept between frames and updated in place, like the sprite batch
+    mesh: Option<Mesh<ColorVertex>>,
+    vertices: Vec<ColorVertex>,
+    indices: Vec<u32>,
+}
+
+impl Emitter {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            position: Vec2::ZERO,
+            sprite: None,
+            owner: None,
+            layer: 0,
+            emitting: true,
+            rate: 20.0,
+            max_particles: 1000,
+            lifetime: Spread(1.0, 1.0),
+            speed: Spread(100.0, 100.0),
+            direction: 90.0,
+            spread: 360.0,
+            radius: 0.0,
+            gravity: Vec2::ZERO,
+            size: Spread(8.0, 8.0),
+            rotation: Spread(0.0, 0.0),
+            spin: Spread(0.0, 0.0),
+            colors: vec![Vec4::ONE],
+            scales: vec![1.0],
+            blend: BlendMode::Alpha,
+            texture: ParticleTexture::Dot,
+            particles: vec![],
+            spawn_debt: 0.0,
+            burst: 0,
+            orphaned: false,
+            mesh: None,
+            vertices: vec![],
+            indices: vec![],
+        }
+    }
+
+    // Applies the options given in `project.toml` or from script. Image paths are relative to
+    // `dir`.
+    pub fn configure(
+        &mut self,
+        options: &HashMap<String, Value>,
+        dir: &Path,
+    ) -> Result<(), String> {
+        // Random ranges can't be made from infinite or NaN bounds, so those are turned down
+        // before anything changes
+        if let Some((key, _)) = options.iter().find(|(_, value)| !finite(value)) {
+            return Err(format!(
+                "Emitter option '{}' must only have finite numbers",
+                key
+            ));
+        }
+        for (key, value) in options {
+            self.set_option(key, value, dir)?;
+        }
+        Ok(())
+    }
+
+    fn set_option(&mut self, key: &str, value: &Value, dir: &Path) -> Result<(), String> {
+        match (key, value) {
+            ("x", Value::Number(x)) => self.position.x = *x,
+            ("y", Value::Number(y)) => self.position.y = *y,
+            ("sprite", Value::String(sprite)) => {
+                self.sprite = Some(sprite.clone());
+                self.orphaned = false;
+            }
+            ("sprite", Value::Null) => self.sprite = None,
+            ("layer", Value::Number(layer)) => self.layer = *layer as isize,
+            ("emitting", Value::Boolean(emitting)) => self.emitting = *emitting,
+            ("rate", Value::Number(rate)) => self.rate = rate.max(0.0),
+            ("max", Value::Number(max)) => self.max_particles = max.max(0.0) as usize,
+            ("lifetime", _) => self.lifetime = spread(key, value)?,
+            ("speed", _) => self.speed = spread(key, value)?,
+            ("direction", Value::Number(direction)) => self.direction = *direction,
+            ("spread", Value::Number(spread)) => self.spread = spread.clamp(0.0, 360.0),
+            ("radius", Value::Number(radius)) => self.radius = radius.max(0.0),
+            ("gravity", _) => self.gravity = vector(key, value)?,
+            ("size", _) => self.size = spread(key, value)?,
+            ("rotation", _) => self.rotation = spread(key, value)?,
+            ("spin", _) => self.spin = spread(key, value)?,
+            ("colors", Value::List(colors)) if !colors.is_empty() => {
+                self.colors = colors.iter().map(color).collect::<Result<_, _>>()?;
+            }
+            ("scales", Value::List(scales)) if !scales.is_empty() => {
+                self.scales = scales
+                    .iter()
+                    .map(|scale| match scale {
+                        Value::Number(scale) => Ok(*scale),
+                        _ => Err("Emitter option 'scales' must be a list of numbers".to_string()),
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            ("blend", Value::String(blend)) => self.blend = BlendMode::parse(blend)?,
+            ("texture", Value::String(path)) => {
+                let path = dir.join(path);
+                let texture = CPUTexture::load_from_file(&path.to_string_lossy())
+                    .map_err(|e| format!("{} ({})", e, path.display()))?;
+                self.texture = ParticleTexture::Image(texture.upload_to_gpu());
+            }
+            ("texture", Value::Null) => self.texture = ParticleTexture::Dot,
+            ("costume", Value::Number(costume)) => {
+                self.texture = ParticleTexture::Costume(costume.max(0.0) as usize)
+            }
+            (
+                "x" | "y" | "sprite" | "layer" | "emitting" | "rate" | "max" | "direction"
+                | "spread" | "radius" | "colors" | "scales" | "blend" | "texture" | "costume",
+                _,
+            ) => {
+                return Err(format!(
+                    "Emitter option '{}' can't be {}",
+                    key,
+                    value.to_string()
+                ));
+            }
+            _ => return Err(format!("Unknown emitter option '{}'", key)),
+        }
+        Ok(())
+    }
+
+    // Spawns `count` particles at once on the next step, even when the emitter isn't emitting
+    pub fn emit(&mut self, count: usize) {
+        self.burst += count;
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.spawn_debt = 0.0;
+        self.burst = 0;
+    }
+
+    pub fn finished(&self) -> bool {
+        self.orphaned && self.particles.is_empty()
+    }
+
+    // Moves every particle along and spawns new ones at the emitter
+    pub fn step(&mut self, dt: f32, sprites: &[Sprite]) {
+        let origin = match &self.sprite {
+            Some(name) => match sprites.iter().find_map(|s| s.find(name)) {
+                Some(sprite) => Some(sprite.center + self.position),
+                None => {
+                    self.orphaned = true;
+                    None
+                }
+            },
+            None => Some(self.position),
+        };
+
+        for particle in &mut self.particles {
+            particle.age += dt;
+            particle.velocity += self.gravity * dt;
+            particle.position += particle.velocity * dt;
+            particle.rotation += particle.spin * dt;
+        }
+        self.particles.retain(|p| p.age < p.lifetime);
+
+        let Some(origin) = origin else {
+            self.burst = 0;
+            return;
+        };
+        if self.emitting {
+            self.spawn_debt += self.rate * dt;
+        }
+        let mut count = self.burst + self.spawn_debt as usize;
+        self.spawn_debt = self.spawn_debt.fract();
+        self.burst = 0;
+        count = count.min(self.max_particles.saturating_sub(self.particles.len()));
+        for _ in 0..count {
+            let spawn = rand::random_range(0.0..std::f32::consts::TAU);
+            let offset = Vec2::from_angle(spawn) * self.radius * rand::random::<f32>().sqrt();
+            let half_spread = self.spread / 2.0;
+            let angle = if half_spread > 0.0 {
+                self.direction + rand::random_range(-half_spread..=half_spread)
+            } else {
+                self.direction
+            };
+            self.particles.push(Particle {
+                position: origin + offset,
+                velocity: Vec2::from_angle(angle.to_radians()) * self.speed.sample(),
+                rotation: self.rotation.sample(),
+                spin: self.spin.sample(),
+                size: self.size.sample(),
+                age: 0.0,
+                lifetime: self.lifetime.sample().max(0.001),
+            });
+        }
+    }
+
+    // Draws every particle with one draw call
+    pub fn draw(&mut self, sprites: &[Sprite], shader: &ShaderProgram, projection: &Mat4) {
+        if self.particles.is_empty() {
+            return;
+        }
+        if matches!(self.texture, ParticleTexture::Dot) {
+            self.texture = ParticleTexture::Image(dot().upload_to_gpu());
+        }
+        // Costumes are a rectangle of their atlas page
+        let (texture, region) = match &self.texture {
+            ParticleTexture::Image(texture) => (texture, vec4(0.0, 0.0, 1.0, 1.0)),
+            ParticleTexture::Costume(index) => {
+                let owner = self.sprite.as_ref().or(self.owner.as_ref());
+                let Some(costume) = owner
+                    .and_then(|name| sprites.iter().find_map(|s| s.find(name)))
+                    .and_then(|sprite| sprite.costumes.get(*index))
+                else {
+                    return;
+                };
+                (costume.texture.page(), costume.texture.rect())
+            }
+            ParticleTexture::Dot => unreachable!(),
+        };
+
+        self.vertices.clear();
+        self.indices.clear();
+        for particle in &self.particles {
+            let t = particle.age / particle.lifetime;
+            let color = keyframe(&self.colors, t, Vec4::lerp);
+            let scale = keyframe(&self.scales, t, FloatExt::lerp);
+            let half = particle.size * scale / 2.0;
+            let rotation = Mat2::from_angle(particle.rotation.to_radians());
+            let base = self.vertices.len() as u32;
+            for (corner, uv) in [
+                (vec2(-1.0, -1.0), vec2(0.0, 1.0)),
+                (vec2(1.0, -1.0), vec2(1.0, 1.0)),
+                (vec2(1.0, 1.0), vec2(1.0, 0.0)),
+                (vec2(-1.0, 1.0), vec2(0.0, 0.0)),
+            ] {
+                self.vertices.push(ColorVertex {
+                    position: particle.position + rotation * (corner * half),
+                    uv: region.xy() + uv * region.zw(),
+                    color,
+                });
+            }
+            self.indices.extend([0, 1, 2, 0, 2, 3].map(|i| base + i));
+        }
+        let mesh = self
+            .mesh
+            .get_or_insert_with(|| Mesh::new(&[], &[], DrawMode::Triangles));
+        mesh.update(&self.vertices, &self.indices);
+
+        shader.use_program();
+        shader.set_uniform("u_color", Vec4::ONE);
+        shader.set_uniform("u_projection", *projection);
+        shader.set_uniform("u_model", Mat4::IDENTITY);
+        shader.set_uniform("u_effects", &[] as &[i32]);
+        shader.set_uniform("u_effect_values", &[] as &[f32]);
+        shader.set_uniform("u_effects_count", 0);
+        shader.set_uniform("u_vertex_color", 1);
+        self.blend.apply();
+        texture.bind();
+        mesh.draw();
+        BlendMode::Alpha.apply();
+        shader.set_uniform("u_vertex_color", 0);
+    }
+}
+
+// The value at `t` (0 to 1) of keyframes spread evenly over that range
+fn keyframe<T: Copy>(frames: &[T], t: f32, lerp: impl Fn(T, T, f32) -> T) -> T {
+    if frames.len() == 1 {
+        return frames[0];
+    }
+    let position = t.clamp(0.0, 1.0) * (frames.len() - 1) as f32;
+    let i = (position as usize).min(frames.len() - 2);
+    lerp(frames[i], frames[i + 1], position - i as f32)
+}
+
+fn dot() -> CPUTexture {
+    let mut texture = CPUTexture::new(DOT_SIZE, DOT_SIZE);
+    let center = Vec2::splat(DOT_SIZE as f32 / 2.0);
+    for y in 0..DOT_SIZE {
+        for x in 0..DOT_SIZE {
+            let distance = (vec2(x as f32, y as f32) + 0.5).distance(center) / center.x;
+            let alpha = (1.0 - distance).clamp(0.0, 1.0).powf(0.5);
+            texture.data[(y * DOT_SIZE + x) as usize] =
+                u8vec4(255, 255, 255, (alpha * 255.0) as u8);
+        }
+    }
+    texture
+}
+
+fn finite(value: &Value) -> bool {
+    match value {
+        Value::Number(n) => n.is_finite(),
+        Value::List(list) => list.iter().all(finite),
+        _ => true,
+    }
+}
+
+fn spread(key: &str, value: &Value) -> Result<Spread, String> {
+    match value {
+        Value::Number(n) => Ok(Spread(*n, *n)),
+        Value::List(list) => match list.as_slice() {
+            [Value::Number(min), Value::Number(max)] => Ok(Spread(*min, *max)),
+            _ => Err(format!(
+                "Emitter option '{}' must be a number or a list of two numbers",
+                key
+            )),
+        },
+        _ => Err(format!(
+            "Emitter option '{}' must be a number or a list of two numbers",
+            key
+        )),
+    }
+}
+
+fn vector(key: &str, value: &Value) -> Result<Vec2, String> {
+    match value {
+        Value::List(list) => match list.as_slice() {
+            [Value::Number(x), Value::Number(y)] => Ok(vec2(*x, *y)),
+            _ => Err(format!("Emitter option '{}' must be [x, y]", key)),
+        },
+        _ => Err(format!("Emitter option '{}' must be [x, y]", key)),
+    }
+}
+
+// `[r, g, b]` or `[r, g, b, a]`, from 0 to 255
+fn color(value: &Value) -> Result<Vec4, String> {
+    let channels = match value {
+        Value::List(list) => list
+            .iter()
+            .map(|c| match c {
+                Value::Number(c) => Some(c.clamp(0.0, 255.0) / 255.0),
+                _ => None,
+            })
+            .collect::<Option<Vec<f32>>>(),
+        _ => None,
+    };
+    match channels.as_deref() {
+        Some([r, g, b]) => Ok(vec4(*r, *g, *b, 1.0)),
+        Some([r, g, b, a]) => Ok(vec4(*r, *g, *b, *a)),
+        _ => Err("Emitter option 'colors' must be a list of [r, g, b] or [r, g, b, a]".to_string()),
+    }
+}
+
+// Every emitter as (layer, index), in the order they are drawn
+pub fn emitter_order(emitters: &[Emitter]) -> Vec<(isize, usize)> {
+    let mut order = emitters
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (e.layer, i))
+        .collect::<Vec<_>>();
+    order.sort_by_key(|(layer, _)| *layer);
+    order
+}
@@ -0,0 +1,190 @@
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// Keeps the trace file (and memory use) reasonable when profiling for a long time. The totals
+// keep being updated after this.
+const MAX_TRACE_EVENTS: usize = 1_000_000;
+
+struct ProfileEntry {
+    category: &'static str,
+    name: String,
+    total: Duration,
+    calls: usize,
+    frame_time: Duration,
+    frame_calls: usize,
+    // Smoothed over the last few frames, so the overlay is readable
+    average_ms: f32,
+    average_calls: f32,
+}
+
+struct TraceEvent {
+    entry: usize,
+    start: Duration,
+    duration: Duration,
+}
+
+// Measures how long sprites, scripts, functions and builtins take. Spans are nested, so the
+// time of a script includes the time of the functions it calls.
+pub struct Profiler {
+    start: Instant,
+    entries: Vec<ProfileEntry>,
+    lookup: HashMap<&'static str, HashMap<String, usize>>,
+    stack: Vec<(usize, Instant)>,
+    events: Vec<TraceEvent>,
+    frames: usize,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            entries: vec![],
+            lookup: HashMap::new(),
+            stack: vec![],
+            events: vec![],
+            frames: 0,
+        }
+    }
+
+    pub fn begin(&mut self, category: &'static str, name: &str) {
+        let names = self.lookup.entry(category).or_default();
+        let entry = match names.get(name) {
+            Some(entry) => *entry,
+            None => {
+                self.entries.push(ProfileEntry {
+                    category,
+                    name: name.to_string(),
+                    total: Duration::ZERO,
+                    calls: 0,
+                    frame_time: Duration::ZERO,
+                    frame_calls: 0,
+                    average_ms: 0.0,
+                    average_calls: 0.0,
+                });
+                names.insert(name.to_string(), self.entries.len() - 1);
+                self.entries.len() - 1
+            }
+        };
+        self.stack.push((entry, Instant::now()));
+    }
+
+    pub fn end(&mut self) {
+        let Some((index, start)) = self.stack.pop() else {
+            return;
+        };
+        let duration = start.elapsed();
+        let entry = &mut self.entries[index];
+        entry.total += duration;
+        entry.calls += 1;
+        entry.frame_time += duration;
+        entry.frame_calls += 1;
+        if self.events.len() < MAX_TRACE_EVENTS {
+            self.events.push(TraceEvent {
+                entry: index,
+                start: start.duration_since(self.start),
+                duration,
+            });
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        // Spans left open by a script that was stopped halfway are closed here
+        while !self.stack.is_empty() {
+            self.end();
+        }
+        for entry in &mut self.entries {
+            let ms = entry.frame_time.as_secs_f32() * 1000.0;
+            if self.frames == 0 {
+                entry.average_ms = ms;
+                entry.average_calls = entry.frame_calls as f32;
+            } else {
+                entry.average_ms = entry.average_ms * 0.9 + ms * 0.1;
+                entry.average_calls = entry.average_calls * 0.9 + entry.frame_calls as f32 * 0.1;
+            }
+            entry.frame_time = Duration::ZERO;
+            entry.frame_calls = 0;
+        }
+        self.frames += 1;
+    }
+
+    // The `count` most expensive entries, in milliseconds per frame
+    pub fn overlay_lines(&self, count: usize) -> Vec<String> {
+        let mut entries = self
+            .entries
+            .iter()
+            .filter(|e| e.category != "frame")
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| b.average_ms.total_cmp(&a.average_ms));
+
+        let frame_ms = self
+            .entries
+            .iter()
+            .find(|e| e.category == "frame")
+            .map(|e| e.average_ms)
+            .unwrap_or(0.0);
+        let mut lines = vec![format!("Frame: {:.2} ms", frame_ms)];
+        for entry in entries.into_iter().take(count) {
+            lines.push(format!(
+                "{:>7.2} ms {:>6.1}x  {:<8} {}",
+                entry.average_ms, entry.average_calls, entry.category, entry.name
+            ));
+        }
+        lines
+    }
+
+    // Writes the spans in the Chrome trace format, which can be opened in chrome://tracing,
+    // Perfetto or speedscope (as a flame graph)
+    pub fn write_trace(&self, path: &Path) -> Result<(), String> {
+        let mut events = self
+            .events
+            .iter()
+            .map(|event| {
+                let entry = &self.entries[event.entry];
+                json!({
+                    "name": entry.name,
+                    "cat": entry.category,
+                    "ph": "X",
+                    "ts": event.start.as_secs_f64() * 1_000_000.0,
+                    "dur": event.duration.as_secs_f64() * 1_000_000.0,
+                    "pid": 1,
+                    "tid": 1,
+                })
+            })
+            .collect::<Vec<_>>();
+        events.push(json!({
+            "name": "thread_name",
+            "ph": "M",
+            "pid": 1,
+            "tid": 1,
+            "args": { "name": "Crust" },
+        }));
+
+        let mut totals = self.entries.iter().collect::<Vec<_>>();
+        totals.sort_by_key(|e| std::cmp::Reverse(e.total));
+        let totals = totals
+            .into_iter()
+            .map(|entry| {
+                json!({
+                    "category": entry.category,
+                    "name": entry.name,
+                    "total_ms": entry.total.as_secs_f64() * 1000.0,
+                    "calls": entry.calls,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let trace = json!({
+            "traceEvents": events,
+            "displayTimeUnit": "ms",
+            "otherData": {
+                "frames": self.frames,
+                "dropped_events": self.events.len() >= MAX_TRACE_EVENTS,
+                "totals": totals,
+            },
+        });
+        std::fs::write(path, trace.to_string())
+            .map_err(|e| format!("Failed to write profile to {}: {}", path.display(), e))
+    }
+}
@@ -1,7 +1,5 @@
 use std::collections::HashMap;
 
-use glfw::Window;
-
 use super::*;
 
 #[derive(Debug, Clone)]
@@ -10,6 +8,13 @@ pub struct Broadcast {
     pub id: usize,
 }
 
+#[derive(Debug, Clone)]
+pub struct AssertFailure {
+    pub condition: String,
+    pub expected: String,
+    pub actual: String,
+}
+
 pub struct Project {
     pub global_variables: HashMap<String, Value>,
     pub broadcast_history: Vec<Broadcast>,
@@ -19,6 +24,16 @@ pub struct Project {
     pub args: Vec<String>,
     pub home_path: String,
     pub export_path: String,
+    pub watchdog: Watchdog,
+    pub exit_code: Option<i32>,
+    pub assert_failures: Vec<AssertFailure>,
+    pub testing: bool,
+    pub debugger: Option<Debugger>,
+    pub profiler: Option<Profiler>,
+    pub physics: PhysicsWorld,
+    pub tilemaps: Vec<Tilemap>,
+    pub emitters: Vec<Emitter>,
+    pub assets: Assets,
 }
 
 impl Project {
@@ -26,7 +41,7 @@ impl Project {
         home_path: String,
         export_path: String,
         args: Vec<String>,
-        window: &Window,
+        window: &dyn WindowBackend,
         builtins: HashMap<String, Callable>,
     ) -> Self {
         Self {
@@ -38,6 +53,16 @@ impl Project {
             args,
             home_path,
             export_path,
+            watchdog: Watchdog::default(),
+            exit_code: None,
+            assert_failures: Vec::new(),
+            testing: false,
+            debugger: None,
+            profiler: None,
+            physics: PhysicsWorld::default(),
+            tilemaps: Vec::new(),
+            emitters: Vec::new(),
+            assets: Assets::default(),
         }
     }
 
@@ -46,6 +71,28 @@ impl Project {
         self.broadcast_history.push(Broadcast { message, id });
     }
 
+    // Resets the limits of the watchdog, and the debugger's last location so the script's first
+    // statement is hit again even if it was the last one hit
+    pub fn begin_script(&mut self) {
+        self.watchdog.begin_script();
+        if let Some(debugger) = &mut self.debugger {
+            debugger.statement_finished();
+        }
+    }
+
+    // The name is only built when profiling, so this is cheap to call from hot code
+    pub fn profile_begin(&mut self, category: &'static str, name: impl FnOnce() -> String) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.begin(category, &name());
+        }
+    }
+
+    pub fn profile_end(&mut self) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.end();
+        }
+    }
+
     pub fn get_broadcast(&self, message: &str) -> Option<&Broadcast> {
         self.broadcast_history
             .iter()
@@ -1,15 +1,23 @@
 use glam::*;
-use glfw::{Context, Window};
-use kira::sound::static_sound::StaticSoundData;
-use kira::{AudioManager, AudioManagerSettings, DefaultBackend};
 use serde::Deserialize;
 use std::collections::{HashMap, HashSet};
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::utils::draw_sprite;
-use crate::utils::{core::*, rasterize_svg};
+use crate::utils::SpriteBatch;
+use crate::utils::{VectorImage, core::*};
 
 use super::sprite::StopRequest;
-use super::{Parser, Project, Tokenizer, sprite::Sprite, sprite::SpriteSnapshot};
+use super::{
+    Animation, AnimationMode, Assets, AudioBackend, Body, BodyKind, ColliderShape, Console,
+    Costume, CostumeSet, DebugOverlay, Debugger, Emitter, FileWatcher, Inspector, LimitAction,
+    Parser, PhysicsWorld, Profiler, Project, Snapshots, SpriteCode, SpritePath, State, Statement,
+    Tilemap, Tokenizer, Value, Watchdog, WindowBackend, apply_edit, canonical_path, console,
+    debugger, emitter_order, find_sprite_mut, load_image, packed_image, slice_grid, slice_packed,
+    sprite::Sprite, step_physics, tile_layer_order,
+};
 
 #[derive(Deserialize, Debug)]
 struct FontConfig {
@@ -22,7 +30,81 @@ struct FontConfig {
 
 #[derive(Deserialize, Debug)]
 struct StageConfig {
-    backdrops: Vec<String>,
+    backdrops: Vec<CostumeConfig>,
+}
+
+// A costume or backdrop file, named after the file unless it is given a name
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum CostumeConfig {
+    File(String),
+    Named { file: String, name: String },
+}
+
+impl CostumeConfig {
+    fn file(&self) -> &str {
+        match self {
+            Self::File(file) | Self::Named { file, .. } => file,
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Self::File(file) => Path::new(file)
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            Self::Named { name, .. } => name.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct TilemapConfig {
+    name: String,
+    file: String,
+    x: Option<f32>,
+    y: Option<f32>,
+    scale: Option<f32>,
+    layer: Option<isize>,
+}
+
+impl TilemapConfig {
+    fn load(&self, dir: &Path) -> Result<Tilemap, String> {
+        let position = self.x.zip(self.y).map(|(x, y)| vec2(x, y));
+        Tilemap::load(
+            &self.name,
+            &dir.join(&self.file),
+            self.scale.unwrap_or(2.0),
+            position,
+            self.layer.unwrap_or(0),
+        )
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct EmitterConfig {
+    name: String,
+    // Everything else is an emitter option, the same ones `create_emitter()` takes
+    #[serde(flatten)]
+    options: HashMap<String, toml::Value>,
+}
+
+impl EmitterConfig {
+    fn options(&self) -> HashMap<String, Value> {
+        self.options
+            .iter()
+            .map(|(key, value)| (key.clone(), toml_to_value(value)))
+            .collect()
+    }
+
+    // Applies the options to an emitter that already exists, keeping its particles
+    fn apply(&self, emitter: &mut Emitter, dir: &Path) {
+        if let Err(e) = emitter.configure(&self.options(), dir) {
+            console::error(None, format!("Emitter '{}': {}", self.name, e));
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -35,7 +117,9 @@ struct SoundConfig {
 struct SpriteConfig {
     name: String,
     code: String,
-    costumes: Vec<String>,
+    costumes: Vec<CostumeConfig>,
+    sheets: Option<Vec<SheetConfig>>,
+    animations: Option<HashMap<String, AnimationConfig>>,
     sounds: Option<Vec<SoundConfig>>,
     x: f32,
     y: f32,
@@ -44,6 +128,255 @@ struct SpriteConfig {
     visible: Option<bool>,
     layer: Option<isize>,
     direction: Option<f32>,
+    physics: Option<BodyConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SheetConfig {
+    // Optional with `data`, which knows its image
+    file: Option<String>,
+    // A TexturePacker or Aseprite JSON file. Without it, the sheet is cut into a grid.
+    data: Option<String>,
+    frame_width: Option<u32>,
+    frame_height: Option<u32>,
+    columns: Option<u32>,
+    rows: Option<u32>,
+    margin: Option<u32>,
+    spacing: Option<u32>,
+    count: Option<usize>,
+}
+
+impl SheetConfig {
+    // The paths the sheet is loaded from, for hot reload
+    fn files(&self, dir: &Path) -> Vec<PathBuf> {
+        self.file
+            .iter()
+            .chain(self.data.iter())
+            .map(|f| dir.join(f))
+            .collect()
+    }
+
+    // Sheets cut the same way out of the same files share their costumes
+    fn cache_key(&self, dir: &Path) -> String {
+        let files: Vec<PathBuf> = self.files(dir).iter().map(|f| canonical_path(f)).collect();
+        format!(
+            "{:?} {:?}",
+            files,
+            (
+                self.frame_width,
+                self.frame_height,
+                self.columns,
+                self.rows,
+                self.margin,
+                self.spacing,
+                self.count
+            )
+        )
+    }
+
+    fn load_cached(&self, dir: &Path, assets: &mut Assets) -> Result<Arc<CostumeSet>, String> {
+        assets.costumes(&self.cache_key(dir), &self.files(dir), |atlas| {
+            let (frames, animations) = self.load(dir)?;
+            Ok(CostumeSet {
+                frames: frames
+                    .iter()
+                    .map(|(name, frame)| Costume::new(frame, atlas).named(name))
+                    .collect(),
+                animations,
+                ..CostumeSet::default()
+            })
+        })
+    }
+
+    // The named frames of the sheet, and the animations of its tags counted from its first frame.
+    // Frames of a grid are named after the image and numbered from 1.
+    fn load(
+        &self,
+        dir: &Path,
+    ) -> Result<(Vec<(String, CPUTexture)>, HashMap<String, Animation>), String> {
+        if let Some(data) = &self.data {
+            let path = dir.join(data);
+            let content = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let json: serde_json::Value = serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+            // The image in the JSON is relative to the JSON file
+            let image = match (&self.file, packed_image(&json)) {
+                (Some(file), _) => dir.join(file),
+                (None, Some(image)) => path.parent().unwrap_or(dir).join(image),
+                (None, None) => {
+                    return Err(format!(
+                        "{} doesn't say which image it is for",
+                        path.display()
+                    ));
+                }
+            };
+            let sheet = load_image(&image)?;
+            return slice_packed(&json, &sheet).map_err(|e| format!("{}: {}", path.display(), e));
+        }
+
+        let Some(file) = &self.file else {
+            return Err("A sprite sheet needs a 'file' or a 'data' file".to_string());
+        };
+        let sheet = load_image(&dir.join(file))?;
+        let (margin, spacing) = (self.margin.unwrap_or(0), self.spacing.unwrap_or(0));
+        // Without a frame size, it is worked out from the number of columns and rows
+        let split = |size: u32, count: Option<u32>| {
+            let count = count.unwrap_or(1).max(1);
+            (size.saturating_sub(margin * 2 + spacing * (count - 1))) / count
+        };
+        let frame = uvec2(
+            self.frame_width
+                .unwrap_or_else(|| split(sheet.width, self.columns)),
+            self.frame_height
+                .unwrap_or_else(|| split(sheet.height, self.rows)),
+        );
+        let frames = slice_grid(&sheet, frame, margin, spacing, self.count)
+            .map_err(|e| format!("{}: {}", file, e))?;
+        let stem = Path::new(file)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let frames = frames
+            .into_iter()
+            .enumerate()
+            .map(|(i, frame)| (format!("{} {}", stem, i + 1), frame))
+            .collect();
+        Ok((frames, HashMap::new()))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct AnimationConfig {
+    frames: Vec<usize>,
+    fps: Option<f32>,
+    mode: Option<String>,
+}
+
+impl AnimationConfig {
+    fn to_animation(&self) -> Result<Animation, String> {
+        let mode = self
+            .mode
+            .as_deref()
+            .map_or(Ok(AnimationMode::Loop), AnimationMode::parse)?;
+        Ok(Animation::new(
+            self.frames.clone(),
+            self.fps.unwrap_or(12.0),
+            mode,
+        ))
+    }
+}
+
+impl SpriteConfig {
+    // The files the costumes are loaded from, for hot reload
+    fn costume_files(&self, dir: &Path) -> Vec<PathBuf> {
+        let sheets = self.sheets.iter().flatten().flat_map(|s| s.files(dir));
+        self.costumes
+            .iter()
+            .map(|c| dir.join(c.file()))
+            .chain(sheets)
+            .collect()
+    }
+
+    // Every costume of the sprite, with every frame of animated images, followed by the costumes
+    // cut out of its sheets. Costumes that fail to load are errors holding their name so the rest
+    // keep their numbers, but sheets that fail to load are left out.
+    fn load_costumes(&self, dir: &Path, assets: &mut Assets) -> SpriteCostumes {
+        let mut costumes = vec![];
+        let mut animations = HashMap::new();
+        let mut vectors = vec![];
+        for costume in &self.costumes {
+            let name = costume.name();
+            match assets.image(&dir.join(costume.file())) {
+                Ok(set) => {
+                    if let Some(vector) = &set.vector {
+                        vectors.push((costumes.len(), vector.clone()));
+                    }
+                    // Animated images get an animation with the costume's name
+                    if !set.delays.is_empty() {
+                        let animation = Animation::from_delays(costumes.len(), set.delays.clone());
+                        animations.insert(name.clone(), animation);
+                    }
+                    costumes.extend(set.named(&name).into_iter().map(Ok));
+                }
+                Err(e) => {
+                    console::error(Some(&self.name), e);
+                    costumes.push(Err(name));
+                }
+            }
+        }
+        for sheet in self.sheets.iter().flatten() {
+            match sheet.load_cached(dir, assets) {
+                Ok(set) => {
+                    let first = costumes.len();
+                    for (name, animation) in &set.animations {
+                        animations.insert(name.clone(), animation.clone().offset(first));
+                    }
+                    costumes.extend(set.frames.iter().cloned().map(Ok));
+                }
+                Err(e) => console::error(Some(&self.name), e),
+            }
+        }
+        // Animations in `project.toml` replace the others with the same name
+        for (name, animation) in self.animations.iter().flatten() {
+            match animation.to_animation() {
+                Ok(animation) => {
+                    animations.insert(name.clone(), animation);
+                }
+                Err(e) => console::error(Some(&self.name), format!("Animation '{}': {}", name, e)),
+            }
+        }
+        SpriteCostumes {
+            textures: costumes,
+            animations,
+            vectors,
+        }
+    }
+}
+
+struct SpriteCostumes {
+    textures: Vec<Result<Costume, String>>,
+    animations: HashMap<String, Animation>,
+    // SVG costumes by number, which are rasterised again to match the size they are drawn at
+    vectors: Vec<(usize, VectorImage)>,
+}
+
+#[derive(Deserialize, Debug)]
+struct BodyConfig {
+    body: String,
+    shape: Option<String>,
+    mass: Option<f32>,
+    friction: Option<f32>,
+    restitution: Option<f32>,
+    fixed_rotation: Option<bool>,
+    gravity_scale: Option<f32>,
+}
+
+impl BodyConfig {
+    fn to_body(&self) -> std::result::Result<Body, String> {
+        let mut body = Body::new(BodyKind::parse(&self.body)?);
+        if let Some(shape) = &self.shape {
+            body.shape = ColliderShape::parse(shape)?;
+        }
+        if let Some(mass) = self.mass {
+            body.set_mass(mass)?;
+        }
+        if let Some(friction) = self.friction {
+            body.set_friction(friction)?;
+        }
+        if let Some(restitution) = self.restitution {
+            body.set_restitution(restitution)?;
+        }
+        body.fixed_rotation = self.fixed_rotation.unwrap_or(body.fixed_rotation);
+        body.gravity_scale = self.gravity_scale.unwrap_or(body.gravity_scale);
+        Ok(body)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct PhysicsConfig {
+    gravity: Option<[f32; 2]>,
+    step: Option<f32>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -53,12 +386,25 @@ struct TagConfig {
     sprites: Vec<String>,
 }
 
+#[derive(Deserialize, Debug)]
+struct LimitsConfig {
+    max_instructions: Option<usize>,
+    max_script_time: Option<u64>,
+    max_call_depth: Option<usize>,
+    on_exceed: Option<String>,
+}
+
 #[derive(Deserialize, Debug)]
 struct ProjectConfig {
     debug_options: Option<Vec<String>>,
+    watch: Option<Vec<String>>,
     vsync: Option<bool>,
+    limits: Option<LimitsConfig>,
+    physics: Option<PhysicsConfig>,
     font: Option<FontConfig>,
     stage: Option<StageConfig>,
+    tilemaps: Option<Vec<TilemapConfig>>,
+    emitters: Option<Vec<EmitterConfig>>,
     sprites: Vec<SpriteConfig>,
     tags: Option<Vec<TagConfig>>,
 }
@@ -73,6 +419,7 @@ pub struct InputManager {
     mouse_buttons_down: HashSet<glfw::MouseButton>,
     mouse_buttons_pressed: HashSet<glfw::MouseButton>,
     mouse_buttons_released: HashSet<glfw::MouseButton>,
+    typed: String,
 }
 
 impl InputManager {
@@ -86,55 +433,67 @@ impl InputManager {
             mouse_buttons_down: HashSet::new(),
             mouse_buttons_pressed: HashSet::new(),
             mouse_buttons_released: HashSet::new(),
+            typed: String::new(),
         }
     }
 
-    pub fn update(
-        &mut self,
-        window: &mut glfw::Window,
-        events: &glfw::GlfwReceiver<(f64, glfw::WindowEvent)>,
-    ) {
+    pub fn begin_frame(&mut self) {
         self.keys_pressed.clear();
         self.keys_released.clear();
         self.mouse_buttons_pressed.clear();
         self.mouse_buttons_released.clear();
+        self.typed.clear();
+    }
 
-        for (_, event) in glfw::flush_messages(events) {
-            match event {
-                glfw::WindowEvent::Key(key, _, action, _) => match action {
-                    glfw::Action::Press => {
-                        self.key_combination_used = false;
-                        if !self.keys_down.contains(&key) {
-                            self.keys_pressed.insert(key);
-                        }
-                        self.key_history.push(key);
-                        self.keys_down.insert(key);
-                    }
-                    glfw::Action::Release => {
-                        self.keys_released.insert(key);
-                        self.keys_down.remove(&key);
-                    }
-                    _ => {}
-                },
-                glfw::WindowEvent::MouseButton(button, action, _) => match action {
-                    glfw::Action::Press => {
-                        if !self.mouse_buttons_down.contains(&button) {
-                            self.mouse_buttons_pressed.insert(button);
-                        }
-                        self.mouse_buttons_down.insert(button);
-                    }
-                    glfw::Action::Release => {
-                        self.mouse_buttons_released.insert(button);
-                        self.mouse_buttons_down.remove(&button);
-                    }
-                    _ => {}
-                },
-                glfw::WindowEvent::Close => {
-                    window.set_should_close(true);
-                }
-                _ => {}
-            }
+    // Makes the keyboard look untouched to the scripts, for when something else (like the
+    // console) uses it
+    pub fn clear_keys(&mut self) {
+        self.keys_down.clear();
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.typed.clear();
+    }
+
+    // Same as `clear_keys`, for the mouse buttons (used by the inspector)
+    pub fn clear_mouse_buttons(&mut self) {
+        self.mouse_buttons_down.clear();
+        self.mouse_buttons_pressed.clear();
+        self.mouse_buttons_released.clear();
+    }
+
+    pub fn type_char(&mut self, c: char) {
+        self.typed.push(c);
+    }
+
+    // The text typed since the last frame
+    pub fn typed(&self) -> &str {
+        &self.typed
+    }
+
+    pub fn press_key(&mut self, key: glfw::Key) {
+        self.key_combination_used = false;
+        if !self.keys_down.contains(&key) {
+            self.keys_pressed.insert(key);
+        }
+        self.key_history.push(key);
+        self.keys_down.insert(key);
+    }
+
+    pub fn release_key(&mut self, key: glfw::Key) {
+        self.keys_released.insert(key);
+        self.keys_down.remove(&key);
+    }
+
+    pub fn press_mouse_button(&mut self, button: glfw::MouseButton) {
+        if !self.mouse_buttons_down.contains(&button) {
+            self.mouse_buttons_pressed.insert(button);
         }
+        self.mouse_buttons_down.insert(button);
+    }
+
+    pub fn release_mouse_button(&mut self, button: glfw::MouseButton) {
+        self.mouse_buttons_released.insert(button);
+        self.mouse_buttons_down.remove(&button);
     }
 
     pub fn key_history(&self) -> &[glfw::Key] {
@@ -174,16 +533,26 @@ impl InputManager {
 
 pub struct Runtime {
     pub project: Project,
-    pub audio_manager: AudioManager<DefaultBackend>,
-    pub font: BitmapFont,
+    pub audio_manager: Box<dyn AudioBackend>,
+    pub font: Arc<BitmapFont>,
     pub vsync: bool,
     debug_options: Vec<String>,
+    overlay: DebugOverlay,
+    console: Console,
+    inspector: Inspector,
+    project_file: PathBuf,
+    watcher: Option<FileWatcher>,
+    batch: SpriteBatch,
 }
 
 impl Runtime {
-    pub fn new(file_path: &str, args: Vec<String>, window: &Window) -> Self {
-        let audio_manager = AudioManager::new(AudioManagerSettings::default())
-            .expect("Failed to create audio manager");
+    pub fn new(
+        file_path: &str,
+        args: Vec<String>,
+        window: &dyn WindowBackend,
+        audio_manager: Box<dyn AudioBackend>,
+        debugger: Option<Debugger>,
+    ) -> Self {
         let dir = std::path::Path::new(file_path).parent().unwrap();
         let raw = std::fs::read_to_string(file_path).unwrap();
         let config: ProjectConfig = toml::from_str(&raw).unwrap();
@@ -206,18 +575,29 @@ impl Runtime {
             window,
             builtins,
         );
+        project.debugger = debugger;
+        let debugging = project.debugger.is_some();
+
+        project.watchdog = limits_watchdog(config.limits.as_ref());
+
+        if let Some(physics) = &config.physics {
+            apply_physics_config(&mut project.physics, physics);
+        }
 
         let font = if let Some(font_config) = config.font {
             let font_path = dir.join(font_config.file);
-            let cpu_texture = CPUTexture::load_from_file(&font_path.to_string_lossy())
-                .expect("Failed to load font texture");
-            BitmapFont::new(
-                cpu_texture.upload_to_gpu(),
-                font_config.first_char,
-                font_config.char_width,
-                font_config.char_height,
-                font_config.chars_per_row,
-            )
+            project
+                .assets
+                .font(&font_path, |texture| {
+                    BitmapFont::new(
+                        texture,
+                        font_config.first_char,
+                        font_config.char_width,
+                        font_config.char_height,
+                        font_config.chars_per_row,
+                    )
+                })
+                .expect("Failed to load font texture")
         } else {
             let font_bytes = include_bytes!("../../assets/font.png");
             let font_image = image::load_from_memory(font_bytes)
@@ -227,83 +607,69 @@ impl Runtime {
             let pixels = font_image.into_raw();
             let cpu_texture = CPUTexture::load_from_bytes(&pixels, width, height)
                 .expect("Failed to load font texture");
-            BitmapFont::new(cpu_texture.upload_to_gpu(), ' ', 12, 7, 12)
+            Arc::new(BitmapFont::new(cpu_texture.upload_to_gpu(), ' ', 12, 7, 12))
         };
 
-        for path in config
-            .stage
-            .unwrap_or(StageConfig { backdrops: vec![] })
-            .backdrops
-        {
-            let path = dir.join(path);
-            let tex = if path.extension().unwrap() != "svg" {
-                CPUTexture::load_from_file(&path.to_string_lossy())
-                    .or_else(|_| {
-                        CPUTexture::load_from_bytes(
-                            include_bytes!("../../assets/missing.png"),
-                            100,
-                            100,
-                        )
-                    })
-                    .unwrap()
-            } else {
-                let content = std::fs::read_to_string(path).unwrap_or_default();
-                rasterize_svg(&content)
-                    .or_else(|_| {
-                        CPUTexture::load_from_bytes(
-                            include_bytes!("../../assets/missing.png"),
-                            100,
-                            100,
-                        )
-                    })
-                    .unwrap()
-            };
-            let tex = tex.upload_to_gpu();
-            project.stage.backdrops.push(tex);
+        let backdrops = config.stage.map(|s| s.backdrops).unwrap_or_default();
+        let (frames, animations) = load_backdrops(&backdrops, dir, &mut project.assets);
+        for frame in frames {
+            let frame =
+                frame.unwrap_or_else(|name| missing_costume(&mut project.assets).named(&name));
+            project.stage.add_backdrop(frame);
         }
+        project.stage.animations = animations;
 
         if project.stage.backdrops.is_empty() {
             project
                 .stage
-                .backdrops
-                .push(CPUTexture::new(1, 1).upload_to_gpu());
+                .add_backdrop(Costume::single(&CPUTexture::new(1, 1)));
         }
 
-        for sprite in config.sprites {
-            let mut textures = vec![];
-            for path in sprite.costumes {
-                let path = dir.join(path);
-                let tex = CPUTexture::load_from_file(&path.to_string_lossy())
-                    .or_else(|_| {
-                        CPUTexture::load_from_bytes(
-                            include_bytes!("../../assets/missing.png"),
-                            100,
-                            100,
-                        )
-                    })
-                    .unwrap_or_else(|e| {
-                        panic!(
-                            "Failed to load sprite texture: {}. Error: {}",
-                            path.to_string_lossy(),
-                            e
-                        );
-                    });
-                textures.push(tex.upload_to_gpu());
+        for tilemap in config.tilemaps.iter().flatten() {
+            match tilemap.load(dir) {
+                Ok(tilemap) => project.tilemaps.push(tilemap),
+                Err(e) => console::error(None, e),
             }
+        }
+
+        for emitter_config in config.emitters.iter().flatten() {
+            let mut emitter = Emitter::new(&emitter_config.name);
+            emitter_config.apply(&mut emitter, dir);
+            project.emitters.push(emitter);
+        }
+
+        for sprite in config.sprites {
+            let SpriteCostumes {
+                textures,
+                animations,
+                vectors,
+            } = sprite.load_costumes(dir, &mut project.assets);
+            let textures = textures
+                .into_iter()
+                .map(|costume| {
+                    costume.unwrap_or_else(|name| missing_costume(&mut project.assets).named(&name))
+                })
+                .collect::<Vec<_>>();
+            // A sprite that starts on an animated image plays it
+            let playing = sprite
+                .costumes
+                .first()
+                .map(CostumeConfig::name)
+                .filter(|name| {
+                    animations
+                        .get(name)
+                        .is_some_and(|a| a.frames.first() == Some(&0))
+                });
 
             let mut sounds = vec![];
             if sprite.sounds.is_some() {
                 let sounds_ = sprite.sounds.unwrap();
                 for sound in sounds_ {
                     let path = dir.join(&sound.file);
-                    let sound_data = StaticSoundData::from_file(&*path.to_string_lossy())
-                        .unwrap_or_else(|e| {
-                            panic!(
-                                "Failed to load sound: {}. Error: {}",
-                                path.to_string_lossy(),
-                                e
-                            );
-                        });
+                    let sound_data = project
+                        .assets
+                        .sound(&path)
+                        .unwrap_or_else(|e| panic!("{}", e));
                     sounds.push((sound.name, sound_data));
                 }
             }
@@ -317,17 +683,24 @@ impl Runtime {
             let mut tokenizer = Tokenizer::new(code);
             let tokens = tokenizer.tokenize_full();
             let mut parser = Parser::new(tokens);
+            if debugging {
+                parser.set_file(&sprite_code_file);
+            }
             let mut ast = parser.parse();
             let mut sprite_tags = vec![];
 
             for (tag_name, (sprites, code)) in tags.iter() {
                 if sprites.contains(&sprite.name) || tag_name == "*" {
                     if let Some(code) = code {
-                        let code = std::fs::read_to_string(dir.join(code))
+                        let tag_code_file = dir.join(code);
+                        let code = std::fs::read_to_string(&tag_code_file)
                             .expect("Failed to read tag code");
                         let mut tokenizer = Tokenizer::new(code.clone());
                         let tokens = tokenizer.tokenize_full();
                         let mut parser = Parser::new(tokens);
+                        if debugging {
+                            parser.set_file(&tag_code_file);
+                        }
                         let tag_ast = parser.parse();
                         ast.extend(tag_ast);
                     }
@@ -335,7 +708,7 @@ impl Runtime {
                 }
             }
 
-            let s = Sprite::new(
+            let mut s = Sprite::new(
                 sprite.name.clone(),
                 textures,
                 sounds,
@@ -349,27 +722,376 @@ impl Runtime {
                 sprite.layer.unwrap_or(0),
                 sprite.direction.unwrap_or(0.0),
                 dir.to_string_lossy().to_string(),
+                debugging,
             );
+            s.animations = animations;
+            s.set_vector_costumes(vectors);
+            if let Some(name) = playing {
+                s.play_animation(&name, true);
+            }
+            if let Some(physics) = &sprite.physics {
+                match physics.to_body() {
+                    Ok(body) => s.body = Some(body),
+                    Err(e) => console::error(Some(&sprite.name), e),
+                }
+            }
 
             project.sprites.push(s);
         }
 
-        Self {
+        let debug_options = config.debug_options.unwrap_or(vec![]);
+        let overlay = DebugOverlay::new(&debug_options, config.watch.unwrap_or_default());
+        let mut runtime = Self {
             project,
             audio_manager,
             font,
             vsync: config.vsync.unwrap_or(true),
-            debug_options: config.debug_options.unwrap_or(vec![]),
+            watcher: None,
+            debug_options,
+            overlay,
+            console: Console::new(),
+            inspector: Inspector::new(),
+            project_file: PathBuf::from(file_path),
+            batch: SpriteBatch::default(),
+        };
+        if runtime.debug_options.contains(&"hot_reload".to_string()) {
+            runtime.watch_project_files();
         }
+        runtime.update_profiler();
+        runtime
     }
 
-    pub fn run(
+    fn update_profiler(&mut self) {
+        let profile = self.debug_options.contains(&"profile".to_string());
+        if profile && self.project.profiler.is_none() {
+            self.project.profiler = Some(Profiler::new());
+        } else if !profile {
+            self.write_profile();
+            self.project.profiler = None;
+        }
+    }
+
+    fn write_profile(&self) {
+        if let Some(profiler) = &self.project.profiler {
+            let path = self.project_file.parent().unwrap().join("profile.json");
+            match profiler.write_trace(&path) {
+                Ok(()) => println!("Wrote profile to {}", path.display()),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+    }
+
+    fn read_config(&self) -> Result<ProjectConfig, String> {
+        let raw = std::fs::read_to_string(&self.project_file).map_err(|e| e.to_string())?;
+        toml::from_str(&raw).map_err(|e| e.to_string())
+    }
+
+    fn watch_project_files(&mut self) {
+        let dir = self.project_file.parent().unwrap().to_path_buf();
+        let mut watcher = self
+            .watcher
+            .take()
+            .unwrap_or_else(|| FileWatcher::new(Duration::from_millis(250)));
+        watcher.clear();
+        watcher.watch(self.project_file.clone());
+        watcher.watch_dir(&dir, "crst");
+        if let Ok(config) = self.read_config() {
+            for sprite in &config.sprites {
+                for file in sprite.costume_files(&dir) {
+                    watcher.watch(file);
+                }
+            }
+            for backdrop in config.stage.map(|s| s.backdrops).unwrap_or_default() {
+                watcher.watch(dir.join(backdrop.file()));
+            }
+            for tilemap in config.tilemaps.iter().flatten() {
+                watcher.watch(dir.join(&tilemap.file));
+            }
+            for emitter in config.emitters.iter().flatten() {
+                if let Some(toml::Value::String(texture)) = emitter.options.get("texture") {
+                    watcher.watch(dir.join(texture));
+                }
+            }
+        }
+        self.watcher = Some(watcher);
+    }
+
+    // Draws the sprites queued so far. This is where the GPU work of drawing sprites happens.
+    fn flush_batch(&mut self, shader_program: &ShaderProgram, projection: &Mat4) {
+        self.project
+            .profile_begin("draw", || "draw_batch".to_string());
+        self.batch.flush(shader_program, *projection);
+        self.project.profile_end();
+    }
+
+    fn draw_tile_layer(
         &mut self,
-        window: &mut Window,
-        events: &glfw::GlfwReceiver<(f64, glfw::WindowEvent)>,
+        map: usize,
+        layer: usize,
         shader_program: &ShaderProgram,
-        glfw: &mut glfw::Glfw,
+        projection: &Mat4,
     ) {
+        self.flush_batch(shader_program, projection);
+        let tilemap = &mut self.project.tilemaps[map];
+        if let Some(profiler) = &mut self.project.profiler {
+            profiler.begin(
+                "draw",
+                &format!(
+                    "draw_tile_layer({}/{})",
+                    tilemap.name, tilemap.layers[layer].name
+                ),
+            );
+        }
+        tilemap.draw_layer(layer, shader_program, projection);
+        self.project.profile_end();
+    }
+
+    fn draw_emitter(
+        &mut self,
+        emitter: usize,
+        sprites: &[Sprite],
+        shader_program: &ShaderProgram,
+        projection: &Mat4,
+    ) {
+        self.flush_batch(shader_program, projection);
+        let emitter = &mut self.project.emitters[emitter];
+        if let Some(profiler) = &mut self.project.profiler {
+            profiler.begin("draw", &format!("draw_particles({})", emitter.name));
+        }
+        emitter.draw(sprites, shader_program, projection);
+        self.project.profile_end();
+    }
+
+    // Draws the tile layers and particles that go below the sprites on layer `below` (or all of
+    // the ones left without it). Tile layers are drawn below the sprites of the same layer and
+    // particles above them.
+    fn draw_layers(
+        &mut self,
+        tile_layers: &mut Peekable<std::vec::IntoIter<(isize, usize, usize)>>,
+        emitters: &mut Peekable<std::vec::IntoIter<(isize, usize)>>,
+        below: Option<isize>,
+        sprites: &[Sprite],
+        shader_program: &ShaderProgram,
+        projection: &Mat4,
+    ) {
+        loop {
+            let tiles = tile_layers
+                .peek()
+                .map(|(layer, _, _)| *layer)
+                .filter(|layer| below.is_none_or(|below| *layer <= below));
+            let particles = emitters
+                .peek()
+                .map(|(layer, _)| *layer)
+                .filter(|layer| below.is_none_or(|below| *layer < below));
+            match (tiles, particles) {
+                (Some(tiles), Some(particles)) if particles < tiles => {
+                    let (_, emitter) = emitters.next().unwrap();
+                    self.draw_emitter(emitter, sprites, shader_program, projection);
+                }
+                (Some(_), _) => {
+                    let (_, map, layer) = tile_layers.next().unwrap();
+                    self.draw_tile_layer(map, layer, shader_program, projection);
+                }
+                (None, Some(_)) => {
+                    let (_, emitter) = emitters.next().unwrap();
+                    self.draw_emitter(emitter, sprites, shader_program, projection);
+                }
+                (None, None) => break,
+            }
+        }
+    }
+
+    // Applies changes to project files while the game keeps running. Code is swapped in without
+    // touching variables, and changed images are loaded again through the asset cache. Anything
+    // that goes wrong is reported and the old version is kept.
+    fn hot_reload(&mut self) {
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        let changed = watcher.changed();
+        if changed.is_empty() {
+            return;
+        }
+        let dir = self.project_file.parent().unwrap().to_path_buf();
+        for path in &changed {
+            console::info(
+                None,
+                format!(
+                    "Hot reload: {} changed",
+                    path.strip_prefix(&dir).unwrap_or(path).display()
+                ),
+            );
+        }
+
+        let config = match self.read_config() {
+            Ok(config) => config,
+            Err(e) => {
+                console::error(
+                    None,
+                    format!("Hot reload: failed to read project.toml: {}", e),
+                );
+                return;
+            }
+        };
+        // Everything loaded from the changed files is loaded again when it's next asked for
+        self.project.assets.forget(&changed);
+        let config_changed = changed.contains(&self.project_file);
+        let code_changed = config_changed
+            || changed
+                .iter()
+                .any(|p| p.extension().is_some_and(|e| e == "crst"));
+        let tags = config.tags.clone().unwrap_or_default();
+
+        for sprite_config in &config.sprites {
+            let Some(sprite) = self
+                .project
+                .sprites
+                .iter_mut()
+                .find(|s| s.name == sprite_config.name)
+            else {
+                console::warning(
+                    None,
+                    format!(
+                        "Hot reload: sprite '{}' is new, restart to load it",
+                        sprite_config.name
+                    ),
+                );
+                continue;
+            };
+
+            if code_changed {
+                let debugging = self.project.debugger.is_some();
+                match load_sprite_ast(&dir, sprite_config, &tags, debugging) {
+                    Ok(ast) => {
+                        sprite.reload_code(SpriteCode::compile(
+                            ast,
+                            &dir.to_string_lossy(),
+                            debugging,
+                        ));
+                    }
+                    Err(errors) => {
+                        let mut report = vec![format!(
+                            "Hot reload: the code of sprite '{}' has errors, keeping the old code:",
+                            sprite.name
+                        )];
+                        for error in errors {
+                            report.push(format!("\t=> {}", error));
+                        }
+                        console::error(None, report.join("\n"));
+                    }
+                }
+            }
+
+            // Every costume is loaded again, since an animated image or a sheet can change how
+            // many costumes come after it
+            let costumes_changed = sprite_config
+                .costume_files(&dir)
+                .iter()
+                .any(|f| changed.contains(f));
+            if config_changed || costumes_changed {
+                let costumes = sprite_config.load_costumes(&dir, &mut self.project.assets);
+                // Costumes that are gone now are removed, but a sprite always keeps one
+                let count = costumes.textures.len().max(1);
+                while sprite.costumes.len() > count {
+                    sprite.remove_costume(sprite.costumes.len() - 1);
+                }
+                for (i, costume) in costumes.textures.into_iter().enumerate() {
+                    match costume {
+                        Ok(costume) if i < sprite.costumes.len() => {
+                            sprite.replace_costume(i, &costume)
+                        }
+                        Ok(costume) => sprite.add_costume(costume),
+                        Err(_) => {}
+                    }
+                }
+                sprite.set_animations(costumes.animations);
+                sprite.set_vector_costumes(costumes.vectors);
+            }
+        }
+
+        let backdrops = config.stage.map(|s| s.backdrops).unwrap_or_default();
+        if config_changed
+            || backdrops
+                .iter()
+                .any(|b| changed.contains(&dir.join(b.file())))
+        {
+            let (frames, animations) = load_backdrops(&backdrops, &dir, &mut self.project.assets);
+            let stage = &mut self.project.stage;
+            let count = frames.len().max(1);
+            while stage.backdrops.len() > count {
+                stage.remove_backdrop(stage.backdrops.len() - 1);
+            }
+            for (i, frame) in frames.into_iter().enumerate() {
+                match frame {
+                    Ok(backdrop) if i < stage.backdrops.len() => {
+                        stage.replace_backdrop(i, backdrop)
+                    }
+                    Ok(backdrop) => stage.add_backdrop(backdrop),
+                    Err(_) => {}
+                }
+            }
+            stage.animations = animations;
+        }
+
+        // Edits made with `set_tile()` are lost, since the whole map is loaded again
+        for tilemap_config in config.tilemaps.iter().flatten() {
+            if !config_changed && !changed.contains(&dir.join(&tilemap_config.file)) {
+                continue;
+            }
+            let tilemap = match tilemap_config.load(&dir) {
+                Ok(tilemap) => tilemap,
+                Err(e) => {
+                    console::error(None, format!("Hot reload: {}", e));
+                    continue;
+                }
+            };
+            let tilemaps = &mut self.project.tilemaps;
+            match tilemaps.iter_mut().find(|t| t.name == tilemap.name) {
+                Some(old) => *old = tilemap,
+                None => tilemaps.push(tilemap),
+            }
+        }
+
+        // Emitters keep their particles, only their options change
+        for emitter_config in config.emitters.iter().flatten() {
+            let texture_changed = match emitter_config.options.get("texture") {
+                Some(toml::Value::String(texture)) => changed.contains(&dir.join(texture)),
+                _ => false,
+            };
+            if !config_changed && !texture_changed {
+                continue;
+            }
+            let emitters = &mut self.project.emitters;
+            match emitters.iter_mut().find(|e| e.name == emitter_config.name) {
+                Some(emitter) => emitter_config.apply(emitter, &dir),
+                None => {
+                    let mut emitter = Emitter::new(&emitter_config.name);
+                    emitter_config.apply(&mut emitter, &dir);
+                    emitters.push(emitter);
+                }
+            }
+        }
+
+        if config_changed {
+            self.project.watchdog = limits_watchdog(config.limits.as_ref());
+            if let Some(physics) = &config.physics {
+                apply_physics_config(&mut self.project.physics, physics);
+            }
+            self.debug_options = config.debug_options.unwrap_or_default();
+            let visible = self.overlay.visible;
+            self.overlay = DebugOverlay::new(&self.debug_options, config.watch.unwrap_or_default());
+            self.overlay.visible = visible;
+            self.watch_project_files();
+            self.update_profiler();
+        }
+
+        // The old versions of the reloaded files are freed once nothing uses them
+        self.project.assets.prune();
+    }
+
+    // Runs the project until the window is closed and returns the exit code requested by the
+    // scripts through `exit()`. Without one, a headless run fails if any `assert` failed.
+    pub fn run(&mut self, window: &mut dyn WindowBackend, shader_program: &ShaderProgram) -> i32 {
         let mut input_manager = InputManager::new();
         let mut projection = Mat4::orthographic_rh_gl(
             -window.get_size().0 as f32,
@@ -393,22 +1115,129 @@ impl Runtime {
         let mut fps = 0.0;
         while !window.should_close() {
             let now = std::time::Instant::now();
-            let dt = now.duration_since(last_time).as_secs_f32();
+            // Headless runs use a fixed timestep so they behave the same on every machine
+            let dt = if window.is_headless() {
+                1.0 / 60.0
+            } else {
+                now.duration_since(last_time).as_secs_f32()
+            };
             if duration.elapsed().as_secs_f32() >= 1.0 {
                 fps = 1.0 / dt;
                 duration = std::time::Instant::now();
             }
             last_time = now;
+            self.project.profile_begin("frame", || "frame".to_string());
 
-            glfw.poll_events();
+            window.poll_events(&mut input_manager);
+            // Expressions typed into the console run in the sprite the console points at (the
+            // first sprite by default). Values typed into the inspector are evaluated the same
+            // way, in the inspected sprite, and then stored in the field being edited.
+            // The console and the inspector can run any code and change any sprite, so shipped
+            // games only have them when they are turned on
+            let console_enabled = self.debug_options.contains(&"console".to_string());
+            if !console_enabled {
+                self.console.open = false;
+                self.inspector.active = false;
+            }
+            let console_input = if console_enabled {
+                self.console.update(&mut input_manager)
+            } else {
+                None
+            };
+            let console_expression = console_input.map(|expression| {
+                let target = self
+                    .console
+                    .target
+                    .clone()
+                    .or_else(|| self.project.sprites.first().map(|s| s.name.clone()))
+                    .unwrap_or_default();
+                let path = SpritePath {
+                    name: target,
+                    clones: vec![],
+                };
+                (path, expression, None)
+            });
+            let inspector_edit = if console_enabled {
+                self.inspector
+                    .update(&self.project.sprites, &mut input_manager, window)
+            } else {
+                None
+            };
+            let inspector_edit =
+                inspector_edit.map(|edit| (edit.path, edit.expression, Some(edit.field)));
+            if let Some((path, expression, field)) = console_expression.or(inspector_edit) {
+                let target = path.name.clone();
+                let mut sprites = std::mem::take(&mut self.project.sprites);
+                let snapshots = Snapshots::new(&sprites);
+                let result = match find_sprite_mut(&mut sprites, &path) {
+                    Some(sprite) => {
+                        self.project.begin_script();
+                        let result = debugger::evaluate(
+                            &expression,
+                            &mut State {
+                                start,
+                                dt,
+                                sprite,
+                                project: &mut self.project,
+                                snapshots: &snapshots,
+                                window,
+                                input_manager: &mut input_manager,
+                                audio_manager: self.audio_manager.as_mut(),
+                                shader_program,
+                                projection: &mut projection,
+                                font: &self.font,
+                                local_vars: &mut [],
+                                script_id: 0,
+                            },
+                        );
+                        let result = self.project.watchdog.take_error().map_or(result, Err);
+                        match (result, &field) {
+                            (Ok(value), Some(field)) => {
+                                apply_edit(sprite, field, value).map(|_| Value::Null)
+                            }
+                            (result, _) => result,
+                        }
+                    }
+                    None => Err(format!("There is no sprite named '{}'", target)),
+                };
+                self.project.sprites = sprites;
+                match (result, field) {
+                    (Ok(_), Some(_)) => {}
+                    (Ok(Value::String(s)), None) => {
+                        console::info(Some(&target), format!("{:?}", s))
+                    }
+                    (Ok(value), None) => console::info(Some(&target), value.to_string()),
+                    (Err(e), _) => console::error(Some(&target), e),
+                }
+            }
+            if input_manager.is_key_pressed(glfw::Key::F3) {
+                self.overlay.visible = !self.overlay.visible;
+            }
 
-            input_manager.update(window, events);
+            self.hot_reload();
 
+            if let Some(debugger) = &mut self.project.debugger {
+                debugger.poll();
+                if !debugger.is_connected() {
+                    console::info(None, "Debugger detached");
+                    self.project.debugger = None;
+                }
+            }
+
+            self.project.stage.step(dt);
+            self.project
+                .profile_begin("draw", || "Stage::draw".to_string());
             self.project.stage.draw(window, shader_program, &projection);
+            self.project.profile_end();
 
             let mut sprites = std::mem::take(&mut self.project.sprites);
 
-            let snapshots: Vec<SpriteSnapshot> = sprites.iter().map(|s| s.into()).collect();
+            self.project
+                .profile_begin("physics", || "step_physics".to_string());
+            step_physics(&mut sprites, &mut self.project.physics, dt);
+            self.project.profile_end();
+
+            let snapshots = Snapshots::new(&sprites);
 
             let mut remove_sprites = vec![];
             let sprites_len = sprites.len();
@@ -445,6 +1274,7 @@ impl Runtime {
             }
 
             for sprite in &mut sprites {
+                self.project.profile_begin("sprite", || sprite.name.clone());
                 sprite.step(
                     start,
                     dt,
@@ -452,27 +1282,66 @@ impl Runtime {
                     &snapshots,
                     window,
                     &mut input_manager,
-                    glfw,
-                    &mut self.audio_manager,
+                    self.audio_manager.as_mut(),
                     shader_program,
                     &mut projection,
                     &self.font,
                 );
+                self.project.profile_end();
             }
 
+            self.project
+                .profile_begin("particles", || "step_particles".to_string());
+            for emitter in &mut self.project.emitters {
+                emitter.step(dt, &sprites);
+            }
+            self.project.emitters.retain(|e| !e.finished());
+            self.project.profile_end();
+
             sprites.sort_by(|a, b| a.layer.cmp(&b.layer));
 
-            for sprite in &mut sprites {
-                draw_sprite(sprite, shader_program, projection, &self.font);
+            let mut tile_layers = tile_layer_order(&self.project.tilemaps)
+                .into_iter()
+                .peekable();
+            let mut emitters = emitter_order(&self.project.emitters).into_iter().peekable();
+            for sprite in &sprites {
+                self.draw_layers(
+                    &mut tile_layers,
+                    &mut emitters,
+                    Some(sprite.layer),
+                    &sprites,
+                    shader_program,
+                    &projection,
+                );
+                // Only queues the sprite, unless it can't join the batch so far (or has a speech
+                // bubble), which draws the batch first. The rest is measured by `draw_batch`.
+                self.project
+                    .profile_begin("draw", || format!("queue_sprite({})", sprite.name));
+                self.batch
+                    .draw_sprite(sprite, shader_program, projection, &self.font);
+                self.project.profile_end();
             }
+            self.draw_layers(
+                &mut tile_layers,
+                &mut emitters,
+                None,
+                &sprites,
+                shader_program,
+                &projection,
+            );
+            self.flush_batch(shader_program, &projection);
+
+            self.overlay
+                .draw_sprites(&sprites, shader_program, projection, &self.font);
 
             self.project.sprites = sprites;
 
             let mut debug_texts = Vec::new();
-            if self.debug_options.contains(&"show_fps".to_string()) {
+            let overlay_visible = self.overlay.visible;
+            if overlay_visible && self.debug_options.contains(&"show_fps".to_string()) {
                 debug_texts.push(format!("FPS: {:.2}", fps));
             }
-            if self.debug_options.contains(&"show_mouse_pos".to_string()) {
+            if overlay_visible && self.debug_options.contains(&"show_mouse_pos".to_string()) {
                 let pos = vec2(
                     window.get_cursor_pos().0 as f32,
                     window.get_cursor_pos().1 as f32,
@@ -481,6 +1350,9 @@ impl Runtime {
                 let pos = pos * Vec2::new(1.0, -1.0);
                 debug_texts.push(format!("Mouse: {:?}", pos));
             }
+            if overlay_visible {
+                debug_texts.extend(self.overlay.text_lines(&self.project));
+            }
 
             for (i, text) in debug_texts.iter().enumerate() {
                 draw_text(TextParams {
@@ -493,8 +1365,199 @@ impl Runtime {
                 });
             }
 
+            self.project.profile_end();
+            if let Some(profiler) = &mut self.project.profiler {
+                profiler.end_frame();
+                let lines = if overlay_visible {
+                    profiler.overlay_lines(10)
+                } else {
+                    vec![]
+                };
+                let top = 10.0 + debug_texts.len() as f32 * 30.0;
+                for (i, text) in lines.iter().enumerate() {
+                    draw_text(TextParams {
+                        text,
+                        projection: top_left_projection,
+                        pos: Vec2::new(10.0, top + i as f32 * 20.0),
+                        font_size: 16.0,
+                        down_positive: true,
+                        ..TextParams::default_params(&self.font, shader_program)
+                    });
+                }
+            }
+
+            self.inspector.draw(
+                &self.project.sprites,
+                shader_program,
+                (projection, top_left_projection),
+                &self.font,
+                window.get_size(),
+            );
+
+            self.console.draw(
+                &self.font,
+                shader_program,
+                top_left_projection,
+                window.get_size(),
+            );
+
             window.swap_buffers();
         }
+
+        // Only headless runs fail on asserts, so normal runs exit the way they always have
+        let asserts_failed = window.is_headless() && !self.project.assert_failures.is_empty();
+        let exit_code = self
+            .project
+            .exit_code
+            .unwrap_or(if asserts_failed { 1 } else { 0 });
+        if let Some(debugger) = &mut self.project.debugger {
+            debugger.finish(exit_code);
+        }
+        self.write_profile();
+        exit_code
+    }
+}
+
+fn limits_watchdog(limits: Option<&LimitsConfig>) -> Watchdog {
+    let defaults = Watchdog::default();
+    let Some(limits) = limits else {
+        return defaults;
+    };
+    let action = match limits.on_exceed.as_deref() {
+        Some("suspend") => LimitAction::Suspend,
+        Some("stop") | None => LimitAction::Stop,
+        Some(other) => {
+            console::warning(
+                None,
+                format!("Unknown limits.on_exceed value '{}', using 'stop'", other),
+            );
+            LimitAction::Stop
+        }
+    };
+    // A time of 0 turns the time limit off
+    let max_script_time = match limits.max_script_time {
+        Some(0) => None,
+        Some(ms) => Some(std::time::Duration::from_millis(ms)),
+        None => defaults.max_script_time,
+    };
+    Watchdog::new(
+        limits.max_instructions.unwrap_or(defaults.max_instructions),
+        max_script_time,
+        limits.max_call_depth.unwrap_or(defaults.max_call_depth),
+        action,
+    )
+}
+
+fn apply_physics_config(world: &mut PhysicsWorld, config: &PhysicsConfig) {
+    if let Some([x, y]) = config.gravity {
+        world.gravity = vec2(x, y);
+    }
+    match config.step {
+        Some(step) if step > 0.0 => world.step = step,
+        Some(step) => console::warning(None, format!("Invalid physics.step {}, ignoring it", step)),
+        None => {}
+    }
+}
+
+fn toml_to_value(value: &toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s.clone()),
+        toml::Value::Integer(n) => Value::Number(*n as f32),
+        toml::Value::Float(n) => Value::Number(*n as f32),
+        toml::Value::Boolean(b) => Value::Boolean(*b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(a) => Value::List(a.iter().map(toml_to_value).collect()),
+        toml::Value::Table(t) => Value::Object(
+            t.iter()
+                .map(|(k, v)| (k.clone(), toml_to_value(v)))
+                .collect(),
+        ),
+    }
+}
+
+// Every backdrop, with every frame of animated images, and the animations of those images.
+// Backdrops that fail to load are errors holding their name so the rest keep their numbers.
+fn load_backdrops(
+    backdrops: &[CostumeConfig],
+    dir: &Path,
+    assets: &mut Assets,
+) -> (Vec<Result<Costume, String>>, Vec<Animation>) {
+    let mut costumes = vec![];
+    let mut animations = vec![];
+    for backdrop in backdrops {
+        match assets.image(&dir.join(backdrop.file())) {
+            Ok(set) => {
+                if !set.delays.is_empty() {
+                    animations.push(Animation::from_delays(costumes.len(), set.delays.clone()));
+                }
+                costumes.extend(set.named(&backdrop.name()).into_iter().map(Ok));
+            }
+            Err(e) => {
+                console::error(None, e);
+                costumes.push(Err(backdrop.name()));
+            }
+        }
+    }
+    (costumes, animations)
+}
+
+// Shown in place of costumes and backdrops that fail to load
+fn missing_costume(assets: &mut Assets) -> Costume {
+    let set = assets.costumes("missing", &[], |atlas| {
+        let image = image::load_from_memory(include_bytes!("../../assets/missing.png"))
+            .expect("The missing texture is a valid PNG");
+        let texture = CPUTexture::from_image(image.to_rgba8());
+        Ok(CostumeSet::still(Costume::new(&texture, atlas)))
+    });
+    set.expect("The missing texture always loads").frames[0].clone()
+}
+
+// Parses the code of a sprite and all of its tags, the same way `Runtime::new` does, but returns
+// the errors instead of printing them
+fn load_sprite_ast(
+    dir: &Path,
+    sprite: &SpriteConfig,
+    tags: &[TagConfig],
+    debugging: bool,
+) -> Result<Vec<Statement>, Vec<String>> {
+    let mut files = vec![dir.join(&sprite.code)];
+    for tag in tags {
+        if (tag.sprites.contains(&sprite.name) || tag.name == "*")
+            && let Some(code) = &tag.code
+        {
+            files.push(dir.join(code));
+        }
+    }
+
+    let mut ast = vec![];
+    let mut errors = vec![];
+    for file in files {
+        let code = match std::fs::read_to_string(&file) {
+            Ok(code) => code,
+            Err(e) => {
+                errors.push(format!("Failed to read {}: {}", file.display(), e));
+                continue;
+            }
+        };
+        let mut tokenizer = Tokenizer::new(code);
+        let tokens = tokenizer.tokenize_full();
+        let mut parser = Parser::new(tokens);
+        if debugging {
+            parser.set_file(&file);
+        }
+        let (file_ast, file_errors) = parser.parse_with_errors();
+        ast.extend(file_ast);
+        errors.extend(
+            file_errors
+                .into_iter()
+                .map(|e| format!("{}: {}", file.display(), e)),
+        );
+    }
+
+    if errors.is_empty() {
+        Ok(ast)
+    } else {
+        Err(errors)
     }
 }
 
@@ -79,6 +79,9 @@ pub fn builtins() -> HashMap<String, Callable> {
     builtin!(builtins, "turn_ccw", |st, ar| motion::turn_ccw(st, ar));
     builtin!(builtins, "goto", |st, ar| motion::goto(st, ar));
     builtin!(builtins, "glide", |st, ar| motion::glide(st, ar));
+    builtin!(builtins, "follow_path", |st, ar| motion::follow_path(st, ar));
+    builtin!(builtins, "stop_path", |st, _| motion::stop_path(st));
+    builtin!(builtins, "path_progress", |st, _| motion::path_progress(st));
     builtin!(builtins, "point", |st, ar| motion::point(st, ar));
     builtin!(builtins, "set_x", |st, ar| motion::set_pos(st, ar, "x"));
     builtin!(builtins, "change_x", |st, ar| motion::change_pos(st, ar, "x"));
@@ -90,6 +93,11 @@ pub fn builtins() -> HashMap<String, Callable> {
     builtin!(builtins, "x", |st, _| motion::position(st, "x"));
     builtin!(builtins, "y", |st, _| motion::position(st, "y"));
 
+    // TWEENING
+    builtin!(builtins, "tween", |st, ar| tweening::tween(st, ar));
+    builtin!(builtins, "cancel_tween", |st, ar| tweening::cancel_tween(st, ar));
+    builtin!(builtins, "tweening", |st, ar| tweening::tweening(st, ar));
+
     // LOOKS
     builtin!(builtins, "hide", |st, _| looks::hide(st));
     builtin!(builtins, "show", |st, _| looks::show(st));
@@ -98,9 +106,23 @@ pub fn builtins() -> HashMap<String, Callable> {
     builtin!(builtins, "switch_costume", |st, ar| looks::switch_costume(st, ar));
     builtin!(builtins, "next_costume", |st, _| looks::next_costume(st));
     builtin!(builtins, "previous_costume", |st, _| looks::previous_costume(st));
+    builtin!(builtins, "load_costume", |st, ar| looks::load_costume(st, ar));
+    builtin!(builtins, "remove_costume", |st, ar| looks::remove_costume(st, ar));
+    builtin!(builtins, "create_costume", |st, ar| looks::create_costume(st, ar));
+    builtin!(builtins, "update_costume", |st, ar| looks::update_costume(st, ar));
+    builtin!(builtins, "get_pixel", |st, ar| looks::get_pixel(st, ar));
+    builtin!(builtins, "set_pixel", |st, ar| looks::set_pixel(st, ar));
+    builtin!(builtins, "costume_pixels", |st, ar| looks::costume_pixels(st, ar));
+    builtin!(builtins, "play_animation", |st, ar| looks::play_animation(st, ar));
+    builtin!(builtins, "stop_animation", |st, _| looks::stop_animation(st));
+    builtin!(builtins, "create_animation", |st, ar| looks::create_animation(st, ar));
+    builtin!(builtins, "animation", |st, _| looks::animation(st));
+    builtin!(builtins, "animation_finished", |st, ar| looks::animation_finished(st, ar));
     builtin!(builtins, "switch_backdrop",|st, ar| looks::switch_backdrop(st, ar));
     builtin!(builtins, "next_backdrop", |st, _| looks::next_backdrop(st));
     builtin!(builtins, "previous_backdrop", |st, _| looks::previous_backdrop(st));
+    builtin!(builtins, "load_backdrop", |st, ar| looks::load_backdrop(st, ar));
+    builtin!(builtins, "remove_backdrop", |st, ar| looks::remove_backdrop(st, ar));
     builtin!(builtins, "set_scale", |st, ar| looks::set_scale(st, ar));
     builtin!(builtins, "change_scale", |st, ar| looks::change_scale(st, ar));
     builtin!(builtins, "set_effect", |st, ar| looks::set_effect(st, ar));
@@ -111,6 +133,10 @@ pub fn builtins() -> HashMap<String, Callable> {
     builtin!(builtins, "go_by_layers", |st, ar| looks::go_by_layers(st, ar));
     builtin!(builtins, "costume", |st, _| looks::costume(st));
     builtin!(builtins, "backdrop", |st, _| looks::backdrop(st));
+    builtin!(builtins, "costume_name", |st, _| looks::costume_name(st));
+    builtin!(builtins, "backdrop_name", |st, _| looks::backdrop_name(st));
+    builtin!(builtins, "costumes", |st, _| looks::costumes(st));
+    builtin!(builtins, "backdrops", |st, _| looks::backdrops(st));
     builtin!(builtins, "size", |st, _| looks::size(st));
     builtin!(builtins, "scale", |st, _| looks::scale(st));
     builtin!(builtins, "bounds", |st, _| looks::bounds(st));
@@ -124,6 +150,9 @@ pub fn builtins() -> HashMap<String, Callable> {
     builtin!(builtins, "change_sound_filter", |st, ar| sounds::change_sound_filter(st, ar));
     builtin!(builtins, "set_sound_filter", |st, ar| sounds::set_sound_filter(st, ar));
     builtin!(builtins, "sound_filter", |st, ar| sounds::sound_filter(st, ar));
+    builtin!(builtins, "load_sound", |st, ar| sounds::load_sound(st, ar));
+    builtin!(builtins, "remove_sound", |st, ar| sounds::remove_sound(st, ar));
+    builtin!(builtins, "sounds", |st, _| sounds::sounds(st));
 
     // EVENTS
     builtin!(builtins, "key_down", |st, ar| events::key_down(st, ar));
@@ -141,12 +170,68 @@ pub fn builtins() -> HashMap<String, Callable> {
     builtin!(builtins, "broadcast_id_of", |st, ar| events::broadcast_id_of(st, ar));
     builtin!(builtins, "broadcast", |st, ar| events::broadcast(st, ar));
 
+    // SENSING
+    builtin!(builtins, "touching", |st, ar| sensing::touching(st, ar));
+    builtin!(builtins, "touching_edge", |st, _| sensing::touching_edge(st));
+    builtin!(builtins, "touching_color", |st, ar| sensing::touching_color(st, ar));
+    builtin!(builtins, "sprites_in_radius", |st, ar| sensing::sprites_in_radius(st, ar));
+    builtin!(builtins, "sprites_in_rect", |st, ar| sensing::sprites_in_rect(st, ar));
+    builtin!(builtins, "nearest_sprite", |st, ar| sensing::nearest_sprite(st, ar));
+    builtin!(builtins, "raycast", |st, ar| sensing::raycast(st, ar));
+    builtin!(builtins, "line_of_sight", |st, ar| sensing::line_of_sight(st, ar));
+
+    // PHYSICS
+    builtin!(builtins, "set_body", |st, ar| physics::set_body(st, ar));
+    builtin!(builtins, "set_collider", |st, ar| physics::set_collider(st, ar));
+    builtin!(builtins, "set_mass", |st, ar| physics::set_body_property(st, ar, "mass"));
+    builtin!(builtins, "set_friction", |st, ar| physics::set_body_property(st, ar, "friction"));
+    builtin!(builtins, "set_restitution", |st, ar| physics::set_body_property(st, ar, "restitution"));
+    builtin!(builtins, "set_gravity_scale", |st, ar| physics::set_body_property(st, ar, "gravity_scale"));
+    builtin!(builtins, "set_fixed_rotation", |st, ar| physics::set_body_property(st, ar, "fixed_rotation"));
+    builtin!(builtins, "apply_force", |st, ar| physics::apply_force(st, ar));
+    builtin!(builtins, "apply_impulse", |st, ar| physics::apply_impulse(st, ar));
+    builtin!(builtins, "apply_torque", |st, ar| physics::apply_torque(st, ar));
+    builtin!(builtins, "set_velocity", |st, ar| physics::set_velocity(st, ar));
+    builtin!(builtins, "velocity", |st, _| physics::velocity(st));
+    builtin!(builtins, "set_angular_velocity", |st, ar| physics::set_angular_velocity(st, ar));
+    builtin!(builtins, "angular_velocity", |st, _| physics::angular_velocity(st));
+    builtin!(builtins, "set_gravity", |st, ar| physics::set_gravity(st, ar));
+    builtin!(builtins, "contacts", |st, _| physics::contacts(st));
+    builtin!(builtins, "contact_started", |st, ar| physics::contact_changed(st, ar, true));
+    builtin!(builtins, "contact_ended", |st, ar| physics::contact_changed(st, ar, false));
+
+    // TILEMAPS
+    builtin!(builtins, "tile_at", |st, ar| tiles::tile_at(st, ar));
+    builtin!(builtins, "set_tile", |st, ar| tiles::set_tile(st, ar));
+    builtin!(builtins, "set_tile_layer_visible", |st, ar| tiles::set_tile_layer_visible(st, ar));
+    builtin!(builtins, "world_to_tile", |st, ar| tiles::world_to_tile(st, ar));
+    builtin!(builtins, "tile_to_world", |st, ar| tiles::tile_to_world(st, ar));
+    builtin!(builtins, "tile_properties", |st, ar| tiles::tile_properties(st, ar));
+    builtin!(builtins, "tilemap_info", |st, ar| tiles::tilemap_info(st, ar));
+    builtin!(builtins, "tilemap_objects", |st, ar| tiles::tilemap_objects(st, ar));
+    builtin!(builtins, "touching_tile", |st, ar| tiles::touching_tile(st, ar));
+    builtin!(builtins, "move_and_collide", |st, ar| tiles::move_and_collide(st, ar));
+
+    // PATHFINDING
+    builtin!(builtins, "find_path", |_, ar| navigation::find_path(ar));
+    builtin!(builtins, "find_tile_path", |st, ar| navigation::find_tile_path(st, ar));
+    builtin!(builtins, "find_sprite_path", |st, ar| navigation::find_sprite_path(st, ar));
+
+    // PARTICLES
+    builtin!(builtins, "create_emitter", |st, ar| emitters::create_emitter(st, ar));
+    builtin!(builtins, "set_emitter", |st, ar| emitters::set_emitter(st, ar));
+    builtin!(builtins, "emit", |st, ar| emitters::emit(st, ar));
+    builtin!(builtins, "clear_particles", |st, ar| emitters::clear_particles(st, ar));
+    builtin!(builtins, "remove_emitter", |st, ar| emitters::remove_emitter(st, ar));
+    builtin!(builtins, "particle_count", |st, ar| emitters::particle_count(st, ar));
+
     // CONTROLS
     builtin!(builtins, "wait", |st, ar| controls::wait(st, ar));
     builtin!(builtins, "stop", |st, ar| controls::stop(st, ar));
     builtin!(builtins, "clone", |st, _| controls::clone(st));
     builtin!(builtins, "delete_clone", |st, ar| controls::delete_clone(st, ar));
     builtin!(builtins, "skip_further_execution_if", |st, ar| controls::skip_further_execution_if(st, ar));
+    builtin!(builtins, "exit", |st, ar| controls::exit(st, ar));
 
     // DRAWING
     builtin!(builtins, "set_color", |st, ar| drawing::set_color(st, ar));
@@ -0,0 +1,27 @@
+use glam::*;
+use std::collections::HashMap;
+
+use crate::utils::*;
+
+// Without a property name, tile collisions and tile paths use the tiles (or layers) with this
+// property
+pub(super) const SOLID: &str = "solid";
+
+pub(super) fn vector(vector: Vec2) -> Value {
+    Value::List(vec![Value::Number(vector.x), Value::Number(vector.y)])
+}
+
+// The optional options object at the end of a builtin's arguments
+pub(super) fn options<'a>(
+    function: &str,
+    args: &'a [Value],
+) -> std::result::Result<Option<&'a HashMap<String, Value>>, String> {
+    match args {
+        [] => Ok(None),
+        [Value::Object(options)] => Ok(Some(options)),
+        _ => Err(format!(
+            "{}() expects an options object as its last argument",
+            function
+        )),
+    }
+}
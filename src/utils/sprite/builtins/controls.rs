@@ -72,3 +72,14 @@ pub fn skip_further_execution_if(state: &mut State, args: &[Value]) -> Result {
         Err("skip_further_execution_if() requires a single boolean argument".to_string())
     }
 }
+
+pub fn exit(state: &mut State, args: &[Value]) -> Result {
+    let code = match args {
+        [] => 0,
+        [Value::Number(code)] => *code as i32,
+        _ => return Err("exit() requires zero or one numeric argument".to_string()),
+    };
+    state.project.exit_code = Some(code);
+    state.window.set_should_close(true);
+    Ok(Value::Null)
+}
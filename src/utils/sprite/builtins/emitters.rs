@@ -0,0 +1,97 @@
+use std::path::Path;
+
+use super::common::options;
+use crate::utils::*;
+
+fn emitter_mut<'a>(
+    state: &'a mut State,
+    name: &str,
+) -> std::result::Result<&'a mut Emitter, String> {
+    state
+        .project
+        .emitters
+        .iter_mut()
+        .find(|e| e.name == name)
+        .ok_or_else(|| format!("There is no emitter named '{}'", name))
+}
+
+// Replaces any emitter with the same name. Without a position or a sprite, the emitter follows
+// the sprite that created it.
+pub fn create_emitter(state: &mut State, args: &[Value]) -> Result {
+    let [Value::String(name), rest @ ..] = args else {
+        return Err(
+            "create_emitter() requires a name and optionally an options object".to_string(),
+        );
+    };
+    let options = options("create_emitter", rest)?;
+    let mut emitter = Emitter::new(name);
+    emitter.owner = Some(state.sprite.name.clone());
+    let placed = options.is_some_and(|o| ["x", "y", "sprite"].iter().any(|k| o.contains_key(*k)));
+    if !placed {
+        emitter.sprite = Some(state.sprite.name.clone());
+    }
+    if let Some(options) = options {
+        emitter.configure(options, Path::new(&state.project.home_path))?;
+    }
+    let emitters = &mut state.project.emitters;
+    emitters.retain(|e| e.name != *name);
+    emitters.push(emitter);
+    Ok(Value::Null)
+}
+
+pub fn set_emitter(state: &mut State, args: &[Value]) -> Result {
+    let [Value::String(name), Value::Object(options)] = args else {
+        return Err("set_emitter() requires a name and an options object".to_string());
+    };
+    let dir = state.project.home_path.clone();
+    emitter_mut(state, name)?.configure(options, Path::new(&dir))?;
+    Ok(Value::Null)
+}
+
+pub fn emit(state: &mut State, args: &[Value]) -> Result {
+    let [Value::String(name), Value::Number(count)] = args else {
+        return Err("emit() requires an emitter name and a number of particles".to_string());
+    };
+    if !count.is_finite() {
+        return Err(format!(
+            "emit() requires a finite number of particles, not {}",
+            count
+        ));
+    }
+    emitter_mut(state, name)?.emit(count.max(0.0) as usize);
+    Ok(Value::Null)
+}
+
+pub fn clear_particles(state: &mut State, args: &[Value]) -> Result {
+    let [Value::String(name)] = args else {
+        return Err("clear_particles() requires an emitter name".to_string());
+    };
+    emitter_mut(state, name)?.clear();
+    Ok(Value::Null)
+}
+
+pub fn remove_emitter(state: &mut State, args: &[Value]) -> Result {
+    let [Value::String(name)] = args else {
+        return Err("remove_emitter() requires an emitter name".to_string());
+    };
+    let emitters = &mut state.project.emitters;
+    let count = emitters.len();
+    emitters.retain(|e| e.name != *name);
+    if emitters.len() == count {
+        return Err(format!("There is no emitter named '{}'", name));
+    }
+    Ok(Value::Null)
+}
+
+pub fn particle_count(state: &State, args: &[Value]) -> Result {
+    let [Value::String(name)] = args else {
+        return Err("particle_count() requires an emitter name".to_string());
+    };
+    let emitter = state
+        .project
+        .emitters
+        .iter()
+        .find(|e| e.name == *name)
+        .ok_or_else(|| format!("There is no emitter named '{}'", name))?;
+    Ok(Value::Number(emitter.particle_count() as f32))
+}
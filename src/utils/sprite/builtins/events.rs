@@ -140,15 +140,13 @@ pub fn sprite_clicked(state: &State) -> function::Result {
 }
 
 pub fn is_backdrop(state: &State, args: &[Value]) -> function::Result {
-    if let [Value::Number(index)] = args {
-        let backdrop = state.project.stage.backdrop();
-        if backdrop == *index as usize {
-            Ok(Value::Boolean(true))
-        } else {
-            Ok(Value::Boolean(false))
-        }
-    } else {
-        Err("is_backdrop() requires a single string argument".to_string())
+    let backdrop = state.project.stage.backdrop();
+    match args {
+        [Value::Number(index)] => Ok(Value::Boolean(backdrop == *index as usize)),
+        [Value::String(name)] => Ok(Value::Boolean(
+            state.project.stage.backdrops[backdrop].name == *name,
+        )),
+        _ => Err("is_backdrop() requires a backdrop number or name".to_string()),
     }
 }
 
@@ -1,4 +1,8 @@
-use crate::utils::{sprite::Dialogue, *};
+use glam::U8Vec4;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::utils::{core::CPUTexture, sprite::Dialogue, *};
 
 pub fn hide(state: &mut State) -> Result {
     state.sprite.visible = false;
@@ -54,13 +58,303 @@ pub fn think(state: &mut State, args: &[Value]) -> Result {
     }
 }
 
+// A costume given by its number or its name
+fn costume_index(
+    state: &State,
+    costume: &Value,
+    which: &str,
+) -> std::result::Result<usize, String> {
+    match costume {
+        Value::Number(index) => Ok(*index as usize),
+        Value::String(name) => state
+            .sprite
+            .costume_index(name)
+            .ok_or_else(|| format!("Costume '{}' not found", name)),
+        _ => Err(format!("{}() requires a costume number or name", which)),
+    }
+}
+
+fn backdrop_index(
+    state: &State,
+    backdrop: &Value,
+    which: &str,
+) -> std::result::Result<usize, String> {
+    match backdrop {
+        Value::Number(index) => Ok(*index as usize),
+        Value::String(name) => state
+            .project
+            .stage
+            .backdrop_index(name)
+            .ok_or_else(|| format!("Backdrop '{}' not found", name)),
+        _ => Err(format!("{}() requires a backdrop number or name", which)),
+    }
+}
+
+// The costumes in an image file relative to the project, and the name they are given: `name` or
+// the file's name
+fn load_costume_set(
+    state: &mut State,
+    args: &[Value],
+    which: &str,
+) -> std::result::Result<(Arc<CostumeSet>, String), String> {
+    let (path, name) = match args {
+        [Value::String(path)] => (Path::new(path), None),
+        [Value::String(path), Value::String(name)] => (Path::new(path), Some(name.clone())),
+        _ => {
+            return Err(format!(
+                "{}() requires a file path and optionally a name",
+                which
+            ));
+        }
+    };
+    let name = name.unwrap_or_else(|| {
+        path.file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    });
+    let path = Path::new(&state.project.home_path).join(path);
+    Ok((state.project.assets.image(&path)?, name))
+}
+
 pub fn switch_costume(state: &mut State, args: &[Value]) -> Result {
-    if let [Value::Number(index)] = args {
-        state.sprite.set_costume(*index as usize);
+    if let [costume] = args {
+        let index = costume_index(state, costume, "switch_costume")?;
+        state.sprite.set_costume(index);
         Ok(Value::Null)
     } else {
-        Err("switch_costume() requires a single string argument".to_string())
+        Err("switch_costume() requires a costume number or name".to_string())
+    }
+}
+
+pub fn load_costume(state: &mut State, args: &[Value]) -> Result {
+    let (set, name) = load_costume_set(state, args, "load_costume")?;
+    let first = state.sprite.costumes.len();
+    // Animated images get an animation with the costume's name, like in `project.toml`
+    if !set.delays.is_empty() {
+        let mut animations = state.sprite.animations.clone();
+        animations.insert(
+            name.clone(),
+            Animation::from_delays(first, set.delays.clone()),
+        );
+        state.sprite.set_animations(animations);
+    }
+    for costume in set.named(&name) {
+        state.sprite.add_costume(costume);
+    }
+    Ok(Value::Number(first as f32))
+}
+
+pub fn remove_costume(state: &mut State, args: &[Value]) -> Result {
+    let [costume] = args else {
+        return Err("remove_costume() requires a costume number or name".to_string());
+    };
+    let index = costume_index(state, costume, "remove_costume")?;
+    if index >= state.sprite.costumes.len() {
+        return Err(format!("Costume {} not found", index));
+    }
+    if state.sprite.costumes.len() == 1 {
+        return Err("remove_costume() can't remove a sprite's last costume".to_string());
+    }
+    state.sprite.remove_costume(index);
+    state.project.assets.prune();
+    Ok(Value::Null)
+}
+
+// The largest width or height of a costume made from pixels, the most textures can be on most GPUs
+const MAX_COSTUME_SIZE: u32 = 8192;
+
+// A texture from a flat list of r, g, b, a values, like the pixels from `parse_image()`
+fn pixels_to_texture(
+    width: f32,
+    height: f32,
+    pixels: &[Value],
+    which: &str,
+) -> std::result::Result<CPUTexture, String> {
+    let (width, height) = (width.max(0.0) as u32, height.max(0.0) as u32);
+    if width == 0 || height == 0 || width > MAX_COSTUME_SIZE || height > MAX_COSTUME_SIZE {
+        return Err(format!(
+            "{}() requires a width and height from 1 to {}",
+            which, MAX_COSTUME_SIZE
+        ));
+    }
+    // Can't overflow, since both are at most `MAX_COSTUME_SIZE`
+    let expected = width as usize * height as usize * 4;
+    if pixels.len() != expected {
+        return Err(format!(
+            "{}() requires {} numbers for {}x{} pixels (r, g, b, a for each), got {}",
+            which,
+            expected,
+            width,
+            height,
+            pixels.len()
+        ));
+    }
+    let bytes: Vec<u8> = pixels
+        .iter()
+        .map(|v| v.to_number().clamp(0.0, 255.0) as u8)
+        .collect();
+    CPUTexture::load_from_bytes(&bytes, width, height)
+}
+
+fn texture_to_pixels(texture: &CPUTexture) -> Value {
+    Value::List(vec![
+        Value::Number(texture.width as f32),
+        Value::Number(texture.height as f32),
+        Value::List(
+            texture
+                .data
+                .iter()
+                .flat_map(|p| p.to_array())
+                .map(|b| Value::Number(b as f32))
+                .collect(),
+        ),
+    ])
+}
+
+pub fn create_costume(state: &mut State, args: &[Value]) -> Result {
+    let (width, height, pixels, name) = match args {
+        [Value::Number(w), Value::Number(h), Value::List(pixels)] => (*w, *h, pixels, None),
+        [
+            Value::Number(w),
+            Value::Number(h),
+            Value::List(pixels),
+            Value::String(name),
+        ] => (*w, *h, pixels, Some(name.clone())),
+        _ => {
+            return Err(
+                "create_costume() requires a width, a height and a list of pixels, optionally \
+                 with a name"
+                    .to_string(),
+            );
+        }
+    };
+    let texture = pixels_to_texture(width, height, pixels, "create_costume")?;
+    let index = state.sprite.costumes.len();
+    let name = name.unwrap_or_else(|| format!("costume {}", index));
+    let costume = Costume::editable(&texture, state.project.assets.atlas()).named(&name);
+    state.sprite.add_costume(costume);
+    Ok(Value::Number(index as f32))
+}
+
+pub fn get_pixel(state: &State, args: &[Value]) -> Result {
+    let [costume, Value::Number(x), Value::Number(y)] = args else {
+        return Err("get_pixel() requires a costume and the x and y of a pixel".to_string());
+    };
+    let index = costume_index(state, costume, "get_pixel")?;
+    let pixel = (*x >= 0.0 && *y >= 0.0)
+        .then(|| state.sprite.costume_pixel(index, *x as u32, *y as u32))
+        .flatten()
+        .ok_or_else(|| format!("get_pixel(): pixel {}, {} is outside of the costume", x, y))?;
+    Ok(Value::List(
+        pixel
+            .to_array()
+            .iter()
+            .map(|c| Value::Number(*c as f32))
+            .collect(),
+    ))
+}
+
+pub fn set_pixel(state: &mut State, args: &[Value]) -> Result {
+    let (costume, x, y, color) = match args {
+        [
+            costume,
+            Value::Number(x),
+            Value::Number(y),
+            Value::Number(r),
+            Value::Number(g),
+            Value::Number(b),
+        ] => (costume, *x, *y, [*r, *g, *b, 255.0]),
+        [
+            costume,
+            Value::Number(x),
+            Value::Number(y),
+            Value::Number(r),
+            Value::Number(g),
+            Value::Number(b),
+            Value::Number(a),
+        ] => (costume, *x, *y, [*r, *g, *b, *a]),
+        _ => {
+            return Err(
+                "set_pixel() requires a costume, the x and y of a pixel and r, g, b and \
+                 optionally a"
+                    .to_string(),
+            );
+        }
+    };
+    let index = costume_index(state, costume, "set_pixel")?;
+    let color = U8Vec4::from_array(color.map(|c| c.clamp(0.0, 255.0) as u8));
+    if x < 0.0
+        || y < 0.0
+        || !state
+            .sprite
+            .set_costume_pixel(index, x as u32, y as u32, color)
+    {
+        return Err(format!(
+            "set_pixel(): pixel {}, {} is outside of the costume",
+            x, y
+        ));
+    }
+    Ok(Value::Null)
+}
+
+pub fn costume_pixels(state: &State, args: &[Value]) -> Result {
+    let [costume] = args else {
+        return Err("costume_pixels() requires a costume number or name".to_string());
+    };
+    let index = costume_index(state, costume, "costume_pixels")?;
+    let texture = state
+        .sprite
+        .costume_pixels(index)
+        .ok_or_else(|| format!("Costume {} not found", index))?;
+    Ok(texture_to_pixels(&texture))
+}
+
+pub fn update_costume(state: &mut State, args: &[Value]) -> Result {
+    let (costume, texture) = match args {
+        [costume] => (costume, None),
+        [
+            costume,
+            Value::Number(w),
+            Value::Number(h),
+            Value::List(pixels),
+        ] => (
+            costume,
+            Some(pixels_to_texture(*w, *h, pixels, "update_costume")?),
+        ),
+        _ => {
+            return Err(
+                "update_costume() requires a costume, optionally with a width, a height and a \
+                 list of pixels"
+                    .to_string(),
+            );
+        }
+    };
+    let index = costume_index(state, costume, "update_costume")?;
+    if index >= state.sprite.costumes.len() {
+        return Err(format!("Costume {} not found", index));
     }
+    state
+        .sprite
+        .upload_costume_pixels(index, texture, state.project.assets.atlas());
+    state.project.assets.prune();
+    Ok(Value::Null)
+}
+
+pub fn costumes(state: &State) -> Result {
+    Ok(Value::List(
+        state
+            .sprite
+            .costumes
+            .iter()
+            .map(|c| Value::String(c.name.clone()))
+            .collect(),
+    ))
+}
+
+pub fn costume_name(state: &State) -> Result {
+    let name = &state.sprite.costumes[state.sprite.costume()].name;
+    Ok(Value::String(name.clone()))
 }
 
 pub fn next_costume(state: &mut State) -> Result {
@@ -74,12 +368,63 @@ pub fn previous_costume(state: &mut State) -> Result {
 }
 
 pub fn switch_backdrop(state: &mut State, args: &[Value]) -> Result {
-    if let [Value::Number(index)] = args {
-        state.project.stage.set_backdrop(*index as usize);
+    if let [backdrop] = args {
+        let index = backdrop_index(state, backdrop, "switch_backdrop")?;
+        state.project.stage.set_backdrop(index);
         Ok(Value::Null)
     } else {
-        Err("switch_backdrop() requires a single string argument".to_string())
+        Err("switch_backdrop() requires a backdrop number or name".to_string())
+    }
+}
+
+pub fn load_backdrop(state: &mut State, args: &[Value]) -> Result {
+    let (set, name) = load_costume_set(state, args, "load_backdrop")?;
+    let stage = &mut state.project.stage;
+    let first = stage.backdrops.len();
+    if !set.delays.is_empty() {
+        stage
+            .animations
+            .push(Animation::from_delays(first, set.delays.clone()));
+    }
+    for backdrop in set.named(&name) {
+        stage.add_backdrop(backdrop);
+    }
+    Ok(Value::Number(first as f32))
+}
+
+pub fn remove_backdrop(state: &mut State, args: &[Value]) -> Result {
+    let [backdrop] = args else {
+        return Err("remove_backdrop() requires a backdrop number or name".to_string());
+    };
+    let index = backdrop_index(state, backdrop, "remove_backdrop")?;
+    if index >= state.project.stage.backdrops.len() {
+        return Err(format!("Backdrop {} not found", index));
     }
+    if state.project.stage.backdrops.len() == 1 {
+        return Err("remove_backdrop() can't remove the stage's last backdrop".to_string());
+    }
+    state.project.stage.remove_backdrop(index);
+    state.project.assets.prune();
+    Ok(Value::Null)
+}
+
+pub fn backdrops(state: &State) -> Result {
+    Ok(Value::List(
+        state
+            .project
+            .stage
+            .backdrops
+            .iter()
+            .map(|b| Value::String(b.name.clone()))
+            .collect(),
+    ))
+}
+
+pub fn backdrop_name(state: &State) -> Result {
+    let stage = &state.project.stage;
+    Ok(Value::String(
+        stage.backdrops[stage.backdrop()].name.clone(),
+    ))
 }
 
 pub fn next_backdrop(state: &mut State) -> Result {
@@ -179,6 +524,79 @@ pub fn costume(state: &State) -> Result {
     Ok(Value::Number(state.sprite.costume() as f32))
 }
 
+pub fn play_animation(state: &mut State, args: &[Value]) -> Result {
+    let (name, restart) = match args {
+        [Value::String(name)] => (name, false),
+        [Value::String(name), Value::Boolean(restart)] => (name, *restart),
+        _ => {
+            return Err(
+                "play_animation() requires an animation name and optionally a boolean".to_string(),
+            );
+        }
+    };
+    if !state.sprite.play_animation(name, restart) {
+        return Err(format!("Animation '{}' not found", name));
+    }
+    Ok(Value::Null)
+}
+
+pub fn stop_animation(state: &mut State) -> Result {
+    state.sprite.animation = None;
+    Ok(Value::Null)
+}
+
+pub fn create_animation(state: &mut State, args: &[Value]) -> Result {
+    let (name, frames, fps, mode) = match args {
+        [Value::String(name), Value::List(frames), Value::Number(fps)] => {
+            (name, frames, *fps, AnimationMode::Loop)
+        }
+        [
+            Value::String(name),
+            Value::List(frames),
+            Value::Number(fps),
+            Value::String(mode),
+        ] => (name, frames, *fps, AnimationMode::parse(mode)?),
+        _ => {
+            return Err(
+                "create_animation() requires a name, a list of costumes and a number of frames \
+                 per second, optionally with a mode"
+                    .to_string(),
+            );
+        }
+    };
+    let frames = frames
+        .iter()
+        .map(|frame| match frame {
+            Value::Number(n) if *n >= 0.0 => Ok(*n as usize),
+            _ => Err("create_animation() requires the costumes to be costume numbers".to_string()),
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    state
+        .sprite
+        .animations
+        .insert(name.clone(), Animation::new(frames, fps, mode));
+    Ok(Value::Null)
+}
+
+pub fn animation(state: &State) -> Result {
+    Ok(state
+        .sprite
+        .animation
+        .as_ref()
+        .map_or(Value::Null, |a| Value::String(a.name.clone())))
+}
+
+pub fn animation_finished(state: &State, args: &[Value]) -> Result {
+    let playback = state.sprite.animation.as_ref();
+    match args {
+        [] => Ok(Value::Boolean(playback.is_some_and(|a| a.finished))),
+        [Value::String(name)] => Ok(Value::Boolean(
+            playback.is_some_and(|a| a.finished && a.name == *name),
+        )),
+        _ => Err("animation_finished() takes an optional animation name".to_string()),
+    }
+}
+
 pub fn backdrop(state: &State) -> Result {
     Ok(Value::Number(state.project.stage.backdrop() as f32))
 }
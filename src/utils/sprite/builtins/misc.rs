@@ -14,18 +14,12 @@ pub fn args(state: &State) -> Result {
 }
 
 pub fn print(state: &State, args: &[Value], raw: bool) -> Result {
-    println!(
-        "{}{}",
-        if !raw {
-            format!("{} => ", state.sprite.name)
-        } else {
-            "".to_string()
-        },
-        args.iter()
-            .map(|v| v.to_string())
-            .collect::<Vec<_>>()
-            .join(" ")
-    );
+    let text = args
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    console::info(if raw { None } else { Some(&state.sprite.name) }, text);
     Ok(Value::Null)
 }
 
@@ -92,7 +86,7 @@ pub fn property_of(state: &mut State, args: &[Value]) -> Result {
         _ => return Err("Second argument must be a string (property name)".to_string()),
     };
 
-    if let Some(snapshot) = state.snapshots.iter().find(|s| s.name == name) {
+    if let Some(snapshot) = state.snapshots.get(&name) {
         Ok(snapshot
             .get(&name)
             .ok_or(format!(
@@ -254,7 +248,7 @@ pub fn distance(state: &State, args: &[Value], to: bool) -> Result {
                 Ok(Value::Number(dist))
             }
             [Value::String(name)] => {
-                if let Some(other_sprite) = state.snapshots.iter().find(|s| s.name == *name) {
+                if let Some(other_sprite) = state.snapshots.get(name) {
                     let dist = state.sprite.center.distance(other_sprite.center);
                     Ok(Value::Number(dist))
                 } else if name == "mouse" {
@@ -388,21 +382,11 @@ pub fn screenshot(state: &State, args: &[Value]) -> Result {
         _ => return Err("screenshot() expects a string argument".to_string()),
     };
 
-    let full_path = Path::new(&state.project.export_path).join(file_name);
     let (width, height) = state.window.get_framebuffer_size();
-    let mut pixels = vec![0; (width * height * 3) as usize];
-
-    unsafe {
-        gl::ReadPixels(
-            0,
-            0,
-            width,
-            height,
-            gl::RGB,
-            gl::UNSIGNED_BYTE,
-            pixels.as_mut_ptr() as *mut _,
-        );
-    }
+    let Some(mut pixels) = core::with_renderer(|r| r.read_pixels(width, height)) else {
+        return Err("screenshot() is not available when running headless".to_string());
+    };
+    let full_path = Path::new(&state.project.export_path).join(file_name);
 
     let row_len = (width * 3) as usize;
     for y in 0..(height / 2) {
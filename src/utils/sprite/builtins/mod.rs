@@ -1,10 +1,17 @@
+mod common;
 mod controls;
 mod drawing;
+mod emitters;
 mod events;
 mod looks;
 mod misc;
 mod motion;
+mod navigation;
+mod physics;
+mod sensing;
 mod sounds;
+mod tiles;
+mod tweening;
 mod window;
 
 pub mod builtins;
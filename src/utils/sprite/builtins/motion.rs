@@ -48,7 +48,7 @@ pub fn goto(state: &mut State, args: &[Value]) -> Result {
                     ),
                 );
                 Ok(Value::Null)
-            } else if let Some(target) = state.snapshots.iter().find(|s| s.name == *name) {
+            } else if let Some(target) = state.snapshots.get(name) {
                 state.sprite.goto(target.center.x, target.center.y);
                 Ok(Value::Null)
             } else {
@@ -127,7 +127,7 @@ pub fn point(state: &mut State, args: &[Value]) -> Result {
             } else if name == "random" {
                 state.sprite.direction = rand::random_range(0.0..=360.0);
                 Ok(Value::Null)
-            } else if let Some(target) = state.snapshots.iter().find(|s| s.name == *name) {
+            } else if let Some(target) = state.snapshots.get(name) {
                 state.sprite.point(target.center.x, target.center.y);
                 Ok(Value::Null)
             } else {
@@ -213,3 +213,95 @@ pub fn position(state: &State, which: &str) -> Result {
         _ => Err(format!("Invalid position argument: '{}'", which)),
     }
 }
+
+fn path_points(points: &[Value]) -> std::result::Result<Vec<Vec2>, String> {
+    points
+        .iter()
+        .map(|point| match point {
+            Value::List(point) => match point.as_slice() {
+                [Value::Number(x), Value::Number(y)] => Ok(vec2(*x, *y)),
+                _ => Err("follow_path() requires every point to be [x, y]".to_string()),
+            },
+            _ => Err("follow_path() requires every point to be [x, y]".to_string()),
+        })
+        .collect()
+}
+
+// Replaces any path the sprite is already following. The sprite starts at the beginning of the
+// path (or at `start`) straight away.
+pub fn follow_path(state: &mut State, args: &[Value]) -> Result {
+    let (points, speed, options) = match args {
+        [Value::List(points), Value::Number(speed)] => (points, *speed, None),
+        [
+            Value::List(points),
+            Value::Number(speed),
+            Value::Object(options),
+        ] => (points, *speed, Some(options)),
+        _ => {
+            return Err(
+                "follow_path() requires a list of points and a speed, optionally with an \
+                 options object"
+                    .to_string(),
+            );
+        }
+    };
+    let option = |name: &str| options.and_then(|o| o.get(name)).unwrap_or(&Value::Null);
+    let flag = |name: &str| match option(name) {
+        Value::Null => Ok(false),
+        Value::Boolean(b) => Ok(*b),
+        _ => Err(format!("follow_path() option '{}' must be a boolean", name)),
+    };
+
+    let curve = match option("curve") {
+        Value::Null => PathCurve::Linear,
+        Value::String(curve) => PathCurve::parse(curve)?,
+        _ => return Err("follow_path() option 'curve' must be a string".to_string()),
+    };
+    let mode = if flag("ping_pong")? {
+        PathMode::PingPong
+    } else if flag("loop")? {
+        PathMode::Loop
+    } else {
+        PathMode::Once
+    };
+    let mut points = path_points(points)?;
+    if flag("relative")? {
+        let origin = state.sprite.center;
+        points.iter_mut().for_each(|p| *p += origin);
+    }
+    let route = Route::new(&points, curve, mode == PathMode::Loop)
+        .map_err(|e| format!("follow_path(): {}", e))?;
+
+    let mut follow = PathFollow::new(route, speed.max(0.0), mode);
+    follow.orient = flag("orient")?;
+    follow.distance = match option("start") {
+        Value::Null => 0.0,
+        Value::Number(start) => start.clamp(0.0, 1.0) * follow.route.length(),
+        _ => return Err("follow_path() option 'start' must be a number".to_string()),
+    };
+    follow.on_complete = match option("callback") {
+        Value::Null => None,
+        callback @ Value::Closure(_) => Some(callback.clone()),
+        _ => return Err("follow_path() option 'callback' must be a closure".to_string()),
+    };
+    let (position, tangent) = follow.route.sample(follow.distance);
+    state.sprite.center = position;
+    if follow.orient && tangent != Vec2::ZERO {
+        state.sprite.direction = tangent.y.atan2(tangent.x).to_degrees();
+    }
+    state.sprite.path = Some(follow);
+    Ok(Value::Null)
+}
+
+pub fn stop_path(state: &mut State) -> Result {
+    state.sprite.path = None;
+    Ok(Value::Null)
+}
+
+pub fn path_progress(state: &State) -> Result {
+    Ok(state
+        .sprite
+        .path
+        .as_ref()
+        .map_or(Value::Null, |follow| Value::Number(follow.progress())))
+}
@@ -0,0 +1,226 @@
+use glam::*;
+use std::collections::HashMap;
+
+use super::common::{SOLID, options, vector};
+use super::sensing;
+use crate::utils::*;
+
+// The tile property that makes a tile more expensive to walk over
+const COST: &str = "cost";
+// The default cell size when sprites are turned into a grid
+const CELL: f32 = 16.0;
+
+fn option<'a>(options: Option<&'a HashMap<String, Value>>, name: &str) -> Option<&'a Value> {
+    options.and_then(|o| o.get(name))
+}
+
+fn string_option<'a>(
+    function: &str,
+    options: Option<&'a HashMap<String, Value>>,
+    name: &str,
+) -> std::result::Result<Option<&'a str>, String> {
+    match option(options, name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s)),
+        Some(_) => Err(format!("{}() option '{}' must be a string", function, name)),
+    }
+}
+
+fn number_option(
+    function: &str,
+    options: Option<&HashMap<String, Value>>,
+    name: &str,
+) -> std::result::Result<Option<f32>, String> {
+    match option(options, name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Number(n)) => Ok(Some(*n)),
+        Some(_) => Err(format!("{}() option '{}' must be a number", function, name)),
+    }
+}
+
+// Runs A* with the `diagonals` and `max_nodes` options, which every variant shares
+fn search(
+    function: &str,
+    grid: &Grid,
+    start: IVec2,
+    goal: IVec2,
+    options: Option<&HashMap<String, Value>>,
+) -> std::result::Result<Option<Vec<IVec2>>, String> {
+    let diagonals = string_option(function, options, "diagonals")?
+        .map(Diagonals::parse)
+        .transpose()?
+        .unwrap_or(Diagonals::Never);
+    let max_nodes =
+        number_option(function, options, "max_nodes")?.map_or(usize::MAX, |n| n.max(0.0) as usize);
+    Ok(pathfinding::find_path(
+        grid, start, goal, diagonals, max_nodes,
+    ))
+}
+
+fn point(function: &str, value: &Value) -> std::result::Result<Vec2, String> {
+    match value {
+        Value::List(list) => match list.as_slice() {
+            [Value::Number(x), Value::Number(y)] => Ok(vec2(*x, *y)),
+            _ => Err(format!(
+                "{}() positions must be lists of two numbers",
+                function
+            )),
+        },
+        _ => Err(format!(
+            "{}() positions must be lists of two numbers",
+            function
+        )),
+    }
+}
+
+fn points(path: Option<Vec<IVec2>>, to_world: impl Fn(IVec2) -> Vec2) -> Value {
+    path.map_or(Value::Null, |path| {
+        Value::List(
+            path.into_iter()
+                .map(|cell| vector(to_world(cell)))
+                .collect(),
+        )
+    })
+}
+
+// A grid from a list of rows. Numbers are the cost of a cell, where 0 or less is a wall.
+// Booleans are a cost of 1 (true) or a wall (false).
+fn grid(rows: &[Value]) -> std::result::Result<Grid, String> {
+    let width = match rows.first() {
+        Some(Value::List(row)) => row.len(),
+        _ => 0,
+    };
+    let mut grid = Grid::new(width, rows.len());
+    for (y, row) in rows.iter().enumerate() {
+        let Value::List(row) = row else {
+            return Err("find_path() requires the grid to be a list of rows".to_string());
+        };
+        if row.len() != width {
+            return Err("find_path() requires every row of the grid to be as long".to_string());
+        }
+        for (x, cell) in row.iter().enumerate() {
+            let cost = match cell {
+                Value::Number(n) => *n,
+                Value::Boolean(true) => 1.0,
+                Value::Boolean(false) | Value::Null => 0.0,
+                _ => {
+                    return Err(
+                        "find_path() requires grid cells to be numbers or booleans".to_string()
+                    );
+                }
+            };
+            grid.set(ivec2(x as i32, y as i32), cost);
+        }
+    }
+    Ok(grid)
+}
+
+pub fn find_path(args: &[Value]) -> Result {
+    let [Value::List(rows), start, goal, rest @ ..] = args else {
+        return Err(
+            "find_path() requires a grid, a start [column, row] and a goal [column, row]"
+                .to_string(),
+        );
+    };
+    let options = options("find_path", rest)?;
+    let grid = grid(rows)?;
+    let start = point("find_path", start)?.floor().as_ivec2();
+    let goal = point("find_path", goal)?.floor().as_ivec2();
+    let path = search("find_path", &grid, start, goal, options)?;
+    Ok(points(path, |cell| cell.as_vec2()))
+}
+
+pub fn find_tile_path(state: &State, args: &[Value]) -> Result {
+    let [Value::String(map), start, goal, rest @ ..] = args else {
+        return Err(
+            "find_tile_path() requires a tilemap name, a start [x, y] and a goal [x, y]"
+                .to_string(),
+        );
+    };
+    let options = options("find_tile_path", rest)?;
+    let solid = string_option("find_tile_path", options, "property")?.unwrap_or(SOLID);
+    let cost = string_option("find_tile_path", options, "cost")?.unwrap_or(COST);
+    let tilemap = state
+        .project
+        .tilemaps
+        .iter()
+        .find(|t| t.name == *map)
+        .ok_or_else(|| format!("There is no tilemap named '{}'", map))?;
+
+    // Solid tiles are walls, the rest cost the highest `cost` of the tiles stacked on them
+    let mut grid = Grid::new(tilemap.columns as usize, tilemap.rows as usize);
+    for y in 0..tilemap.rows as i32 {
+        for x in 0..tilemap.columns as i32 {
+            let tile = ivec2(x, y);
+            let layers = 0..tilemap.layers.len();
+            if layers.clone().any(|l| tilemap.is_solid(l, tile, solid)) {
+                grid.set(tile, 0.0);
+                continue;
+            }
+            let weight = layers
+                .filter_map(|l| tilemap.tile(l, tile).filter(|id| *id != 0))
+                .filter_map(|id| tilemap.tile_properties(id)?.get(cost))
+                .map(Value::to_number)
+                .fold(None, |a: Option<f32>, b| Some(a.map_or(b, |a| a.max(b))));
+            if let Some(weight) = weight {
+                grid.set(tile, weight);
+            }
+        }
+    }
+
+    let start = tilemap.world_to_tile(point("find_tile_path", start)?);
+    let goal = tilemap.world_to_tile(point("find_tile_path", goal)?);
+    let path = search("find_tile_path", &grid, start, goal, options)?;
+    Ok(points(path, |tile| tilemap.tile_to_world(tile)))
+}
+
+// Other sprites are rasterised into a grid over the visible part of the world, and any cell
+// their rectangle touches (plus `padding`) is a wall
+pub fn find_sprite_path(state: &State, args: &[Value]) -> Result {
+    let [start, goal, rest @ ..] = args else {
+        return Err("find_sprite_path() requires a start [x, y] and a goal [x, y]".to_string());
+    };
+    let options = options("find_sprite_path", rest)?;
+    let target = string_option("find_sprite_path", options, "obstacles")?;
+    let cell = number_option("find_sprite_path", options, "cell")?.unwrap_or(CELL);
+    let padding = number_option("find_sprite_path", options, "padding")?
+        .unwrap_or(0.0)
+        .max(0.0);
+    if cell < 1.0 {
+        return Err("find_sprite_path() requires a cell size of at least 1".to_string());
+    }
+
+    let (width, height) = state.window.get_size();
+    let half = vec2(width as f32, height as f32);
+    let size = (half * 2.0 / cell).ceil().as_uvec2();
+    let origin = -half;
+    let to_cell = |point: Vec2| ((point - origin) / cell).floor().as_ivec2();
+    let to_world = |c: IVec2| origin + (c.as_vec2() + 0.5) * cell;
+
+    let mut grid = Grid::new(size.x as usize, size.y as usize);
+    for id in state.snapshots.grid.query(-half, half) {
+        let (snapshot, hitbox) = state.snapshots.hitbox(id);
+        if hitbox.name == state.sprite.name
+            || target.is_some_and(|t| !sensing::matches(snapshot, hitbox, t))
+        {
+            continue;
+        }
+        let (min, max) = hitbox.bounds();
+        let (a, b) = (to_cell(min - padding), to_cell(max + padding));
+        for y in a.y.max(0)..=b.y.min(size.y as i32 - 1) {
+            for x in a.x.max(0)..=b.x.min(size.x as i32 - 1) {
+                let c = ivec2(x, y);
+                let center = to_world(c);
+                let half_cell = Vec2::splat(cell / 2.0 + padding);
+                if hitbox.overlaps_rect(center - half_cell, center + half_cell) {
+                    grid.set(c, 0.0);
+                }
+            }
+        }
+    }
+
+    let start = to_cell(point("find_sprite_path", start)?);
+    let goal = to_cell(point("find_sprite_path", goal)?);
+    let path = search("find_sprite_path", &grid, start, goal, options)?;
+    Ok(points(path, to_world))
+}
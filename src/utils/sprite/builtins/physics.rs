@@ -0,0 +1,153 @@
+use glam::*;
+use std::collections::HashMap;
+
+use super::common::vector;
+use crate::utils::*;
+
+fn body<'a>(state: &'a mut State, function: &str) -> std::result::Result<&'a mut Body, String> {
+    state.sprite.body.as_mut().ok_or_else(|| {
+        format!(
+            "{}() requires a physics body, call set_body() first",
+            function
+        )
+    })
+}
+
+pub fn set_body(state: &mut State, args: &[Value]) -> Result {
+    let [Value::String(kind)] = args else {
+        return Err("set_body() requires a single string argument".to_string());
+    };
+    if kind == "none" {
+        state.sprite.body = None;
+        return Ok(Value::Null);
+    }
+    let kind = BodyKind::parse(kind)?;
+    match &mut state.sprite.body {
+        Some(body) => body.kind = kind,
+        None => state.sprite.body = Some(Body::new(kind)),
+    }
+    Ok(Value::Null)
+}
+
+pub fn set_collider(state: &mut State, args: &[Value]) -> Result {
+    let [Value::String(shape)] = args else {
+        return Err("set_collider() requires a single string argument".to_string());
+    };
+    body(state, "set_collider")?.shape = ColliderShape::parse(shape)?;
+    Ok(Value::Null)
+}
+
+pub fn set_body_property(state: &mut State, args: &[Value], property: &str) -> Result {
+    let function = format!("set_{}", property);
+    let body = body(state, &function)?;
+    match (property, args) {
+        ("mass", [Value::Number(n)]) => body.set_mass(*n)?,
+        ("friction", [Value::Number(n)]) => body.set_friction(*n)?,
+        ("restitution", [Value::Number(n)]) => body.set_restitution(*n)?,
+        ("gravity_scale", [Value::Number(n)]) => body.gravity_scale = *n,
+        ("fixed_rotation", [Value::Boolean(b)]) => body.fixed_rotation = *b,
+        ("fixed_rotation", _) => {
+            return Err(format!("{}() requires a single boolean argument", function));
+        }
+        _ => return Err(format!("{}() requires a single numeric argument", function)),
+    }
+    Ok(Value::Null)
+}
+
+pub fn apply_force(state: &mut State, args: &[Value]) -> Result {
+    let [Value::Number(x), Value::Number(y)] = args else {
+        return Err("apply_force() requires two numeric arguments: x, y".to_string());
+    };
+    body(state, "apply_force")?.force += vec2(*x, *y);
+    Ok(Value::Null)
+}
+
+pub fn apply_impulse(state: &mut State, args: &[Value]) -> Result {
+    let [Value::Number(x), Value::Number(y)] = args else {
+        return Err("apply_impulse() requires two numeric arguments: x, y".to_string());
+    };
+    let body = body(state, "apply_impulse")?;
+    if body.kind == BodyKind::Dynamic {
+        body.velocity += vec2(*x, *y) / body.mass;
+    }
+    Ok(Value::Null)
+}
+
+pub fn apply_torque(state: &mut State, args: &[Value]) -> Result {
+    let [Value::Number(torque)] = args else {
+        return Err("apply_torque() requires a single numeric argument".to_string());
+    };
+    body(state, "apply_torque")?.torque += *torque;
+    Ok(Value::Null)
+}
+
+pub fn set_velocity(state: &mut State, args: &[Value]) -> Result {
+    let [Value::Number(x), Value::Number(y)] = args else {
+        return Err("set_velocity() requires two numeric arguments: x, y".to_string());
+    };
+    body(state, "set_velocity")?.velocity = vec2(*x, *y);
+    Ok(Value::Null)
+}
+
+pub fn velocity(state: &mut State) -> Result {
+    Ok(vector(body(state, "velocity")?.velocity))
+}
+
+pub fn set_angular_velocity(state: &mut State, args: &[Value]) -> Result {
+    let [Value::Number(speed)] = args else {
+        return Err("set_angular_velocity() requires a single numeric argument".to_string());
+    };
+    body(state, "set_angular_velocity")?.angular_velocity = *speed;
+    Ok(Value::Null)
+}
+
+pub fn angular_velocity(state: &mut State) -> Result {
+    Ok(Value::Number(
+        body(state, "angular_velocity")?.angular_velocity,
+    ))
+}
+
+pub fn set_gravity(state: &mut State, args: &[Value]) -> Result {
+    let [Value::Number(x), Value::Number(y)] = args else {
+        return Err("set_gravity() requires two numeric arguments: x, y".to_string());
+    };
+    state.project.physics.gravity = vec2(*x, *y);
+    Ok(Value::Null)
+}
+
+pub fn contacts(state: &mut State) -> Result {
+    let contacts = body(state, "contacts")?
+        .contacts
+        .iter()
+        .map(|contact| {
+            Value::Object(HashMap::from([
+                ("name".to_string(), Value::String(contact.name.clone())),
+                ("sprite".to_string(), Value::String(contact.owner.clone())),
+                ("normal".to_string(), vector(contact.normal)),
+                ("point".to_string(), vector(contact.point)),
+            ]))
+        })
+        .collect();
+    Ok(Value::List(contacts))
+}
+
+// `contact_started()` and `contact_ended()`, optionally only for a sprite, tag or clone
+pub fn contact_changed(state: &mut State, args: &[Value], started: bool) -> Result {
+    let function = if started {
+        "contact_started"
+    } else {
+        "contact_ended"
+    };
+    let body = body(state, function)?;
+    let contacts = if started { &body.started } else { &body.ended };
+    match args {
+        [] => Ok(Value::Boolean(!contacts.is_empty())),
+        [Value::String(target)] => Ok(Value::Boolean(
+            contacts.iter().any(|contact| contact.matches(target)),
+        )),
+        _ => Err(format!(
+            "{}() requires a single string argument or no arguments",
+            function
+        )),
+    }
+}
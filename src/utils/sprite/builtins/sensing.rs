@@ -0,0 +1,299 @@
+use glam::*;
+use std::collections::HashMap;
+
+use crate::utils::{Hitbox, SpriteSnapshot, State, Value, function};
+
+// The other sprites and clones whose bounds overlap `min..max`, in the order they are drawn.
+// The current sprite is left out, since it's always touching itself.
+fn nearby<'a>(
+    state: &'a State,
+    min: Vec2,
+    max: Vec2,
+) -> impl DoubleEndedIterator<Item = (&'a SpriteSnapshot, &'a Hitbox)> {
+    state
+        .snapshots
+        .grid
+        .query(min, max)
+        .into_iter()
+        .map(|id| state.snapshots.hitbox(id))
+        .filter(|(_, hitbox)| hitbox.name != state.sprite.name)
+}
+
+// A sprite's name or tag covers its clones too, a clone's own name only that clone
+pub fn matches(snapshot: &SpriteSnapshot, hitbox: &Hitbox, target: &str) -> bool {
+    snapshot.name == target || snapshot.tags.iter().any(|t| t == target) || hitbox.name == target
+}
+
+// The optional last argument of the proximity queries
+fn target<'a>(function: &str, args: &'a [Value]) -> std::result::Result<Option<&'a str>, String> {
+    match args {
+        [] => Ok(None),
+        [Value::String(target)] => Ok(Some(target)),
+        _ => Err(format!(
+            "{}() expects a sprite name or tag as its last argument",
+            function
+        )),
+    }
+}
+
+fn names<'a>(hitboxes: impl Iterator<Item = &'a Hitbox>) -> Value {
+    Value::List(hitboxes.map(|h| Value::String(h.name.clone())).collect())
+}
+
+fn half_window(state: &State) -> Vec2 {
+    vec2(
+        state.window.get_size().0 as f32,
+        state.window.get_size().1 as f32,
+    )
+}
+
+pub fn touching(state: &State, args: &[Value]) -> function::Result {
+    let [Value::String(target)] = args else {
+        return Err("touching() requires a single string argument".to_string());
+    };
+    if !state.sprite.visible {
+        return Ok(Value::Boolean(false));
+    }
+    let hitbox = Hitbox::new(state.sprite);
+    let (min, max) = hitbox.bounds();
+    Ok(Value::Boolean(nearby(state, min, max).any(
+        |(snapshot, other)| matches(snapshot, other, target) && hitbox.touches(other),
+    )))
+}
+
+pub fn touching_edge(state: &State) -> function::Result {
+    if !state.sprite.visible {
+        return Ok(Value::Boolean(false));
+    }
+    Ok(Value::Boolean(
+        Hitbox::new(state.sprite).touches_edge(half_window(state)),
+    ))
+}
+
+pub fn touching_color(state: &State, args: &[Value]) -> function::Result {
+    let [Value::Number(r), Value::Number(g), Value::Number(b)] = args else {
+        return Err("touching_color() requires three number arguments: r, g, b".to_string());
+    };
+    if !state.sprite.visible {
+        return Ok(Value::Boolean(false));
+    }
+    let hitbox = Hitbox::new(state.sprite);
+    let color = U8Vec4::new(
+        r.clamp(0.0, 255.0) as u8,
+        g.clamp(0.0, 255.0) as u8,
+        b.clamp(0.0, 255.0) as u8,
+        255,
+    );
+    let (min, max) = hitbox.bounds();
+    let nearby = nearby(state, min, max)
+        .map(|(_, other)| other)
+        .filter(|other| hitbox.overlaps(other))
+        .collect::<Vec<_>>();
+    Ok(Value::Boolean(hitbox.touches_color(
+        color,
+        half_window(state),
+        |point| {
+            nearby
+                .iter()
+                .rev()
+                .find_map(|other| other.color_at(point))
+                .unwrap_or_else(|| state.project.stage.color_at(point, state.window))
+        },
+    )))
+}
+
+pub fn sprites_in_radius(state: &State, args: &[Value]) -> function::Result {
+    let [
+        Value::Number(x),
+        Value::Number(y),
+        Value::Number(radius),
+        rest @ ..,
+    ] = args
+    else {
+        return Err(
+            "sprites_in_radius() requires three number arguments: x, y, radius".to_string(),
+        );
+    };
+    let target = target("sprites_in_radius", rest)?;
+    let (point, radius) = (vec2(*x, *y), radius.max(0.0));
+    let mut found = nearby(state, point - radius, point + radius)
+        .filter(|(snapshot, hitbox)| target.is_none_or(|t| matches(snapshot, hitbox, t)))
+        .map(|(_, hitbox)| (hitbox.distance_to(point), hitbox))
+        .filter(|(distance, _)| *distance <= radius)
+        .collect::<Vec<_>>();
+    found.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Ok(names(found.into_iter().map(|(_, hitbox)| hitbox)))
+}
+
+pub fn sprites_in_rect(state: &State, args: &[Value]) -> function::Result {
+    let [
+        Value::Number(x1),
+        Value::Number(y1),
+        Value::Number(x2),
+        Value::Number(y2),
+        rest @ ..,
+    ] = args
+    else {
+        return Err("sprites_in_rect() requires four number arguments: x1, y1, x2, y2".to_string());
+    };
+    let target = target("sprites_in_rect", rest)?;
+    let (a, b) = (vec2(*x1, *y1), vec2(*x2, *y2));
+    let (min, max) = (a.min(b), a.max(b));
+    Ok(names(
+        nearby(state, min, max)
+            .filter(|(snapshot, hitbox)| target.is_none_or(|t| matches(snapshot, hitbox, t)))
+            .filter(|(_, hitbox)| hitbox.overlaps_rect(min, max))
+            .map(|(_, hitbox)| hitbox),
+    ))
+}
+
+pub fn nearest_sprite(state: &State, args: &[Value]) -> function::Result {
+    let target = target("nearest_sprite", args)?;
+    let nearest = state
+        .snapshots
+        .grid
+        .nearest(state.sprite.center, |snapshot, hitbox| {
+            let (snapshot, hitbox) = state.snapshots.hitbox((snapshot, hitbox));
+            hitbox.name != state.sprite.name && target.is_none_or(|t| matches(snapshot, hitbox, t))
+        });
+    Ok(nearest.map_or(Value::Null, |id| {
+        Value::String(state.snapshots.hitbox(id).1.name.clone())
+    }))
+}
+
+// The closest hit along a ray, out of the other sprites that `accept` allows
+fn cast<'a>(
+    state: &'a State,
+    origin: Vec2,
+    direction: Vec2,
+    max_distance: f32,
+    exact: bool,
+    accept: impl Fn(&SpriteSnapshot, &Hitbox) -> bool,
+) -> Option<(f32, Vec2, &'a SpriteSnapshot, &'a Hitbox)> {
+    // Keeps the end of the ray finite
+    let max_distance = max_distance.clamp(0.0, 1e7);
+    let end = origin + direction * max_distance;
+    nearby(state, origin.min(end), origin.max(end))
+        .filter(|(snapshot, hitbox)| accept(snapshot, hitbox))
+        .filter_map(|(snapshot, hitbox)| {
+            let (distance, normal) = hitbox.raycast(origin, direction, max_distance, exact)?;
+            Some((distance, normal, snapshot, hitbox))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+}
+
+pub fn raycast(state: &State, args: &[Value]) -> function::Result {
+    let [
+        Value::Number(x),
+        Value::Number(y),
+        Value::Number(dx),
+        Value::Number(dy),
+        Value::Number(max_distance),
+        rest @ ..,
+    ] = args
+    else {
+        return Err(
+            "raycast() requires five number arguments: x, y, dx, dy, max_distance".to_string(),
+        );
+    };
+    let (target, exact) = match rest {
+        [] => (None, true),
+        [Value::String(target)] => (Some(target.as_str()), true),
+        [Value::Null] => (None, true),
+        [Value::String(target), Value::Boolean(exact)] => (Some(target.as_str()), *exact),
+        [Value::Null, Value::Boolean(exact)] => (None, *exact),
+        _ => {
+            return Err(
+                "raycast() expects a sprite name or tag (or null) and whether to check pixels after the numbers"
+                    .to_string(),
+            );
+        }
+    };
+    let Some(direction) = vec2(*dx, *dy).try_normalize() else {
+        return Err("raycast() requires a direction that isn't 0, 0".to_string());
+    };
+    let origin = vec2(*x, *y);
+    let hit = cast(
+        state,
+        origin,
+        direction,
+        *max_distance,
+        exact,
+        |snapshot, hitbox| target.is_none_or(|t| matches(snapshot, hitbox, t)),
+    );
+    let Some((distance, normal, snapshot, hitbox)) = hit else {
+        return Ok(Value::Null);
+    };
+    let point = origin + direction * distance;
+    Ok(Value::Object(HashMap::from([
+        ("name".to_string(), Value::String(hitbox.name.clone())),
+        ("sprite".to_string(), Value::String(snapshot.name.clone())),
+        ("x".to_string(), Value::Number(point.x)),
+        ("y".to_string(), Value::Number(point.y)),
+        (
+            "normal".to_string(),
+            Value::List(vec![Value::Number(normal.x), Value::Number(normal.y)]),
+        ),
+        ("distance".to_string(), Value::Number(distance)),
+    ])))
+}
+
+// An end of a `line_of_sight()` line: an `[x, y]` position or the name of a sprite or clone
+fn endpoint<'a>(
+    state: &State,
+    value: &'a Value,
+) -> std::result::Result<(Vec2, Option<&'a str>), String> {
+    match value {
+        Value::List(list) => match list.as_slice() {
+            [Value::Number(x), Value::Number(y)] => Ok((vec2(*x, *y), None)),
+            _ => Err("line_of_sight() positions must be lists of two numbers".to_string()),
+        },
+        Value::String(name) if *name == state.sprite.name => Ok((state.sprite.center, Some(name))),
+        Value::String(name) => state
+            .snapshots
+            .get(name)
+            .map(|snapshot| snapshot.center)
+            .or_else(|| {
+                state
+                    .snapshots
+                    .iter()
+                    .flat_map(|snapshot| &snapshot.hitboxes)
+                    .find(|hitbox| hitbox.name == *name)
+                    .map(|hitbox| hitbox.center())
+            })
+            .map(|center| (center, Some(name.as_str())))
+            .ok_or_else(|| format!("line_of_sight() target '{}' not found", name)),
+        _ => Err("line_of_sight() requires two sprite names or [x, y] positions".to_string()),
+    }
+}
+
+pub fn line_of_sight(state: &State, args: &[Value]) -> function::Result {
+    let (a, b, target) = match args {
+        [a, b] => (a, b, None),
+        [a, b, Value::String(target)] => (a, b, Some(target.as_str())),
+        _ => {
+            return Err(
+                "line_of_sight() requires two sprite names or [x, y] positions, and optionally a sprite name or tag"
+                    .to_string(),
+            );
+        }
+    };
+    let ((from, a), (to, b)) = (endpoint(state, a)?, endpoint(state, b)?);
+    let Some(direction) = (to - from).try_normalize() else {
+        return Ok(Value::Boolean(true));
+    };
+    // The sprites at either end don't block the line
+    let blocked = cast(
+        state,
+        from,
+        direction,
+        from.distance(to),
+        true,
+        |snapshot, hitbox| {
+            Some(hitbox.name.as_str()) != a
+                && Some(hitbox.name.as_str()) != b
+                && target.is_none_or(|t| matches(snapshot, hitbox, t))
+        },
+    );
+    Ok(Value::Boolean(blocked.is_none()))
+}
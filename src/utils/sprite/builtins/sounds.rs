@@ -1,28 +1,14 @@
-use indexmap::IndexMap;
-use kira::{Tween, sound::static_sound::StaticSoundHandle};
+use kira::Tween;
+use std::path::Path;
 
 use crate::utils::{sprite::function::Result, *};
 
-fn update_sound_handle(sound_filters: &IndexMap<String, f32>, handle: &mut StaticSoundHandle) {
-    for (effect, value) in sound_filters {
-        match effect.as_str() {
-            "volume" => handle.set_volume(percentage_to_decibels(*value / 100.0), Tween::default()),
-            "pitch" => handle.set_playback_rate(*value as f64 / 100.0, Tween::default()),
-            "pan" => handle.set_panning((*value / 100.0) * 2.0 - 1.0, Tween::default()),
-            _ => continue,
-        }
-    }
-}
-
 pub fn play_sound(state: &mut State, args: &[Value]) -> Result {
     fn play_sound_inner(state: &mut State, name: &str) -> Result {
         if let Some(sound) = state.sprite.sounds.get(name) {
-            let mut handle = state
-                .audio_manager
-                .play(sound.clone())
-                .map_err(|e| e.to_string())?;
-            update_sound_handle(&state.sprite.sound_filters, &mut handle);
+            let handle = state.audio_manager.play(sound.clone())?;
             state.sprite.sound_handles.insert(name.to_string(), handle);
+            state.sprite.update_sound_handles();
             Ok(Value::Null)
         } else {
             Err(format!("Sound '{}' not found", name))
@@ -40,6 +26,41 @@ pub fn play_sound(state: &mut State, args: &[Value]) -> Result {
     }
 }
 
+pub fn load_sound(state: &mut State, args: &[Value]) -> Result {
+    if let [Value::String(path), Value::String(name)] = args {
+        let path = Path::new(&state.project.home_path).join(path);
+        let sound = state.project.assets.sound(&path)?;
+        state.sprite.add_sound(name, sound);
+        Ok(Value::Null)
+    } else {
+        Err("load_sound() requires a file path and a name".to_string())
+    }
+}
+
+pub fn remove_sound(state: &mut State, args: &[Value]) -> Result {
+    if let [Value::String(name)] = args {
+        if !state.sprite.remove_sound(name) {
+            return Err(format!("Sound '{}' not found", name));
+        }
+        state.project.assets.prune();
+        Ok(Value::Null)
+    } else {
+        Err("remove_sound() requires a single string argument".to_string())
+    }
+}
+
+// Sorted, since sounds are kept by name in no particular order
+pub fn sounds(state: &State) -> Result {
+    let mut names: Vec<&String> = state.sprite.sounds.keys().collect();
+    names.sort();
+    Ok(Value::List(
+        names
+            .into_iter()
+            .map(|n| Value::String(n.clone()))
+            .collect(),
+    ))
+}
+
 pub fn stop_all_sounds(state: &mut State) -> Result {
     for sound_handle in state.sprite.sound_handles.values_mut() {
         sound_handle.stop(Tween::default());
@@ -68,9 +89,7 @@ pub fn change_sound_filter(state: &mut State, args: &[Value]) -> Result {
             .entry(effect.clone())
             .and_modify(|v| *v += *value)
             .or_insert(*value);
-        for sound_handle in state.sprite.sound_handles.values_mut() {
-            update_sound_handle(&state.sprite.sound_filters, sound_handle);
-        }
+        state.sprite.update_sound_handles();
         Ok(Value::Null)
     } else {
         Err("change_sound_filter() requires a string and a numeric argument".to_string())
@@ -80,9 +99,7 @@ pub fn change_sound_filter(state: &mut State, args: &[Value]) -> Result {
 pub fn set_sound_filter(state: &mut State, args: &[Value]) -> Result {
     if let [Value::String(effect), Value::Number(value)] = args {
         state.sprite.sound_filters.insert(effect.clone(), *value);
-        for sound_handle in state.sprite.sound_handles.values_mut() {
-            update_sound_handle(&state.sprite.sound_filters, sound_handle);
-        }
+        state.sprite.update_sound_handles();
         Ok(Value::Null)
     } else {
         Err("set_sound_filter() requires a string and a numeric argument".to_string())
@@ -0,0 +1,278 @@
+use glam::*;
+use std::collections::HashMap;
+
+use super::common::{SOLID, vector};
+use crate::utils::*;
+
+fn tilemap<'a>(state: &'a State, name: &str) -> std::result::Result<&'a Tilemap, String> {
+    state
+        .project
+        .tilemaps
+        .iter()
+        .find(|t| t.name == name)
+        .ok_or_else(|| format!("There is no tilemap named '{}'", name))
+}
+
+fn tilemap_mut<'a>(
+    state: &'a mut State,
+    name: &str,
+) -> std::result::Result<&'a mut Tilemap, String> {
+    state
+        .project
+        .tilemaps
+        .iter_mut()
+        .find(|t| t.name == name)
+        .ok_or_else(|| format!("There is no tilemap named '{}'", name))
+}
+
+// A tile layer by name or index
+fn layer(tilemap: &Tilemap, layer: &Value) -> std::result::Result<usize, String> {
+    match layer {
+        Value::String(name) => tilemap
+            .layers
+            .iter()
+            .position(|l| l.name == *name)
+            .ok_or_else(|| {
+                format!(
+                    "The tilemap '{}' has no layer named '{}'",
+                    tilemap.name, name
+                )
+            }),
+        Value::Number(n) if *n >= 0.0 && (*n as usize) < tilemap.layers.len() => Ok(*n as usize),
+        _ => Err(format!(
+            "The tilemap '{}' has no layer {}",
+            tilemap.name,
+            layer.to_string()
+        )),
+    }
+}
+
+fn properties(properties: &HashMap<String, Value>) -> Value {
+    Value::Object(properties.clone())
+}
+
+pub fn tile_at(state: &State, args: &[Value]) -> Result {
+    let [
+        Value::String(map),
+        l,
+        Value::Number(column),
+        Value::Number(row),
+    ] = args
+    else {
+        return Err("tile_at() requires a tilemap name, a layer, a column and a row".to_string());
+    };
+    let tilemap = tilemap(state, map)?;
+    let layer = layer(tilemap, l)?;
+    let tile = ivec2(column.floor() as i32, row.floor() as i32);
+    Ok(Value::Number(tilemap.tile(layer, tile).unwrap_or(0) as f32))
+}
+
+pub fn set_tile(state: &mut State, args: &[Value]) -> Result {
+    let [
+        Value::String(map),
+        l,
+        Value::Number(column),
+        Value::Number(row),
+        Value::Number(id),
+    ] = args
+    else {
+        return Err(
+            "set_tile() requires a tilemap name, a layer, a column, a row and a tile id"
+                .to_string(),
+        );
+    };
+    let tilemap = tilemap_mut(state, map)?;
+    let layer = layer(tilemap, l)?;
+    let tile = ivec2(column.floor() as i32, row.floor() as i32);
+    tilemap.set_tile(layer, tile, id.max(0.0) as u32)?;
+    Ok(Value::Null)
+}
+
+pub fn set_tile_layer_visible(state: &mut State, args: &[Value]) -> Result {
+    let [Value::String(map), l, Value::Boolean(visible)] = args else {
+        return Err(
+            "set_tile_layer_visible() requires a tilemap name, a layer and a boolean".to_string(),
+        );
+    };
+    let tilemap = tilemap_mut(state, map)?;
+    let layer = layer(tilemap, l)?;
+    tilemap.layers[layer].visible = *visible;
+    Ok(Value::Null)
+}
+
+pub fn world_to_tile(state: &State, args: &[Value]) -> Result {
+    let [Value::String(map), Value::Number(x), Value::Number(y)] = args else {
+        return Err("world_to_tile() requires a tilemap name, x and y".to_string());
+    };
+    let tile = tilemap(state, map)?.world_to_tile(vec2(*x, *y));
+    Ok(vector(tile.as_vec2()))
+}
+
+pub fn tile_to_world(state: &State, args: &[Value]) -> Result {
+    let [
+        Value::String(map),
+        Value::Number(column),
+        Value::Number(row),
+    ] = args
+    else {
+        return Err("tile_to_world() requires a tilemap name, a column and a row".to_string());
+    };
+    let tile = ivec2(column.floor() as i32, row.floor() as i32);
+    Ok(vector(tilemap(state, map)?.tile_to_world(tile)))
+}
+
+pub fn tile_properties(state: &State, args: &[Value]) -> Result {
+    let [Value::String(map), Value::Number(id)] = args else {
+        return Err("tile_properties() requires a tilemap name and a tile id".to_string());
+    };
+    let tilemap = tilemap(state, map)?;
+    Ok(tilemap
+        .tile_properties(id.max(0.0) as u32)
+        .map_or(Value::Object(HashMap::new()), properties))
+}
+
+pub fn tilemap_info(state: &State, args: &[Value]) -> Result {
+    let [Value::String(map)] = args else {
+        return Err("tilemap_info() requires a tilemap name".to_string());
+    };
+    let tilemap = tilemap(state, map)?;
+    let cell = tilemap.cell_size();
+    Ok(Value::Object(HashMap::from([
+        ("columns".to_string(), Value::Number(tilemap.columns as f32)),
+        ("rows".to_string(), Value::Number(tilemap.rows as f32)),
+        ("tile_width".to_string(), Value::Number(cell.x)),
+        ("tile_height".to_string(), Value::Number(cell.y)),
+        ("x".to_string(), Value::Number(tilemap.origin.x)),
+        ("y".to_string(), Value::Number(tilemap.origin.y)),
+        (
+            "layers".to_string(),
+            Value::List(
+                tilemap
+                    .layers
+                    .iter()
+                    .map(|l| Value::String(l.name.clone()))
+                    .collect(),
+            ),
+        ),
+        ("properties".to_string(), properties(&tilemap.properties)),
+    ])))
+}
+
+pub fn tilemap_objects(state: &State, args: &[Value]) -> Result {
+    let (map, layer) = match args {
+        [Value::String(map)] => (map, None),
+        [Value::String(map), Value::String(layer)] => (map, Some(layer)),
+        _ => {
+            return Err(
+                "tilemap_objects() requires a tilemap name and optionally an object layer name"
+                    .to_string(),
+            );
+        }
+    };
+    let tilemap = tilemap(state, map)?;
+    let objects = tilemap
+        .objects
+        .iter()
+        .filter(|object| layer.is_none_or(|l| object.layer == *l))
+        .map(|object| {
+            let (center, size) = tilemap.object_bounds(object);
+            let points = object
+                .points
+                .iter()
+                .map(|point| vector(tilemap.to_world(object.position + *point)))
+                .collect();
+            Value::Object(HashMap::from([
+                ("id".to_string(), Value::Number(object.id as f32)),
+                ("name".to_string(), Value::String(object.name.clone())),
+                ("type".to_string(), Value::String(object.class.clone())),
+                ("layer".to_string(), Value::String(object.layer.clone())),
+                ("x".to_string(), Value::Number(center.x)),
+                ("y".to_string(), Value::Number(center.y)),
+                ("width".to_string(), Value::Number(size.x)),
+                ("height".to_string(), Value::Number(size.y)),
+                ("rotation".to_string(), Value::Number(object.rotation)),
+                ("tile".to_string(), Value::Number(object.tile as f32)),
+                ("points".to_string(), Value::List(points)),
+                ("properties".to_string(), properties(&object.properties)),
+            ]))
+        })
+        .collect();
+    Ok(Value::List(objects))
+}
+
+// The optional property name at the end of the tile collision functions
+fn property<'a>(function: &str, args: &'a [Value]) -> std::result::Result<&'a str, String> {
+    match args {
+        [] => Ok(SOLID),
+        [Value::String(property)] => Ok(property),
+        _ => Err(format!(
+            "{}() expects a property name as its last argument",
+            function
+        )),
+    }
+}
+
+pub fn touching_tile(state: &State, args: &[Value]) -> Result {
+    let [Value::String(map), rest @ ..] = args else {
+        return Err("touching_tile() requires a tilemap name".to_string());
+    };
+    let property = property("touching_tile", rest)?;
+    let tilemap = tilemap(state, map)?;
+    let hitbox = Hitbox::new(state.sprite);
+    let (min, max) = hitbox.bounds();
+    Ok(Value::Boolean(
+        tilemap
+            .solid_tiles(min, max, property)
+            .into_iter()
+            .any(|(min, max)| hitbox.overlaps_rect(min, max)),
+    ))
+}
+
+// Moves the sprite one axis at a time, stopping it at the first solid tile in the way. Tiles the
+// sprite is already inside of don't block it, so it can always get out.
+pub fn move_and_collide(state: &mut State, args: &[Value]) -> Result {
+    let [
+        Value::String(map),
+        Value::Number(dx),
+        Value::Number(dy),
+        rest @ ..,
+    ] = args
+    else {
+        return Err("move_and_collide() requires a tilemap name, dx and dy".to_string());
+    };
+    let property = property("move_and_collide", rest)?;
+    let tilemap = tilemap(state, map)?;
+    let (mut min, mut max) = Hitbox::new(state.sprite).bounds();
+    let mut offset = Vec2::ZERO;
+    let mut blocked = [false; 2];
+    for (axis, delta) in [*dx, *dy].into_iter().enumerate() {
+        if delta == 0.0 {
+            continue;
+        }
+        let other = 1 - axis;
+        let mut step = Vec2::ZERO;
+        step[axis] = delta;
+        let mut allowed = delta;
+        for (tile_min, tile_max) in
+            tilemap.solid_tiles(min.min(min + step), max.max(max + step), property)
+        {
+            if tile_max[other] <= min[other] || tile_min[other] >= max[other] {
+                continue;
+            }
+            if delta > 0.0 && tile_min[axis] >= max[axis] - 0.01 {
+                allowed = allowed.min((tile_min[axis] - max[axis]).max(0.0));
+            } else if delta < 0.0 && tile_max[axis] <= min[axis] + 0.01 {
+                allowed = allowed.max((tile_max[axis] - min[axis]).min(0.0));
+            }
+        }
+        blocked[axis] = allowed != delta;
+        min[axis] += allowed;
+        max[axis] += allowed;
+        offset[axis] += allowed;
+    }
+    state.sprite.center += offset;
+    Ok(Value::List(vec![
+        Value::Boolean(blocked[0]),
+        Value::Boolean(blocked[1]),
+    ]))
+}
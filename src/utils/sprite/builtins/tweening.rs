@@ -0,0 +1,81 @@
+use crate::utils::*;
+
+// Matches a tween by id (Number) or by property name (String)
+fn matches(tween: &PropertyTween, target: &Value) -> std::result::Result<bool, String> {
+    match target {
+        Value::Number(id) => Ok(tween.id == *id as usize),
+        Value::String(property) => Ok(tween.property == TweenProperty::parse(property)?),
+        _ => Err("A tween is picked by its id or by the property it changes".to_string()),
+    }
+}
+
+// Starts a tween, replacing any tween that is already changing the same property
+pub fn tween(state: &mut State, args: &[Value]) -> Result {
+    let [
+        Value::String(property),
+        Value::Number(target),
+        Value::Number(seconds),
+        rest @ ..,
+    ] = args
+    else {
+        return Err(
+            "tween() requires a property, a target value and a duration in seconds, optionally \
+             with an easing and a callback"
+                .to_string(),
+        );
+    };
+    let (easing, on_complete) = match rest {
+        [] => (Easing::Linear, None),
+        [easing] => (Easing::parse(easing)?, None),
+        [Value::Null, callback @ Value::Closure(_)] => (Easing::Linear, Some(callback.clone())),
+        [easing, callback @ Value::Closure(_)] => (Easing::parse(easing)?, Some(callback.clone())),
+        _ => return Err("tween() expects its callback to be a closure".to_string()),
+    };
+    let property = TweenProperty::parse(property)?;
+    let from = property.get(state.sprite, state.project);
+    let mut tween = PropertyTween::new(property, from, *target, seconds.max(0.0), easing);
+    tween.on_complete = on_complete;
+    let id = tween.id;
+
+    let tweens = &mut state.sprite.tweens;
+    tweens.retain(|t| t.property != tween.property);
+    tweens.push(tween);
+    Ok(Value::Number(id as f32))
+}
+
+// Cancelled tweens leave the property where it is and don't run their callback
+pub fn cancel_tween(state: &mut State, args: &[Value]) -> Result {
+    match args {
+        [] => state.sprite.tweens.clear(),
+        [target] => {
+            let mut error = None;
+            state.sprite.tweens.retain(|t| match matches(t, target) {
+                Ok(matched) => !matched,
+                Err(e) => {
+                    error = Some(e);
+                    true
+                }
+            });
+            if let Some(e) = error {
+                return Err(format!("cancel_tween(): {}", e));
+            }
+        }
+        _ => return Err("cancel_tween() takes an optional tween id or property".to_string()),
+    }
+    Ok(Value::Null)
+}
+
+pub fn tweening(state: &State, args: &[Value]) -> Result {
+    match args {
+        [] => Ok(Value::Boolean(!state.sprite.tweens.is_empty())),
+        [target] => {
+            for tween in &state.sprite.tweens {
+                if matches(tween, target).map_err(|e| format!("tweening(): {}", e))? {
+                    return Ok(Value::Boolean(true));
+                }
+            }
+            Ok(Value::Boolean(false))
+        }
+        _ => Err("tweening() takes an optional tween id or property".to_string()),
+    }
+}
@@ -1,5 +1,3 @@
-use glfw::WindowMode;
-
 use crate::utils::*;
 
 pub fn set_window_width(state: &mut State, args: &[Value]) -> Result {
@@ -35,27 +33,9 @@ pub fn set_window_size(state: &mut State, args: &[Value]) -> Result {
 
 pub fn set_window_state(state: &mut State, args: &[Value]) -> Result {
     if let [Value::String(mode)] = args {
-        let (xpos, ypos) = state.window.get_pos();
-        let (width, height) = state.window.get_size();
         match mode.as_str() {
-            "normal" => state.window.set_monitor(
-                WindowMode::Windowed,
-                xpos as i32,
-                ypos as i32,
-                width as u32,
-                height as u32,
-                None,
-            ),
-            "fullscreen" => state.glfw.with_primary_monitor(|_, m| {
-                state.window.set_monitor(
-                    m.map_or(WindowMode::Windowed, |m| WindowMode::FullScreen(m)),
-                    xpos as i32,
-                    ypos as i32,
-                    width as u32,
-                    height as u32,
-                    None,
-                )
-            }),
+            "normal" => state.window.set_fullscreen(false),
+            "fullscreen" => state.window.set_fullscreen(true),
             _ => return Err(format!("Invalid window state: '{}'", mode)),
         }
         Ok(Value::Null)
@@ -93,11 +73,7 @@ pub fn set_window_position(state: &mut State, args: &[Value]) -> Result {
 
 pub fn pointer_grab(state: &mut State, args: &[Value]) -> Result {
     if let [Value::Boolean(grab)] = args {
-        if *grab {
-            state.window.set_cursor_mode(glfw::CursorMode::Disabled);
-        } else {
-            state.window.set_cursor_mode(glfw::CursorMode::Normal);
-        }
+        state.window.set_cursor_grabbed(*grab);
         Ok(Value::Null)
     } else {
         Err("pointer_grab() requires a single boolean argument".to_string())
@@ -0,0 +1,392 @@
+use glam::*;
+use std::sync::Arc;
+
+use crate::utils::{Sprite, SpriteTransform, core::CPUTexture};
+
+// How far apart two colors can be (per channel) and still count as the same color in
+// `touching_color()`
+const COLOR_TOLERANCE: u8 = 8;
+// How many pixels a ray looks at in one sprite before giving up, for heavily tiled UVs
+const MAX_RAY_PIXELS: usize = 65536;
+
+// A copy of a costume's pixels, kept on the CPU for collision checks. Built once when the
+// costume is loaded and shared between a sprite and its clones.
+#[derive(Debug)]
+pub struct CostumeMask {
+    width: u32,
+    height: u32,
+    pixels: Vec<U8Vec4>,
+}
+
+impl From<&CPUTexture> for CostumeMask {
+    fn from(texture: &CPUTexture) -> Self {
+        Self {
+            width: texture.width,
+            height: texture.height,
+            pixels: texture.data.clone(),
+        }
+    }
+}
+
+impl CostumeMask {
+    // The pixel at `x, y`, wrapping around
+    fn pixel(&self, x: i32, y: i32) -> U8Vec4 {
+        if self.pixels.is_empty() {
+            return U8Vec4::ZERO;
+        }
+        let x = x.rem_euclid(self.width as i32) as u32;
+        let y = y.rem_euclid(self.height as i32) as u32;
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    // The pixel at `x, y` from the top left corner, if it is inside the costume
+    pub fn get(&self, x: u32, y: u32) -> Option<U8Vec4> {
+        (x < self.width && y < self.height).then(|| self.pixels[(y * self.width + x) as usize])
+    }
+
+    pub fn to_texture(&self) -> CPUTexture {
+        CPUTexture {
+            width: self.width,
+            height: self.height,
+            data: self.pixels.clone(),
+        }
+    }
+
+    pub fn sample(&self, uv: Vec2) -> U8Vec4 {
+        if self.pixels.is_empty() {
+            return U8Vec4::ZERO;
+        }
+        let x = ((uv.x.rem_euclid(1.0) * self.width as f32) as u32).min(self.width - 1);
+        let y = ((uv.y.rem_euclid(1.0) * self.height as f32) as u32).min(self.height - 1);
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    // The convex hull of the opaque pixels, with the costume spanning -0.5..0.5 and y pointing
+    // up. Used as the outline of `polygon` colliders, so it's kept to a few dozen points.
+    pub fn convex_hull(&self) -> Vec<Vec2> {
+        let mut points = vec![];
+        for y in 0..self.height {
+            let row = &self.pixels[(y * self.width) as usize..((y + 1) * self.width) as usize];
+            let (Some(left), Some(right)) = (
+                row.iter().position(|p| p.w > 0),
+                row.iter().rposition(|p| p.w > 0),
+            ) else {
+                continue;
+            };
+            for x in [left as f32, right as f32 + 1.0] {
+                for y in [y as f32, y as f32 + 1.0] {
+                    points.push(vec2(
+                        x / self.width as f32 - 0.5,
+                        0.5 - y / self.height as f32,
+                    ));
+                }
+            }
+        }
+        if points.len() < 3 {
+            return vec![
+                vec2(-0.5, -0.5),
+                vec2(0.5, -0.5),
+                vec2(0.5, 0.5),
+                vec2(-0.5, 0.5),
+            ];
+        }
+
+        // Monotone chain, counter-clockwise
+        points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+        points.dedup();
+        let cross = |o: Vec2, a: Vec2, b: Vec2| (a - o).perp_dot(b - o);
+        let mut hull: Vec<Vec2> = vec![];
+        for pass in 0..2 {
+            let start = hull.len();
+            let iter: Box<dyn Iterator<Item = &Vec2>> = if pass == 0 {
+                Box::new(points.iter())
+            } else {
+                Box::new(points.iter().rev())
+            };
+            for point in iter {
+                while hull.len() >= start + 2
+                    && cross(hull[hull.len() - 2], hull[hull.len() - 1], *point) <= 0.0
+                {
+                    hull.pop();
+                }
+                hull.push(*point);
+            }
+            hull.pop();
+        }
+
+        // Any subset of the points of a convex polygon is still convex
+        const MAX_POINTS: usize = 32;
+        if hull.len() > MAX_POINTS {
+            let step = hull.len() as f32 / MAX_POINTS as f32;
+            hull = (0..MAX_POINTS)
+                .map(|i| hull[(i as f32 * step) as usize])
+                .collect();
+        }
+        hull
+    }
+}
+
+// Where a sprite (or a clone) is on the screen and what it looks like there, with the same
+// transform as `draw_sprite`
+#[derive(Debug, Clone)]
+pub struct Hitbox {
+    pub name: String,
+    center: Vec2,
+    half_size: Vec2,
+    angle: f32,
+    uv: [Vec2; 2],
+    mask: Arc<CostumeMask>,
+}
+
+impl Hitbox {
+    pub fn new(sprite: &Sprite) -> Self {
+        let transform = SpriteTransform::new(sprite);
+        Self {
+            name: sprite.name.clone(),
+            center: transform.center,
+            half_size: (transform.size / 2.0).abs(),
+            angle: transform.angle,
+            uv: transform.uv(sprite.uv),
+            mask: sprite.costumes[sprite.costume()].mask.clone(),
+        }
+    }
+
+    fn axes(&self) -> [Vec2; 2] {
+        let rotation = Mat2::from_angle(self.angle);
+        [rotation * Vec2::X, rotation * Vec2::Y]
+    }
+
+    fn corners(&self) -> [Vec2; 4] {
+        let [x, y] = self.axes();
+        let (x, y) = (x * self.half_size.x, y * self.half_size.y);
+        [
+            self.center - x - y,
+            self.center + x - y,
+            self.center + x + y,
+            self.center - x + y,
+        ]
+    }
+
+    // The axis-aligned box around the sprite
+    pub fn bounds(&self) -> (Vec2, Vec2) {
+        let corners = self.corners();
+        let min = corners.iter().fold(Vec2::MAX, |a, b| a.min(*b));
+        let max = corners.iter().fold(Vec2::MIN, |a, b| a.max(*b));
+        (min, max)
+    }
+
+    pub fn center(&self) -> Vec2 {
+        self.center
+    }
+
+    // `point` relative to the sprite, which spans -0.5..0.5 on both axes
+    fn local(&self, point: Vec2) -> Vec2 {
+        Mat2::from_angle(-self.angle) * (point - self.center) / (self.half_size * 2.0)
+    }
+
+    // Texture coordinates of a local point
+    fn uv_at(&self, local: Vec2) -> Vec2 {
+        self.uv[0] + (local + 0.5) * (self.uv[1] - self.uv[0])
+    }
+
+    // The color of the sprite at `point`, if it's inside the sprite and not fully transparent
+    pub fn color_at(&self, point: Vec2) -> Option<U8Vec4> {
+        if self.half_size.x == 0.0 || self.half_size.y == 0.0 {
+            return None;
+        }
+        let local = self.local(point);
+        if local.x.abs() > 0.5 || local.y.abs() > 0.5 {
+            return None;
+        }
+        let color = self.mask.sample(self.uv_at(local));
+        (color.w > 0).then_some(color)
+    }
+
+    // Where a ray from `origin` along the normalized `direction` first hits the sprite, as the
+    // distance along the ray and the normal of the surface it hit. With `pixels`, transparent
+    // pixels are passed through and the normal is the side of the pixel that was hit.
+    pub fn raycast(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        max_distance: f32,
+        pixels: bool,
+    ) -> Option<(f32, Vec2)> {
+        if self.half_size.x == 0.0 || self.half_size.y == 0.0 {
+            return None;
+        }
+        let axes = self.axes();
+        // The normal of a side along `axis`, facing the ray
+        let facing = |axis: usize| -axes[axis] * direction.dot(axes[axis]).signum();
+
+        // Slab test against the rectangle, in local space
+        let start = self.local(origin);
+        let step = self.local(origin + direction) - start;
+        let (mut enter, mut exit, mut side) = (f32::MIN, f32::MAX, None);
+        for axis in 0..2 {
+            if step[axis] == 0.0 {
+                if start[axis].abs() > 0.5 {
+                    return None;
+                }
+                continue;
+            }
+            let a = (-0.5 - start[axis]) / step[axis];
+            let b = (0.5 - start[axis]) / step[axis];
+            if a.min(b) > enter {
+                enter = a.min(b);
+                side = Some(axis);
+            }
+            exit = exit.min(a.max(b));
+        }
+        let exit = exit.min(max_distance);
+        if enter > exit || exit < 0.0 {
+            return None;
+        }
+        // Starting inside, the ray hits right away, head on
+        let (enter, normal) = match side {
+            Some(axis) if enter >= 0.0 => (enter, facing(axis)),
+            _ => (0.0, -direction),
+        };
+        if !pixels {
+            return Some((enter, normal));
+        }
+
+        // Walk the pixels the ray crosses, one at a time
+        let size = vec2(self.mask.width as f32, self.mask.height as f32);
+        let texel = |t: f32| self.uv_at(start + step * t) * size;
+        // Nudged forward, so a ray entering on the far side of the texture starts in its
+        // last pixel and not in the first one after wrapping around
+        let first = enter + 1e-3;
+        let (from, delta) = (texel(first), texel(first + 1.0) - texel(first));
+        let mut cell = from.floor().as_ivec2();
+        let mut next = Vec2::ZERO;
+        let mut per_cell = Vec2::ZERO;
+        for axis in 0..2 {
+            if delta[axis] == 0.0 {
+                next[axis] = f32::MAX;
+                per_cell[axis] = f32::MAX;
+            } else {
+                let boundary = if delta[axis] > 0.0 {
+                    from[axis].floor() + 1.0
+                } else {
+                    from[axis].floor()
+                };
+                next[axis] = first + (boundary - from[axis]) / delta[axis];
+                per_cell[axis] = 1.0 / delta[axis].abs();
+            }
+        }
+        let (mut t, mut normal) = (enter, normal);
+        for _ in 0..MAX_RAY_PIXELS {
+            if t > exit {
+                break;
+            }
+            if self.mask.pixel(cell.x, cell.y).w > 0 {
+                return Some((t, normal));
+            }
+            let axis = if next.x < next.y { 0 } else { 1 };
+            t = next[axis];
+            next[axis] += per_cell[axis];
+            cell[axis] += delta[axis].signum() as i32;
+            normal = facing(axis);
+        }
+        None
+    }
+
+    // Separating axis test between the two rotated rectangles
+    pub fn overlaps(&self, other: &Hitbox) -> bool {
+        let axes = self.axes().into_iter().chain(other.axes());
+        corners_overlap(&self.corners(), &other.corners(), axes)
+    }
+
+    // Same as `overlaps`, against an axis-aligned rectangle
+    pub fn overlaps_rect(&self, min: Vec2, max: Vec2) -> bool {
+        let rect = [min, vec2(max.x, min.y), max, vec2(min.x, max.y)];
+        let axes = self.axes().into_iter().chain([Vec2::X, Vec2::Y]);
+        corners_overlap(&self.corners(), &rect, axes)
+    }
+
+    // How far `point` is from the sprite's rectangle, 0 if it's inside
+    pub fn distance_to(&self, point: Vec2) -> f32 {
+        let local = Mat2::from_angle(-self.angle) * (point - self.center);
+        (local.abs() - self.half_size).max(Vec2::ZERO).length()
+    }
+
+    // Both sprites have an opaque pixel at the same spot
+    pub fn touches(&self, other: &Hitbox) -> bool {
+        if !self.overlaps(other) {
+            return false;
+        }
+        let ((a_min, a_max), (b_min, b_max)) = (self.bounds(), other.bounds());
+        samples(a_min.max(b_min), a_max.min(b_max))
+            .any(|point| self.color_at(point).is_some() && other.color_at(point).is_some())
+    }
+
+    // Any opaque pixel is outside of the window, which spans `-half_window..half_window`
+    pub fn touches_edge(&self, half_window: Vec2) -> bool {
+        let (min, max) = self.bounds();
+        if min.x >= -half_window.x
+            && min.y >= -half_window.y
+            && max.x <= half_window.x
+            && max.y <= half_window.y
+        {
+            return false;
+        }
+        samples(min, max).any(|point| {
+            (point.x.abs() > half_window.x || point.y.abs() > half_window.y)
+                && self.color_at(point).is_some()
+        })
+    }
+
+    // Whether any opaque pixel of this sprite is drawn over `color`. `color_below` gives the
+    // color of everything drawn below the sprite at a point.
+    pub fn touches_color(
+        &self,
+        color: U8Vec4,
+        half_window: Vec2,
+        color_below: impl Fn(Vec2) -> U8Vec4,
+    ) -> bool {
+        let (min, max) = self.bounds();
+        samples(min.max(-half_window), max.min(half_window)).any(|point| {
+            self.color_at(point).is_some() && {
+                let below = color_below(point);
+                (0..3).all(|i| below[i].abs_diff(color[i]) <= COLOR_TOLERANCE)
+            }
+        })
+    }
+}
+
+fn corners_overlap(a: &[Vec2; 4], b: &[Vec2; 4], mut axes: impl Iterator<Item = Vec2>) -> bool {
+    axes.all(|axis| {
+        let project = |corners: &[Vec2; 4]| {
+            corners.iter().fold((f32::MAX, f32::MIN), |(min, max), c| {
+                (min.min(c.dot(axis)), max.max(c.dot(axis)))
+            })
+        };
+        let ((a_min, a_max), (b_min, b_max)) = (project(a), project(b));
+        a_min <= b_max && b_min <= a_max
+    })
+}
+
+// Points from `min` to `max`, two world units (one screen pixel) apart
+fn samples(min: Vec2, max: Vec2) -> impl Iterator<Item = Vec2> {
+    let rows = if min.x <= max.x && min.y <= max.y {
+        ((max.y - min.y) / 2.0).ceil() as usize + 1
+    } else {
+        0
+    };
+    let columns = ((max.x - min.x) / 2.0).ceil().max(0.0) as usize + 1;
+    (0..rows).flat_map(move |y| {
+        (0..columns).map(move |x| (min + vec2(x as f32, y as f32) * 2.0).min(max))
+    })
+}
+
+// The hitboxes of a sprite and its clones, in the order they are drawn (clones first). Hidden
+// sprites can't be touched.
+pub fn hitboxes(sprite: &Sprite, hitboxes: &mut Vec<Hitbox>) {
+    for clone in &sprite.clones {
+        self::hitboxes(clone, hitboxes);
+    }
+    if sprite.visible && !sprite.delete_pending {
+        hitboxes.push(Hitbox::new(sprite));
+    }
+}
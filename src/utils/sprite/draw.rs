@@ -1,6 +1,6 @@
 use glam::*;
 
-use crate::utils::{Sprite, core::*};
+use crate::utils::{Sprite, SpriteTransform, core::*};
 
 fn effects_to_ints(effects: Vec<String>) -> Vec<i32> {
     effects
@@ -29,70 +29,124 @@ fn effects_to_ints(effects: Vec<String>) -> Vec<i32> {
         .collect()
 }
 
-pub fn draw_sprite(sprite: &Sprite, shader: &ShaderProgram, projection: Mat4, font: &BitmapFont) {
-    if !sprite.visible {
-        return;
+// Effects past this many aren't sent with the vertices, so sprites with more are drawn alone
+const MAX_BATCH_EFFECTS: usize = 8;
+
+// Queues sprites up while they share an atlas page and the same effects (in the same order), and
+// draws them together with one draw call. Anything else drawn in between has to `flush` first.
+#[derive(Default)]
+pub struct SpriteBatch {
+    mesh: Option<Mesh<SpriteVertex>>,
+    vertices: Vec<SpriteVertex>,
+    indices: Vec<u32>,
+    costume: Option<AtlasRegion>,
+    effects: Vec<i32>,
+    // Only used by sprites with more effects than the vertices can carry
+    effect_values: Vec<f32>,
+}
+
+impl SpriteBatch {
+    // Queues a sprite after its clones, drawing its speech bubble right away
+    pub fn draw_sprite(
+        &mut self,
+        sprite: &Sprite,
+        shader: &ShaderProgram,
+        projection: Mat4,
+        font: &BitmapFont,
+    ) {
+        if !sprite.visible {
+            return;
+        }
+
+        for clone in &sprite.clones {
+            self.draw_sprite(clone, shader, projection, font);
+        }
+
+        self.push(sprite, shader, projection);
+
+        if sprite.dialogue.is_some() {
+            self.flush(shader, projection);
+            draw_dialogue(sprite, shader, projection, font);
+        }
     }
 
-    for clone in &sprite.clones {
-        draw_sprite(clone, shader, projection, font);
+    fn push(&mut self, sprite: &Sprite, shader: &ShaderProgram, projection: Mat4) {
+        let costume = &sprite.costumes[sprite.costume()].texture;
+        let effects = effects_to_ints(sprite.effects.keys().map(String::clone).collect());
+        let joins = self.costume.as_ref().is_some_and(|c| c.same_page(costume))
+            && effects == self.effects
+            && effects.len() <= MAX_BATCH_EFFECTS;
+        if !joins {
+            self.flush(shader, projection);
+            self.costume = Some(costume.clone());
+            self.effects = effects;
+            self.effect_values = sprite.effects.values().copied().collect();
+        }
+
+        let transform = SpriteTransform::new(sprite);
+        let uv = transform.uv(sprite.uv);
+        let transform = transform.affine();
+        let region = costume.rect();
+        let mut effect_values = [Vec4::ZERO; 2];
+        for (i, value) in sprite.effects.values().take(MAX_BATCH_EFFECTS).enumerate() {
+            effect_values[i / 4][i % 4] = *value;
+        }
+
+        let base = self.vertices.len() as u32;
+        for (corner, uv) in [
+            (vec2(-0.5, -0.5), uv[0]),
+            (vec2(0.5, -0.5), vec2(uv[1].x, uv[0].y)),
+            (vec2(0.5, 0.5), uv[1]),
+            (vec2(-0.5, 0.5), vec2(uv[0].x, uv[1].y)),
+        ] {
+            self.vertices.push(SpriteVertex {
+                position: transform.transform_point2(corner),
+                uv,
+                region,
+                effects: effect_values,
+            });
+        }
+        self.indices.extend([0, 1, 2, 0, 2, 3].map(|i| base + i));
     }
 
-    let tex = &sprite.costumes[sprite.costume()];
-    let direction = sprite.direction.to_radians();
-
-    let vertices = [
-        Vertex {
-            position: Vec2::new(-0.5, -0.5),
-            uv: sprite.uv[0],
-        },
-        Vertex {
-            position: Vec2::new(0.5, -0.5),
-            uv: Vec2::new(sprite.uv[1].x, sprite.uv[0].y),
-        },
-        Vertex {
-            position: Vec2::new(0.5, 0.5),
-            uv: sprite.uv[1],
-        },
-        Vertex {
-            position: Vec2::new(-0.5, 0.5),
-            uv: Vec2::new(sprite.uv[0].x, sprite.uv[1].y),
-        },
-    ];
-    let indices = [0, 1, 2, 0, 2, 3];
-
-    let quad = Mesh::new(&vertices, &indices, crate::utils::core::DrawMode::Triangles);
-
-    shader.use_program();
-    shader.set_uniform("u_color", Vec4::splat(1.0));
-    shader.set_uniform("u_projection", projection);
-    shader.set_uniform(
-        "u_model",
-        Mat4::from_translation(sprite.center.extend(0.0))
-            * Mat4::from_rotation_z(direction)
-            * Mat4::from_scale(Vec3::new(
-                sprite.size.x * sprite.scale,
-                sprite.size.y * sprite.scale,
-                1.0,
-            )),
-    );
-    shader.set_uniform(
-        "u_effects",
-        effects_to_ints(sprite.effects.keys().map(String::clone).collect()).as_slice(),
-    );
-    shader.set_uniform(
-        "u_effect_values",
-        sprite
-            .effects
-            .values()
-            .map(|v| *v)
-            .collect::<Vec<f32>>()
-            .as_slice(),
-    );
-    shader.set_uniform("u_effects_count", sprite.effects.len() as i32);
-    tex.bind();
-    quad.draw();
+    // Draws everything queued so far
+    pub fn flush(&mut self, shader: &ShaderProgram, projection: Mat4) {
+        let Some(costume) = self.costume.take() else {
+            return;
+        };
+        if self.vertices.is_empty() {
+            return;
+        }
+        let mesh = self
+            .mesh
+            .get_or_insert_with(|| Mesh::new(&[], &[], DrawMode::Triangles));
+        mesh.update(&self.vertices, &self.indices);
+
+        shader.use_program();
+        shader.set_uniform("u_color", Vec4::splat(1.0));
+        shader.set_uniform("u_projection", projection);
+        shader.set_uniform("u_model", Mat4::IDENTITY);
+        shader.set_uniform("u_effects", self.effects.as_slice());
+        shader.set_uniform("u_effect_values", self.effect_values.as_slice());
+        shader.set_uniform("u_effects_count", self.effects.len() as i32);
+        shader.set_uniform("u_batched", 1);
+        costume.bind();
+        mesh.draw();
+        shader.set_uniform("u_batched", 0);
+
+        self.vertices.clear();
+        self.indices.clear();
+    }
+}
+
+// Draws a sprite and its clones right away, like when stamping
+pub fn draw_sprite(sprite: &Sprite, shader: &ShaderProgram, projection: Mat4, font: &BitmapFont) {
+    let mut batch = SpriteBatch::default();
+    batch.draw_sprite(sprite, shader, projection, font);
+    batch.flush(shader, projection);
+}
 
+fn draw_dialogue(sprite: &Sprite, shader: &ShaderProgram, projection: Mat4, font: &BitmapFont) {
     if let Some(dialogue) = &sprite.dialogue {
         let italicised = dialogue.think;
         let color = Vec4::new(1.0, 1.0, 1.0, if dialogue.think { 0.75 } else { 1.0 });
@@ -20,6 +20,8 @@ impl Function {
             ));
         }
 
+        state.project.watchdog.enter_call()?;
+
         let mut new_local_vars = state.local_vars.to_vec();
         for (i, arg) in self.args.iter().enumerate() {
             new_local_vars.push((arg.clone(), args[i].clone()));
@@ -35,7 +37,6 @@ impl Function {
             snapshots: state.snapshots,
             window: state.window,
             input_manager: state.input_manager,
-            glfw: state.glfw,
             audio_manager: state.audio_manager,
             shader_program: state.shader_program,
             projection: state.projection,
@@ -48,10 +49,9 @@ impl Function {
             Sprite::execute_statement(statement, &mut new_state);
         }
 
-        Ok(crate::utils::resolve_expression(
-            &self.returns,
-            &mut new_state,
-        ))
+        let result = crate::utils::resolve_expression(&self.returns, &mut new_state);
+        new_state.project.watchdog.exit_call();
+        Ok(result)
     }
 }
 
@@ -0,0 +1,537 @@
+use glam::*;
+use std::collections::HashSet;
+
+use crate::utils::{
+    Hitbox, InputManager, RotationStyle, Sprite, Value, WindowBackend, core::*, draw_rectangle,
+};
+
+use super::overlay::{count_clones, draw_outline};
+
+const PANEL_WIDTH: f32 = 360.0;
+const FONT_SIZE: f32 = 16.0;
+const LINE_HEIGHT: f32 = 20.0;
+
+// Points at a sprite or one of its clones: the sprite's name, then the index of the clone at
+// every level of the clone tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpritePath {
+    pub name: String,
+    pub clones: Vec<usize>,
+}
+
+// A value typed into the inspector, to be evaluated in the sprite and stored in `field`
+pub struct InspectorEdit {
+    pub path: SpritePath,
+    pub field: String,
+    pub expression: String,
+}
+
+enum RowAction {
+    None,
+    Edit(String),
+    Toggle(Vec<usize>),
+    Select(Vec<usize>),
+}
+
+struct Row {
+    indent: usize,
+    text: String,
+    color: Vec4,
+    action: RowAction,
+}
+
+// Toggled with F4. Clicking a sprite selects it and shows its state in a panel on the right,
+// where clicking a value edits it.
+pub struct Inspector {
+    pub active: bool,
+    selected: Option<SpritePath>,
+    expanded: HashSet<Vec<usize>>,
+    editing: Option<(String, String)>,
+    scroll: usize,
+    rows: Vec<Row>,
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            selected: None,
+            expanded: HashSet::new(),
+            editing: None,
+            scroll: 0,
+            rows: vec![],
+        }
+    }
+
+    // Handles clicks and typing, and returns an edit once Enter is pressed. While the inspector
+    // is active, the game doesn't see the mouse buttons (and the keyboard while editing).
+    pub fn update(
+        &mut self,
+        sprites: &[Sprite],
+        input_manager: &mut InputManager,
+        window: &dyn WindowBackend,
+    ) -> Option<InspectorEdit> {
+        use glfw::{Key, MouseButton};
+
+        if input_manager.is_key_pressed(Key::F4) {
+            self.active = !self.active;
+            self.editing = None;
+        }
+        if !self.active {
+            return None;
+        }
+        if let Some(path) = &self.selected
+            && find_sprite(sprites, path).is_none()
+        {
+            self.selected = None;
+            self.editing = None;
+        }
+        self.rows = match &self.selected {
+            Some(path) => self.build_rows(sprites, path),
+            None => vec![],
+        };
+
+        let mut edit = None;
+        if let Some((field, input)) = &mut self.editing {
+            input.push_str(input_manager.typed());
+            if input_manager.is_key_pressed(Key::Backspace) {
+                input.pop();
+            }
+            if input_manager.is_key_pressed(Key::Enter) {
+                edit = Some(InspectorEdit {
+                    path: self.selected.clone()?,
+                    field: field.clone(),
+                    expression: input.trim().to_string(),
+                });
+                self.editing = None;
+            } else if input_manager.is_key_pressed(Key::Escape) {
+                self.editing = None;
+            }
+            input_manager.clear_keys();
+        } else {
+            if input_manager.is_key_pressed(Key::PageUp) {
+                self.scroll = self.scroll.saturating_sub(5);
+            }
+            if input_manager.is_key_pressed(Key::PageDown) {
+                self.scroll = (self.scroll + 5).min(self.rows.len().saturating_sub(1));
+            }
+        }
+
+        if input_manager.is_mouse_button_pressed(MouseButton::Button1) {
+            let (width, height) = window.get_size();
+            let (x, y) = window.get_cursor_pos();
+            let (x, y) = (x as f32, y as f32);
+            if self.selected.is_some() && x >= width as f32 - PANEL_WIDTH {
+                let row = ((y - 8.0) / LINE_HEIGHT).floor();
+                if row >= 0.0 {
+                    self.click_row(row as usize + self.scroll);
+                }
+            } else {
+                let world =
+                    (vec2(x, y) * 2.0 - vec2(width as f32, height as f32)) * vec2(1.0, -1.0);
+                self.selected = pick_sprite(sprites, world);
+                self.editing = None;
+                self.scroll = 0;
+            }
+        }
+        input_manager.clear_mouse_buttons();
+        edit
+    }
+
+    fn click_row(&mut self, index: usize) {
+        let Some(row) = self.rows.get(index) else {
+            return;
+        };
+        match &row.action {
+            RowAction::None => {}
+            RowAction::Edit(field) => {
+                // Start from the current value, without the `name = ` in front
+                let current = row.text.split_once(" = ").map_or("", |(_, v)| v);
+                self.editing = Some((field.clone(), current.to_string()));
+            }
+            RowAction::Toggle(clones) => {
+                if !self.expanded.remove(clones) {
+                    self.expanded.insert(clones.clone());
+                }
+            }
+            RowAction::Select(clones) => {
+                if let Some(selected) = &mut self.selected {
+                    selected.clones = clones.clone();
+                    self.editing = None;
+                }
+            }
+        }
+    }
+
+    fn build_rows(&self, sprites: &[Sprite], path: &SpritePath) -> Vec<Row> {
+        let mut rows = vec![];
+        let Some(sprite) = find_sprite(sprites, path) else {
+            return rows;
+        };
+        let white = vec4(1.0, 1.0, 1.0, 1.0);
+        let gray = vec4(0.7, 0.7, 0.7, 1.0);
+        let heading = vec4(1.0, 1.0, 0.0, 1.0);
+        let mut push = |indent: usize, text: String, color: Vec4, action: RowAction| {
+            rows.push(Row {
+                indent,
+                text,
+                color,
+                action,
+            })
+        };
+        let field = |name: &str, value: String| (format!("{} = {}", name, value), name.to_string());
+
+        let title = match sprite.clone_id {
+            Some(id) => format!("{} (clone {})", sprite.name, id),
+            None => sprite.name.clone(),
+        };
+        push(0, title, heading, RowAction::None);
+        let rotation_style = match sprite.rotation_style {
+            RotationStyle::AllAround => "all-around",
+            RotationStyle::LeftRight => "left-right",
+            RotationStyle::DontRotate => "dont-rotate",
+        };
+        for (text, name) in [
+            field("x", sprite.center.x.to_string()),
+            field("y", sprite.center.y.to_string()),
+            field("direction", sprite.direction.to_string()),
+            field("scale", sprite.scale.to_string()),
+            field("size", format!("[{}, {}]", sprite.size.x, sprite.size.y)),
+            field("layer", sprite.layer.to_string()),
+            field("costume", sprite.current_costume.to_string()),
+            field("visible", sprite.visible.to_string()),
+            field("rotation_style", format!("{:?}", rotation_style)),
+            field("edge_bounce", sprite.edge_bounce.to_string()),
+        ] {
+            push(1, text, white, RowAction::Edit(name));
+        }
+        push(
+            1,
+            format!("costumes: {}", sprite.costumes.len()),
+            gray,
+            RowAction::None,
+        );
+        if !sprite.tags.is_empty() {
+            push(
+                1,
+                format!("tags: {}", sprite.tags.join(", ")),
+                gray,
+                RowAction::None,
+            );
+        }
+
+        if !sprite.effects.is_empty() {
+            push(0, "Effects".to_string(), heading, RowAction::None);
+            for (name, value) in &sprite.effects {
+                push(
+                    1,
+                    format!("{} = {}", name, value),
+                    white,
+                    RowAction::Edit(format!("effect:{}", name)),
+                );
+            }
+        }
+
+        push(0, "Variables".to_string(), heading, RowAction::None);
+        let mut variables = sprite.variables.iter().collect::<Vec<_>>();
+        variables.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, value) in variables {
+            push(
+                1,
+                format!("{} = {}", name, display_value(value)),
+                white,
+                RowAction::Edit(format!("var:{}", name)),
+            );
+        }
+
+        // What the sprite is waiting on, so a stuck sprite is easy to spot
+        push(0, "Scripts".to_string(), heading, RowAction::None);
+        push(
+            1,
+            format!(
+                "setup: {}",
+                if sprite.setup_finished {
+                    "finished"
+                } else {
+                    "running"
+                }
+            ),
+            gray,
+            RowAction::None,
+        );
+        if sprite.time_waiting > 0 {
+            push(
+                1,
+                format!("waiting: {} frames", sprite.time_waiting),
+                gray,
+                RowAction::None,
+            );
+        }
+        if let Some(glide) = &sprite.glide {
+            push(
+                1,
+                format!(
+                    "gliding to ({}, {}): {} frames left",
+                    glide.end_x, glide.end_y, glide.remaining
+                ),
+                gray,
+                RowAction::None,
+            );
+        }
+        if let Some(dialogue) = &sprite.dialogue {
+            let verb = if dialogue.think { "thinking" } else { "saying" };
+            push(
+                1,
+                format!("{}: {:?}", verb, dialogue.text),
+                gray,
+                RowAction::None,
+            );
+        }
+        for (condition, _, triggered) in &sprite.boolean_recievers {
+            push(
+                1,
+                format!(
+                    "when {}: {}",
+                    condition,
+                    if *triggered { "triggered" } else { "waiting" }
+                ),
+                gray,
+                RowAction::None,
+            );
+        }
+
+        // The clone tree always starts at the original sprite, so the selection can move around
+        let Some(root) = find_sprite(
+            sprites,
+            &SpritePath {
+                name: path.name.clone(),
+                clones: vec![],
+            },
+        ) else {
+            return rows;
+        };
+        push(
+            0,
+            format!("Clones ({})", count_clones(root)),
+            heading,
+            RowAction::None,
+        );
+        self.clone_rows(root, vec![], &path.clones, 1, &mut push);
+        rows
+    }
+
+    fn clone_rows(
+        &self,
+        sprite: &Sprite,
+        clones: Vec<usize>,
+        selected: &[usize],
+        indent: usize,
+        push: &mut impl FnMut(usize, String, Vec4, RowAction),
+    ) {
+        let expanded = self.expanded.contains(&clones);
+        let marker = match (sprite.clones.is_empty(), expanded) {
+            (true, _) => " ",
+            (false, true) => "-",
+            (false, false) => "+",
+        };
+        let label = match sprite.clone_id {
+            Some(id) => format!("clone {} ({}, {})", id, sprite.center.x, sprite.center.y),
+            None => sprite.name.clone(),
+        };
+        let color = if clones == selected {
+            vec4(0.4, 1.0, 0.4, 1.0)
+        } else {
+            vec4(1.0, 1.0, 1.0, 1.0)
+        };
+        if !sprite.clones.is_empty() {
+            push(
+                indent,
+                format!("[{}]", marker),
+                color,
+                RowAction::Toggle(clones.clone()),
+            );
+        }
+        push(
+            if sprite.clones.is_empty() {
+                indent
+            } else {
+                indent + 1
+            },
+            label,
+            color,
+            RowAction::Select(clones.clone()),
+        );
+        if expanded {
+            for (i, clone) in sprite.clones.iter().enumerate() {
+                let mut path = clones.clone();
+                path.push(i);
+                self.clone_rows(clone, path, selected, indent + 2, push);
+            }
+        }
+    }
+
+    pub fn draw(
+        &self,
+        sprites: &[Sprite],
+        shader: &ShaderProgram,
+        projections: (Mat4, Mat4),
+        font: &BitmapFont,
+        window_size: (i32, i32),
+    ) {
+        if !self.active {
+            return;
+        }
+        let (projection, top_left_projection) = projections;
+        shader.use_program();
+
+        let selected = self
+            .selected
+            .as_ref()
+            .and_then(|path| find_sprite(sprites, path));
+        if let Some(sprite) = selected {
+            shader.set_uniform("u_projection", projection);
+            draw_outline(sprite, 4.0, shader, vec4(1.0, 1.0, 0.0, 1.0));
+        }
+
+        let width = window_size.0 as f32;
+        let height = window_size.1 as f32;
+        shader.set_uniform("u_projection", top_left_projection);
+        if selected.is_none() {
+            let text = "Inspector: click a sprite";
+            draw_text(TextParams {
+                text,
+                projection: top_left_projection,
+                pos: vec2(width - font.size(text, FONT_SIZE).0 - 8.0, 8.0),
+                font_size: FONT_SIZE,
+                down_positive: true,
+                color: vec4(1.0, 1.0, 0.0, 1.0),
+                ..TextParams::default_params(font, shader)
+            });
+            return;
+        }
+
+        // Flipped projection, so the corners are swapped like in the console
+        draw_rectangle(
+            vec2(width - PANEL_WIDTH, height),
+            vec2(width, 0.0),
+            shader,
+            vec4(0.0, 0.0, 0.0, 0.8),
+        );
+        for (i, row) in self.rows.iter().skip(self.scroll).enumerate() {
+            let y = 8.0 + i as f32 * LINE_HEIGHT;
+            if y > height {
+                break;
+            }
+            let text = match (&row.action, &self.editing) {
+                (RowAction::Edit(field), Some((editing, input))) if field == editing => {
+                    let name = row.text.split_once(" = ").map_or("", |(name, _)| name);
+                    format!("{} = {}_", name, input)
+                }
+                _ => row.text.clone(),
+            };
+            draw_text(TextParams {
+                text: &text,
+                projection: top_left_projection,
+                pos: vec2(width - PANEL_WIDTH + 8.0 + row.indent as f32 * 12.0, y),
+                font_size: FONT_SIZE,
+                down_positive: true,
+                color: row.color,
+                ..TextParams::default_params(font, shader)
+            });
+        }
+    }
+}
+
+pub fn find_sprite<'a>(sprites: &'a [Sprite], path: &SpritePath) -> Option<&'a Sprite> {
+    let mut sprite = sprites.iter().find(|s| s.name == path.name)?;
+    for index in &path.clones {
+        sprite = sprite.clones.get(*index)?;
+    }
+    Some(sprite)
+}
+
+pub fn find_sprite_mut<'a>(sprites: &'a mut [Sprite], path: &SpritePath) -> Option<&'a mut Sprite> {
+    let mut sprite = sprites.iter_mut().find(|s| s.name == path.name)?;
+    for index in &path.clones {
+        sprite = sprite.clones.get_mut(*index)?;
+    }
+    Some(sprite)
+}
+
+// The sprite drawn on top at `point`, ignoring its transparent pixels. `Hitbox` places the sprite
+// with its `SpriteTransform`, like it's drawn and outlined, so rotation styles pick what's on the
+// screen. Sprites are drawn in order (sorted by layer) with their clones before them, so the last
+// hit wins.
+fn pick_sprite(sprites: &[Sprite], point: Vec2) -> Option<SpritePath> {
+    fn pick(sprite: &Sprite, clones: &mut Vec<usize>, point: Vec2, hit: &mut Option<Vec<usize>>) {
+        for (i, clone) in sprite.clones.iter().enumerate() {
+            clones.push(i);
+            pick(clone, clones, point, hit);
+            clones.pop();
+        }
+        if sprite.visible && Hitbox::new(sprite).color_at(point).is_some() {
+            *hit = Some(clones.clone());
+        }
+    }
+
+    let mut result = None;
+    for sprite in sprites {
+        let mut hit = None;
+        pick(sprite, &mut vec![], point, &mut hit);
+        if let Some(clones) = hit {
+            result = Some(SpritePath {
+                name: sprite.name.clone(),
+                clones,
+            });
+        }
+    }
+    result
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        value => value.to_string(),
+    }
+}
+
+// Stores an evaluated inspector edit in the sprite
+pub fn apply_edit(sprite: &mut Sprite, field: &str, value: Value) -> Result<(), String> {
+    if let Some(name) = field.strip_prefix("var:") {
+        sprite.variables.insert(name.to_string(), value);
+        return Ok(());
+    }
+    if let Some(name) = field.strip_prefix("effect:") {
+        sprite.effects.insert(name.to_string(), value.to_number());
+        return Ok(());
+    }
+    match (field, value) {
+        ("x", Value::Number(n)) => sprite.center.x = n,
+        ("y", Value::Number(n)) => sprite.center.y = n,
+        ("direction", Value::Number(n)) => sprite.direction = n,
+        ("scale", Value::Number(n)) => sprite.scale = n,
+        ("layer", Value::Number(n)) => sprite.layer = n as isize,
+        ("costume", Value::Number(n)) => {
+            if n < 0.0 || n as usize >= sprite.costumes.len() {
+                return Err(format!("Costume {} is out of bounds", n));
+            }
+            sprite.current_costume = n as usize;
+        }
+        ("size", Value::List(size)) => match size.as_slice() {
+            [Value::Number(w), Value::Number(h)] => sprite.size = vec2(*w, *h),
+            _ => return Err("size must be a list of two numbers".to_string()),
+        },
+        ("visible", Value::Boolean(b)) => sprite.visible = b,
+        ("edge_bounce", Value::Boolean(b)) => sprite.edge_bounce = b,
+        ("rotation_style", Value::String(style)) => {
+            sprite.rotation_style = match style.to_lowercase().as_str() {
+                "all-around" => RotationStyle::AllAround,
+                "left-right" => RotationStyle::LeftRight,
+                "dont-rotate" => RotationStyle::DontRotate,
+                _ => return Err(format!("Invalid rotation style: '{}'", style)),
+            }
+        }
+        (field, value) => return Err(format!("Can't set {} to {}", field, display_value(&value))),
+    }
+    Ok(())
+}
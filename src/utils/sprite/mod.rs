@@ -6,5 +6,32 @@ pub use function::*;
 pub mod sprite;
 pub use sprite::*;
 
+pub mod transform;
+pub use transform::*;
+
 pub mod draw;
 pub use draw::*;
+
+pub mod collision;
+pub use collision::*;
+
+pub mod spatial;
+pub use spatial::*;
+
+pub mod rigid_body;
+pub use rigid_body::*;
+
+pub mod tween;
+pub use tween::*;
+
+pub mod route;
+pub use route::*;
+
+pub mod pathfinding;
+pub use pathfinding::{Diagonals, Grid};
+
+pub mod overlay;
+pub use overlay::*;
+
+pub mod inspector;
+pub use inspector::*;
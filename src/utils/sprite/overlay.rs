@@ -0,0 +1,179 @@
+use glam::*;
+
+use crate::utils::{Project, Sprite, SpriteSnapshot, SpriteTransform, Value, core::*, draw_line};
+
+// Draws the `show_bounds`, `show_names`, `show_layers`, `show_counts` and `watch` debug options
+pub struct DebugOverlay {
+    pub visible: bool,
+    pub bounds: bool,
+    pub names: bool,
+    pub layers: bool,
+    pub counts: bool,
+    pub watch: Vec<String>,
+}
+
+impl DebugOverlay {
+    pub fn new(debug_options: &[String], watch: Vec<String>) -> Self {
+        let enabled = |option: &str| debug_options.iter().any(|o| o == option);
+        Self {
+            visible: true,
+            bounds: enabled("show_bounds"),
+            names: enabled("show_names"),
+            layers: enabled("show_layers"),
+            counts: enabled("show_counts"),
+            watch,
+        }
+    }
+
+    // Outlines and labels, drawn in world coordinates on top of the sprites
+    pub fn draw_sprites(
+        &self,
+        sprites: &[Sprite],
+        shader: &ShaderProgram,
+        projection: Mat4,
+        font: &BitmapFont,
+    ) {
+        if !self.visible || !(self.bounds || self.names || self.layers) {
+            return;
+        }
+        shader.use_program();
+        shader.set_uniform("u_projection", projection);
+        for sprite in sprites {
+            self.draw_sprite(sprite, shader, projection, font);
+        }
+    }
+
+    fn draw_sprite(
+        &self,
+        sprite: &Sprite,
+        shader: &ShaderProgram,
+        projection: Mat4,
+        font: &BitmapFont,
+    ) {
+        for clone in &sprite.clones {
+            self.draw_sprite(clone, shader, projection, font);
+        }
+        if !sprite.visible {
+            return;
+        }
+
+        let half_size = sprite.size * sprite.scale / 2.0;
+        if self.bounds {
+            let color = if sprite.clone_id.is_some() {
+                vec4(1.0, 0.6, 0.0, 1.0)
+            } else {
+                vec4(0.0, 1.0, 0.0, 1.0)
+            };
+            draw_outline(sprite, 2.0, shader, color);
+            let cross = 6.0;
+            draw_line(
+                sprite.center - vec2(cross, 0.0),
+                sprite.center + vec2(cross, 0.0),
+                2.0,
+                shader,
+                vec4(1.0, 0.0, 0.0, 1.0),
+            );
+            draw_line(
+                sprite.center - vec2(0.0, cross),
+                sprite.center + vec2(0.0, cross),
+                2.0,
+                shader,
+                vec4(1.0, 0.0, 0.0, 1.0),
+            );
+        }
+
+        let mut label = vec![];
+        if self.names {
+            match sprite.clone_id {
+                Some(id) => label.push(format!("{} (clone {})", sprite.name, id)),
+                None => label.push(sprite.name.clone()),
+            }
+        }
+        if self.layers {
+            label.push(format!("layer {}", sprite.layer));
+        }
+        if !label.is_empty() {
+            let text = label.join(" | ");
+            let font_size = 20.0;
+            let width = font.size(&text, font_size).0;
+            draw_text(TextParams {
+                text: &text,
+                projection,
+                pos: vec2(
+                    sprite.center.x - width / 2.0,
+                    sprite.center.y - half_size.length() - font_size,
+                ),
+                font_size,
+                color: vec4(1.0, 1.0, 0.0, 1.0),
+                ..TextParams::default_params(font, shader)
+            });
+        }
+    }
+
+    // Lines for the text overlay in the top left corner
+    pub fn text_lines(&self, project: &Project) -> Vec<String> {
+        let mut lines = vec![];
+        if self.counts {
+            let clones = project.sprites.iter().map(count_clones).sum::<usize>();
+            let pending = project
+                .sprites
+                .iter()
+                .map(|s| s.pending_broadcasts(project))
+                .sum::<usize>();
+            lines.push(format!(
+                "Sprites: {}  Clones: {}  Broadcasts: {} ({} pending)  Assets: {}",
+                project.sprites.len(),
+                clones,
+                project.broadcast_history.len(),
+                pending,
+                project.assets.count()
+            ));
+        }
+        for watch in &self.watch {
+            lines.push(format!("{} = {}", watch, watch_value(watch, project)));
+        }
+        lines
+    }
+}
+
+pub fn draw_outline(sprite: &Sprite, thickness: f32, shader: &ShaderProgram, color: Vec4) {
+    let corners = SpriteTransform::new(sprite).corners();
+    for i in 0..4 {
+        draw_line(corners[i], corners[(i + 1) % 4], thickness, shader, color);
+    }
+}
+
+pub(super) fn count_clones(sprite: &Sprite) -> usize {
+    sprite.clones.len() + sprite.clones.iter().map(count_clones).sum::<usize>()
+}
+
+// `name` is a global variable, `sprite.name` is a variable (or `x`, `y`, `direction`, ...) of a
+// sprite
+fn watch_value(watch: &str, project: &Project) -> String {
+    let value = match watch.split_once('.') {
+        Some((sprite_name, name)) => project
+            .sprites
+            .iter()
+            .find(|s| s.name == sprite_name)
+            .and_then(|sprite| {
+                sprite
+                    .variables
+                    .get(name)
+                    .cloned()
+                    .or_else(|| SpriteSnapshot::from(sprite).get(name))
+                    .or_else(|| match name {
+                        "layer" => Some(Value::Number(sprite.layer as f32)),
+                        "costume" => Some(Value::Number(sprite.costume() as f32)),
+                        "visible" => Some(Value::Boolean(sprite.visible)),
+                        "clones" => Some(Value::Number(count_clones(sprite) as f32)),
+                        _ => None,
+                    })
+            }),
+        None => project.global_variables.get(watch).cloned(),
+    };
+    match value {
+        Some(Value::String(s)) => format!("{:?}", s),
+        Some(value) => value.to_string(),
+        None => "<undefined>".to_string(),
+    }
+}
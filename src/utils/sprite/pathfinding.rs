@@ -0,0 +1,200 @@
+use glam::*;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+// When a path may move diagonally. Diagonal steps cost √2 times the cell they step onto.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Diagonals {
+    Never,
+    Always,
+    // At least one of the two cells next to the corner has to be free
+    IfOneFree,
+    // Both cells next to the corner have to be free, so paths never cut corners
+    IfBothFree,
+}
+
+impl Diagonals {
+    pub fn parse(diagonals: &str) -> Result<Self, String> {
+        match diagonals {
+            "never" => Ok(Self::Never),
+            "always" => Ok(Self::Always),
+            "if_one_free" => Ok(Self::IfOneFree),
+            "if_both_free" => Ok(Self::IfBothFree),
+            _ => Err(format!(
+                "Unknown diagonals '{}', expected 'never', 'always', 'if_one_free' or 'if_both_free'",
+                diagonals
+            )),
+        }
+    }
+}
+
+// The cost of stepping onto each cell, row by row. Cells that cost 0 or less are walls.
+pub struct Grid {
+    pub width: usize,
+    pub height: usize,
+    pub costs: Vec<f32>,
+}
+
+impl Grid {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            costs: vec![1.0; width * height],
+        }
+    }
+
+    fn index(&self, cell: IVec2) -> Option<usize> {
+        let in_bounds = cell.x >= 0
+            && cell.y >= 0
+            && (cell.x as usize) < self.width
+            && (cell.y as usize) < self.height;
+        in_bounds.then(|| cell.y as usize * self.width + cell.x as usize)
+    }
+
+    pub fn set(&mut self, cell: IVec2, cost: f32) {
+        if let Some(index) = self.index(cell) {
+            self.costs[index] = cost;
+        }
+    }
+
+    fn free(&self, cell: IVec2) -> bool {
+        self.index(cell).is_some_and(|i| self.costs[i] > 0.0)
+    }
+}
+
+#[derive(PartialEq)]
+struct Open {
+    estimate: f32,
+    index: usize,
+}
+
+impl Eq for Open {}
+
+// Reversed, so the heap pops the lowest estimate first
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// A* from `start` to `goal`, both included in the path. The start can be a wall (something
+// standing in a wall can still walk out of it), the goal can't. Gives up after looking at
+// `max_nodes` cells.
+pub fn find_path(
+    grid: &Grid,
+    start: IVec2,
+    goal: IVec2,
+    diagonals: Diagonals,
+    max_nodes: usize,
+) -> Option<Vec<IVec2>> {
+    let start_index = grid.index(start)?;
+    let goal_index = grid.index(goal).filter(|_| grid.free(goal))?;
+
+    // The cheapest cell keeps the estimate from ever being too high
+    let cheapest = grid
+        .costs
+        .iter()
+        .copied()
+        .filter(|c| *c > 0.0)
+        .fold(f32::MAX, f32::min);
+    let estimate = |cell: IVec2| {
+        let d = (goal - cell).abs().as_vec2();
+        let steps = if diagonals == Diagonals::Never {
+            d.x + d.y
+        } else {
+            d.max_element() + (std::f32::consts::SQRT_2 - 1.0) * d.min_element()
+        };
+        steps * cheapest
+    };
+
+    let mut cost = vec![f32::INFINITY; grid.costs.len()];
+    let mut came_from = vec![usize::MAX; grid.costs.len()];
+    let mut closed = vec![false; grid.costs.len()];
+    let mut open = BinaryHeap::new();
+    cost[start_index] = 0.0;
+    open.push(Open {
+        estimate: estimate(start),
+        index: start_index,
+    });
+
+    let mut visited = 0;
+    while let Some(Open { index, .. }) = open.pop() {
+        if index == goal_index {
+            let mut path = vec![goal];
+            let mut index = goal_index;
+            while index != start_index {
+                index = came_from[index];
+                path.push(ivec2(
+                    (index % grid.width) as i32,
+                    (index / grid.width) as i32,
+                ));
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if closed[index] {
+            continue;
+        }
+        closed[index] = true;
+        visited += 1;
+        if visited > max_nodes {
+            return None;
+        }
+
+        let cell = ivec2((index % grid.width) as i32, (index / grid.width) as i32);
+        for step in [
+            IVec2::X,
+            IVec2::NEG_X,
+            IVec2::Y,
+            IVec2::NEG_Y,
+            ivec2(1, 1),
+            ivec2(1, -1),
+            ivec2(-1, 1),
+            ivec2(-1, -1),
+        ] {
+            let next = cell + step;
+            if !grid.free(next) {
+                continue;
+            }
+            let diagonal = step.x != 0 && step.y != 0;
+            if diagonal {
+                let sides = [
+                    grid.free(cell + ivec2(step.x, 0)),
+                    grid.free(cell + ivec2(0, step.y)),
+                ];
+                let allowed = match diagonals {
+                    Diagonals::Never => false,
+                    Diagonals::Always => true,
+                    Diagonals::IfOneFree => sides[0] || sides[1],
+                    Diagonals::IfBothFree => sides[0] && sides[1],
+                };
+                if !allowed {
+                    continue;
+                }
+            }
+            let next_index = grid.index(next).unwrap();
+            let step_cost = if diagonal {
+                grid.costs[next_index] * std::f32::consts::SQRT_2
+            } else {
+                grid.costs[next_index]
+            };
+            let next_cost = cost[index] + step_cost;
+            if next_cost < cost[next_index] {
+                cost[next_index] = next_cost;
+                came_from[next_index] = index;
+                open.push(Open {
+                    estimate: next_cost + estimate(next),
+                    index: next_index,
+                });
+            }
+        }
+    }
+    None
+}
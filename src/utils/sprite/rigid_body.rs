@@ -0,0 +1,650 @@
+use glam::*;
+use std::sync::Arc;
+
+use crate::utils::{RotationStyle, SpatialGrid, Sprite, SpriteTransform};
+
+// Contacts slower than this don't bounce, so resting bodies settle instead of jittering
+const BOUNCE_THRESHOLD: f32 = 30.0;
+// How much overlap is allowed to stay, and how much of the rest is pushed apart every step
+const PENETRATION_SLOP: f32 = 0.5;
+const CORRECTION: f32 = 0.6;
+const ITERATIONS: usize = 8;
+// Long frames (like the first one, or after a breakpoint) slow the simulation down instead of
+// running a lot of steps at once
+const MAX_STEPS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BodyKind {
+    Static,    // Never moved by the simulation
+    Dynamic,   // Moved by gravity, forces and collisions
+    Kinematic, // Moved by its velocity only, pushes dynamic bodies around
+}
+
+impl BodyKind {
+    pub fn parse(kind: &str) -> Result<Self, String> {
+        match kind {
+            "static" => Ok(Self::Static),
+            "dynamic" => Ok(Self::Dynamic),
+            "kinematic" => Ok(Self::Kinematic),
+            _ => Err(format!("Invalid body type: '{}'", kind)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColliderShape {
+    Box,
+    Circle,
+    Polygon, // The convex hull of the costume's opaque pixels
+}
+
+impl ColliderShape {
+    pub fn parse(shape: &str) -> Result<Self, String> {
+        match shape {
+            "box" => Ok(Self::Box),
+            "circle" => Ok(Self::Circle),
+            "polygon" => Ok(Self::Polygon),
+            _ => Err(format!("Invalid collider shape: '{}'", shape)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Contact {
+    pub name: String,
+    pub owner: String,
+    pub tags: Vec<String>,
+    pub normal: Vec2, // Points from this sprite towards the other one
+    pub point: Vec2,
+}
+
+impl Contact {
+    // Same rules as `touching()`: a sprite's name or tag covers its clones too
+    pub fn matches(&self, target: &str) -> bool {
+        self.name == target || self.owner == target || self.tags.iter().any(|t| t == target)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Body {
+    pub kind: BodyKind,
+    pub shape: ColliderShape,
+    pub mass: f32,
+    pub friction: f32,
+    pub restitution: f32,
+    pub fixed_rotation: bool,
+    pub gravity_scale: f32,
+    pub velocity: Vec2,
+    pub angular_velocity: f32, // Degrees per second, like `direction`
+    pub force: Vec2,
+    pub torque: f32,
+    pub contacts: Vec<Contact>,
+    pub started: Vec<Contact>,
+    pub ended: Vec<Contact>,
+    hull: Option<(usize, Vec<Vec2>)>,
+}
+
+impl Body {
+    pub fn new(kind: BodyKind) -> Self {
+        Self {
+            kind,
+            shape: ColliderShape::Box,
+            mass: 1.0,
+            friction: 0.5,
+            restitution: 0.0,
+            fixed_rotation: false,
+            gravity_scale: 1.0,
+            velocity: Vec2::ZERO,
+            angular_velocity: 0.0,
+            force: Vec2::ZERO,
+            torque: 0.0,
+            contacts: vec![],
+            started: vec![],
+            ended: vec![],
+            hull: None,
+        }
+    }
+
+    // A copy for a new clone, which starts out without contacts
+    pub fn for_clone(&self) -> Self {
+        Self {
+            contacts: vec![],
+            started: vec![],
+            ended: vec![],
+            ..self.clone()
+        }
+    }
+
+    // The setters below check the values the same way for `project.toml` and scripts. A mass of
+    // 0 or below would divide the impulses into infinite velocities.
+    pub fn set_mass(&mut self, mass: f32) -> Result<(), String> {
+        if !(mass > 0.0 && mass.is_finite()) {
+            return Err(format!(
+                "The mass must be a finite number above 0, not {}",
+                mass
+            ));
+        }
+        self.mass = mass;
+        Ok(())
+    }
+
+    pub fn set_friction(&mut self, friction: f32) -> Result<(), String> {
+        if !friction.is_finite() {
+            return Err(format!(
+                "The friction must be a finite number, not {}",
+                friction
+            ));
+        }
+        self.friction = friction.max(0.0);
+        Ok(())
+    }
+
+    pub fn set_restitution(&mut self, restitution: f32) -> Result<(), String> {
+        if !restitution.is_finite() {
+            return Err(format!(
+                "The restitution must be a finite number, not {}",
+                restitution
+            ));
+        }
+        self.restitution = restitution.clamp(0.0, 1.0);
+        Ok(())
+    }
+
+    fn inverse_mass(&self) -> f32 {
+        match self.kind {
+            BodyKind::Dynamic if self.mass > 0.0 => 1.0 / self.mass,
+            _ => 0.0,
+        }
+    }
+}
+
+pub struct PhysicsWorld {
+    pub gravity: Vec2,
+    pub step: f32,
+    accumulator: f32,
+}
+
+impl Default for PhysicsWorld {
+    fn default() -> Self {
+        Self {
+            gravity: vec2(0.0, -1000.0),
+            step: 1.0 / 60.0,
+            accumulator: 0.0,
+        }
+    }
+}
+
+enum Shape {
+    Polygon(Vec<Vec2>),
+    Circle(Vec2, f32),
+}
+
+// A body taken out of its sprite for the length of the simulation
+struct SimBody {
+    body: Body,
+    name: String,
+    owner: String,
+    tags: Vec<String>,
+    center: Vec2,
+    angle: f32,
+    rotates: bool,
+    local: Vec<Vec2>,
+    radius: f32,
+    inverse_mass: f32,
+    inverse_inertia: f32,
+    contacts: Vec<Contact>,
+}
+
+impl SimBody {
+    fn new(sprite: &Sprite, mut body: Body, owner: &str, tags: &[String]) -> Self {
+        let transform = SpriteTransform::new(sprite);
+        let (angle, flip) = (transform.angle, transform.flip);
+        let size = transform.size.abs();
+        let half = size / 2.0;
+        let local = match body.shape {
+            ColliderShape::Box | ColliderShape::Circle => vec![
+                vec2(-half.x, -half.y),
+                vec2(half.x, -half.y),
+                vec2(half.x, half.y),
+                vec2(-half.x, half.y),
+            ],
+            ColliderShape::Polygon => {
+                // The hull only changes with the costume, so it's cached by its mask
+                let mask = &sprite.costumes[sprite.costume()].mask;
+                let key = Arc::as_ptr(mask) as usize;
+                if body.hull.as_ref().is_none_or(|(k, _)| *k != key) {
+                    body.hull = Some((key, mask.convex_hull()));
+                }
+                let hull = &body.hull.as_ref().unwrap().1;
+                let flip = if flip { vec2(-1.0, 1.0) } else { Vec2::ONE };
+                let mut points = hull.iter().map(|p| *p * size * flip).collect::<Vec<_>>();
+                if flip.x < 0.0 {
+                    points.reverse();
+                }
+                points
+            }
+        };
+        let radius = half.x.min(half.y);
+
+        let inverse_mass = body.inverse_mass();
+        let rotates = body.kind != BodyKind::Static
+            && !body.fixed_rotation
+            && sprite.rotation_style == RotationStyle::AllAround;
+        let inertia = match body.shape {
+            ColliderShape::Circle => body.mass * radius * radius / 2.0,
+            _ => body.mass * (size.x * size.x + size.y * size.y) / 12.0,
+        };
+        let inverse_inertia = if rotates && inverse_mass > 0.0 && inertia > 0.0 {
+            1.0 / inertia
+        } else {
+            0.0
+        };
+        Self {
+            body,
+            name: sprite.name.clone(),
+            owner: owner.to_string(),
+            tags: tags.to_vec(),
+            center: sprite.center,
+            angle,
+            rotates,
+            local,
+            radius,
+            inverse_mass,
+            inverse_inertia,
+            contacts: vec![],
+        }
+    }
+
+    fn shape(&self) -> Shape {
+        match self.body.shape {
+            ColliderShape::Circle => Shape::Circle(self.center, self.radius),
+            _ => {
+                let rotation = Mat2::from_angle(self.angle);
+                Shape::Polygon(
+                    self.local
+                        .iter()
+                        .map(|p| self.center + rotation * *p)
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    fn angular_velocity(&self) -> f32 {
+        self.body.angular_velocity.to_radians()
+    }
+
+    fn velocity_at(&self, offset: Vec2) -> Vec2 {
+        self.body.velocity + self.angular_velocity() * offset.perp()
+    }
+
+    fn apply_impulse(&mut self, impulse: Vec2, offset: Vec2) {
+        self.body.velocity += impulse * self.inverse_mass;
+        self.body.angular_velocity +=
+            (offset.perp_dot(impulse) * self.inverse_inertia).to_degrees();
+    }
+}
+
+struct Manifold {
+    a: usize,
+    b: usize,
+    normal: Vec2, // From `a` to `b`
+    penetration: f32,
+    point: Vec2,
+    target: f32,
+    normal_impulse: f32,
+    tangent_impulse: f32,
+}
+
+// Moves every sprite with a body forward by `dt`, in fixed steps. Runs before the sprites'
+// scripts, so they see where the bodies ended up and what they touched this frame.
+pub fn step_physics(sprites: &mut [Sprite], world: &mut PhysicsWorld, dt: f32) {
+    let mut has_body = vec![];
+    let mut bodies = vec![];
+    for sprite in sprites.iter_mut() {
+        let (owner, tags) = (sprite.name.clone(), sprite.tags.clone());
+        gather(sprite, &owner, &tags, &mut has_body, &mut bodies);
+    }
+    if bodies.is_empty() {
+        return;
+    }
+
+    world.accumulator = (world.accumulator + dt).min(world.step * MAX_STEPS as f32);
+    let mut steps = 0;
+    while world.accumulator >= world.step {
+        world.accumulator -= world.step;
+        substep(&mut bodies, world, world.step);
+        steps += 1;
+    }
+
+    for body in &mut bodies {
+        body.body.force = Vec2::ZERO;
+        body.body.torque = 0.0;
+        if steps == 0 {
+            body.body.started.clear();
+            body.body.ended.clear();
+            continue;
+        }
+        let previous = std::mem::take(&mut body.body.contacts);
+        body.body.started = body
+            .contacts
+            .iter()
+            .filter(|c| !previous.iter().any(|p| p.name == c.name))
+            .cloned()
+            .collect();
+        body.body.ended = previous
+            .iter()
+            .filter(|p| !body.contacts.iter().any(|c| c.name == p.name))
+            .cloned()
+            .collect();
+        body.body.contacts = std::mem::take(&mut body.contacts);
+    }
+
+    let mut bodies = bodies.into_iter();
+    let mut has_body = has_body.into_iter();
+    for sprite in sprites.iter_mut() {
+        scatter(sprite, &mut has_body, &mut bodies);
+    }
+}
+
+// Clones come before their parent, and the same order is used to put the bodies back
+fn gather(
+    sprite: &mut Sprite,
+    owner: &str,
+    tags: &[String],
+    has_body: &mut Vec<bool>,
+    bodies: &mut Vec<SimBody>,
+) {
+    for clone in &mut sprite.clones {
+        gather(clone, owner, tags, has_body, bodies);
+    }
+    let body = if sprite.delete_pending {
+        None
+    } else {
+        sprite.body.take()
+    };
+    has_body.push(body.is_some());
+    if let Some(body) = body {
+        bodies.push(SimBody::new(sprite, body, owner, tags));
+    }
+}
+
+fn scatter(
+    sprite: &mut Sprite,
+    has_body: &mut impl Iterator<Item = bool>,
+    bodies: &mut impl Iterator<Item = SimBody>,
+) {
+    for clone in &mut sprite.clones {
+        scatter(clone, has_body, bodies);
+    }
+    if !has_body.next().unwrap_or(false) {
+        return;
+    }
+    let Some(simulated) = bodies.next() else {
+        return;
+    };
+    if simulated.body.kind != BodyKind::Static {
+        sprite.center = simulated.center;
+        if simulated.rotates {
+            sprite.direction = simulated.angle.to_degrees();
+        }
+    }
+    sprite.body = Some(simulated.body);
+}
+
+fn substep(bodies: &mut [SimBody], world: &PhysicsWorld, h: f32) {
+    for body in bodies.iter_mut() {
+        if body.body.kind == BodyKind::Dynamic {
+            body.body.velocity +=
+                (world.gravity * body.body.gravity_scale + body.body.force * body.inverse_mass) * h;
+            body.body.angular_velocity +=
+                (body.body.torque * body.inverse_inertia * h).to_degrees();
+        }
+    }
+
+    let mut manifolds = detect(bodies);
+    for m in &mut manifolds {
+        let (a, b) = (&bodies[m.a], &bodies[m.b]);
+        let relative = b.velocity_at(m.point - b.center) - a.velocity_at(m.point - a.center);
+        let approach = relative.dot(m.normal);
+        let restitution = a.body.restitution.max(b.body.restitution);
+        m.target = if approach < -BOUNCE_THRESHOLD {
+            -restitution * approach
+        } else {
+            0.0
+        };
+    }
+
+    // Sequential impulses, with the total impulse of each contact clamped so contacts only push
+    for _ in 0..ITERATIONS {
+        for m in &mut manifolds {
+            let (a, b) = pair(bodies, m.a, m.b);
+            let (ra, rb) = (m.point - a.center, m.point - b.center);
+            let relative = b.velocity_at(rb) - a.velocity_at(ra);
+
+            let normal_mass = effective_mass(a, b, ra, rb, m.normal);
+            let impulse = (m.target - relative.dot(m.normal)) * normal_mass;
+            let total = (m.normal_impulse + impulse).max(0.0);
+            let impulse = total - m.normal_impulse;
+            m.normal_impulse = total;
+            a.apply_impulse(-m.normal * impulse, ra);
+            b.apply_impulse(m.normal * impulse, rb);
+
+            let relative = b.velocity_at(rb) - a.velocity_at(ra);
+            let tangent = m.normal.perp();
+            let friction = (a.body.friction * b.body.friction).sqrt();
+            let impulse = -relative.dot(tangent) * effective_mass(a, b, ra, rb, tangent);
+            let limit = m.normal_impulse * friction;
+            let total = (m.tangent_impulse + impulse).clamp(-limit, limit);
+            let impulse = total - m.tangent_impulse;
+            m.tangent_impulse = total;
+            a.apply_impulse(-tangent * impulse, ra);
+            b.apply_impulse(tangent * impulse, rb);
+        }
+    }
+
+    for body in bodies.iter_mut() {
+        if body.body.kind != BodyKind::Static {
+            body.center += body.body.velocity * h;
+            if body.rotates {
+                body.angle += body.angular_velocity() * h;
+            }
+        }
+    }
+
+    for m in &manifolds {
+        let (a, b) = pair(bodies, m.a, m.b);
+        let total = a.inverse_mass + b.inverse_mass;
+        if total > 0.0 {
+            let correction =
+                m.normal * (m.penetration - PENETRATION_SLOP).max(0.0) / total * CORRECTION;
+            a.center -= correction * a.inverse_mass;
+            b.center += correction * b.inverse_mass;
+        }
+        let contact = |other: &SimBody, normal: Vec2| Contact {
+            name: other.name.clone(),
+            owner: other.owner.clone(),
+            tags: other.tags.clone(),
+            normal,
+            point: m.point,
+        };
+        let (contact_a, contact_b) = (contact(b, m.normal), contact(a, -m.normal));
+        if !a.contacts.iter().any(|c| c.name == contact_a.name) {
+            a.contacts.push(contact_a);
+        }
+        if !b.contacts.iter().any(|c| c.name == contact_b.name) {
+            b.contacts.push(contact_b);
+        }
+    }
+}
+
+fn pair(bodies: &mut [SimBody], a: usize, b: usize) -> (&mut SimBody, &mut SimBody) {
+    let (low, high) = bodies.split_at_mut(b);
+    (&mut low[a], &mut high[0])
+}
+
+fn effective_mass(a: &SimBody, b: &SimBody, ra: Vec2, rb: Vec2, direction: Vec2) -> f32 {
+    let ra_n = ra.perp_dot(direction);
+    let rb_n = rb.perp_dot(direction);
+    let k = a.inverse_mass
+        + b.inverse_mass
+        + ra_n * ra_n * a.inverse_inertia
+        + rb_n * rb_n * b.inverse_inertia;
+    if k > 0.0 { 1.0 / k } else { 0.0 }
+}
+
+fn detect(bodies: &[SimBody]) -> Vec<Manifold> {
+    let shapes = bodies.iter().map(SimBody::shape).collect::<Vec<_>>();
+    let bounds = shapes.iter().map(shape_bounds).collect::<Vec<_>>();
+    // Only bodies whose bounds overlap are checked, found with the same grid as `touching()`
+    let grid = SpatialGrid::from_bounds(&bounds);
+    let mut manifolds = vec![];
+    for a in 0..bodies.len() {
+        let (min, max) = bounds[a];
+        for (b, _) in grid.query(min, max) {
+            if b <= a
+                || bodies[a].body.kind != BodyKind::Dynamic
+                    && bodies[b].body.kind != BodyKind::Dynamic
+            {
+                continue;
+            }
+            let collision = match (&shapes[a], &shapes[b]) {
+                (Shape::Polygon(pa), Shape::Polygon(pb)) => polygon_polygon(pa, pb),
+                (Shape::Circle(ca, ra), Shape::Circle(cb, rb)) => circle_circle(*ca, *ra, *cb, *rb),
+                (Shape::Polygon(p), Shape::Circle(c, r)) => polygon_circle(p, *c, *r),
+                (Shape::Circle(c, r), Shape::Polygon(p)) => {
+                    polygon_circle(p, *c, *r).map(|(n, depth, point)| (-n, depth, point))
+                }
+            };
+            if let Some((normal, penetration, point)) = collision {
+                manifolds.push(Manifold {
+                    a,
+                    b,
+                    normal,
+                    penetration,
+                    point,
+                    target: 0.0,
+                    normal_impulse: 0.0,
+                    tangent_impulse: 0.0,
+                });
+            }
+        }
+    }
+    manifolds
+}
+
+fn shape_bounds(shape: &Shape) -> (Vec2, Vec2) {
+    match shape {
+        Shape::Circle(center, radius) => (center - radius, center + radius),
+        Shape::Polygon(points) => points.iter().fold((Vec2::MAX, Vec2::MIN), |(min, max), p| {
+            (min.min(*p), max.max(*p))
+        }),
+    }
+}
+
+fn centroid(points: &[Vec2]) -> Vec2 {
+    points.iter().sum::<Vec2>() / points.len().max(1) as f32
+}
+
+fn project(points: &[Vec2], axis: Vec2) -> (f32, f32) {
+    points.iter().fold((f32::MAX, f32::MIN), |(min, max), p| {
+        (min.min(p.dot(axis)), max.max(p.dot(axis)))
+    })
+}
+
+// Works for either winding
+fn contains(polygon: &[Vec2], point: Vec2) -> bool {
+    let sides = (0..polygon.len()).map(|i| {
+        let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+        (b - a).perp_dot(point - a)
+    });
+    let (mut positive, mut negative) = (false, false);
+    for side in sides {
+        positive |= side > 0.0;
+        negative |= side < 0.0;
+    }
+    !(positive && negative)
+}
+
+// Separating axis test. The contact point is the middle of the corners that ended up inside the
+// other polygon, which keeps boxes resting flat from rocking.
+fn polygon_polygon(a: &[Vec2], b: &[Vec2]) -> Option<(Vec2, f32, Vec2)> {
+    let mut best = (Vec2::ZERO, f32::MAX);
+    for polygon in [a, b] {
+        for i in 0..polygon.len() {
+            let edge = polygon[(i + 1) % polygon.len()] - polygon[i];
+            let axis = edge.perp().normalize_or_zero();
+            if axis == Vec2::ZERO {
+                continue;
+            }
+            let ((a_min, a_max), (b_min, b_max)) = (project(a, axis), project(b, axis));
+            let overlap = a_max.min(b_max) - a_min.max(b_min);
+            if overlap <= 0.0 {
+                return None;
+            }
+            if overlap < best.1 {
+                best = (axis, overlap);
+            }
+        }
+    }
+    let (mut normal, penetration) = best;
+    if (centroid(b) - centroid(a)).dot(normal) < 0.0 {
+        normal = -normal;
+    }
+
+    let inside = b
+        .iter()
+        .filter(|p| contains(a, **p))
+        .chain(a.iter().filter(|p| contains(b, **p)))
+        .collect::<Vec<_>>();
+    let point = if inside.is_empty() {
+        *b.iter()
+            .min_by(|p, q| p.dot(normal).total_cmp(&q.dot(normal)))?
+    } else {
+        inside.iter().copied().sum::<Vec2>() / inside.len() as f32
+    };
+    Some((normal, penetration, point))
+}
+
+fn circle_circle(a: Vec2, ra: f32, b: Vec2, rb: f32) -> Option<(Vec2, f32, Vec2)> {
+    let offset = b - a;
+    let distance = offset.length();
+    if distance >= ra + rb {
+        return None;
+    }
+    let normal = if distance > 0.0 {
+        offset / distance
+    } else {
+        Vec2::Y
+    };
+    Some((normal, ra + rb - distance, a + normal * ra))
+}
+
+// The normal points from the polygon to the circle
+fn polygon_circle(polygon: &[Vec2], center: Vec2, radius: f32) -> Option<(Vec2, f32, Vec2)> {
+    let closest = (0..polygon.len())
+        .map(|i| {
+            let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+            let t = ((center - a).dot(b - a) / (b - a).length_squared().max(f32::EPSILON))
+                .clamp(0.0, 1.0);
+            a + (b - a) * t
+        })
+        .min_by(|p, q| {
+            p.distance_squared(center)
+                .total_cmp(&q.distance_squared(center))
+        })?;
+    let offset = center - closest;
+    let distance = offset.length();
+    if contains(polygon, center) {
+        let normal = if distance > 0.0 {
+            -offset / distance
+        } else {
+            (center - centroid(polygon)).normalize_or(Vec2::Y)
+        };
+        Some((normal, radius + distance, closest))
+    } else if distance < radius {
+        Some((offset / distance, radius - distance, closest))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,223 @@
+use glam::*;
+
+use crate::utils::{Value, cubic_bezier};
+
+// How many straight pieces each curved segment is measured and followed with
+const SAMPLES: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathCurve {
+    // Straight lines between the points
+    Linear,
+    // A smooth curve through every point
+    CatmullRom,
+    // Cubic beziers, as start, control, control, end, control, control, end...
+    Bezier,
+}
+
+impl PathCurve {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "linear" => Ok(Self::Linear),
+            "catmull-rom" => Ok(Self::CatmullRom),
+            "bezier" => Ok(Self::Bezier),
+            _ => Err(format!(
+                "Unknown curve '{}', expected \"linear\", \"catmull-rom\" or \"bezier\"",
+                name
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathMode {
+    Once,
+    Loop,
+    PingPong,
+}
+
+// A curve flattened into short straight pieces, so it can be followed at a constant speed
+#[derive(Debug, Clone)]
+pub struct Route {
+    points: Vec<Vec2>,
+    // The distance along the route at each point
+    lengths: Vec<f32>,
+}
+
+impl Route {
+    // A closed route joins its last point back to its first
+    pub fn new(points: &[Vec2], curve: PathCurve, closed: bool) -> Result<Self, String> {
+        if points.len() < 2 {
+            return Err("A path needs at least two points".to_string());
+        }
+        let mut samples = match curve {
+            PathCurve::Linear => points.to_vec(),
+            PathCurve::CatmullRom => catmull_rom(points, closed),
+            PathCurve::Bezier => bezier(points, closed)?,
+        };
+        if closed && curve == PathCurve::Linear {
+            samples.push(points[0]);
+        }
+        let mut lengths = Vec::with_capacity(samples.len());
+        let mut total = 0.0;
+        for (i, point) in samples.iter().enumerate() {
+            if i > 0 {
+                total += point.distance(samples[i - 1]);
+            }
+            lengths.push(total);
+        }
+        Ok(Self {
+            points: samples,
+            lengths,
+        })
+    }
+
+    pub fn length(&self) -> f32 {
+        self.lengths.last().copied().unwrap_or(0.0)
+    }
+
+    // The point at `distance` along the route and the direction the route goes there
+    pub fn sample(&self, distance: f32) -> (Vec2, Vec2) {
+        let distance = distance.clamp(0.0, self.length());
+        let i = self
+            .lengths
+            .partition_point(|l| *l < distance)
+            .clamp(1, self.points.len() - 1);
+        let (a, b) = (self.points[i - 1], self.points[i]);
+        let span = self.lengths[i] - self.lengths[i - 1];
+        let t = if span > 0.0 {
+            (distance - self.lengths[i - 1]) / span
+        } else {
+            0.0
+        };
+        (a.lerp(b, t), (b - a).normalize_or_zero())
+    }
+}
+
+fn catmull_rom(points: &[Vec2], closed: bool) -> Vec<Vec2> {
+    let n = points.len() as isize;
+    // Open routes repeat their end points, closed ones wrap around
+    let point = |i: isize| {
+        if closed {
+            points[i.rem_euclid(n) as usize]
+        } else {
+            points[i.clamp(0, n - 1) as usize]
+        }
+    };
+    let segments = if closed { n } else { n - 1 };
+    let mut samples = vec![points[0]];
+    for i in 0..segments {
+        let (p0, p1, p2, p3) = (point(i - 1), point(i), point(i + 1), point(i + 2));
+        for s in 1..=SAMPLES {
+            let t = s as f32 / SAMPLES as f32;
+            let (t2, t3) = (t * t, t * t * t);
+            samples.push(
+                0.5 * (2.0 * p1
+                    + (p2 - p0) * t
+                    + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+                    + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3),
+            );
+        }
+    }
+    samples
+}
+
+fn bezier(points: &[Vec2], closed: bool) -> Result<Vec<Vec2>, String> {
+    // A closed route leaves out its last point, since it ends back at the first one
+    let mut points = points.to_vec();
+    if closed {
+        points.push(points[0]);
+    }
+    if points.len() < 4 || !(points.len() - 1).is_multiple_of(3) {
+        return Err(if closed {
+            "A looping bezier path needs 3 points per curve: control, control, end".to_string()
+        } else {
+            "A bezier path needs a start point and 3 points per curve: control, control, end"
+                .to_string()
+        });
+    }
+    let mut samples = vec![points[0]];
+    for curve in points.windows(4).step_by(3) {
+        let [p0, p1, p2, p3] = [curve[0], curve[1], curve[2], curve[3]];
+        for s in 1..=SAMPLES {
+            let t = s as f32 / SAMPLES as f32;
+            samples.push(vec2(
+                cubic_bezier(t, p0.x, p1.x, p2.x, p3.x),
+                cubic_bezier(t, p0.y, p1.y, p2.y, p3.y),
+            ));
+        }
+    }
+    Ok(samples)
+}
+
+#[derive(Debug, Clone)]
+pub struct PathFollow {
+    pub route: Route,
+    // Units per second
+    pub speed: f32,
+    pub distance: f32,
+    pub mode: PathMode,
+    pub forward: bool,
+    // Whether the sprite points along the route
+    pub orient: bool,
+    pub on_complete: Option<Value>,
+}
+
+impl PathFollow {
+    pub fn new(route: Route, speed: f32, mode: PathMode) -> Self {
+        Self {
+            route,
+            speed,
+            distance: 0.0,
+            mode,
+            forward: true,
+            orient: false,
+            on_complete: None,
+        }
+    }
+
+    // How far along the route the sprite is, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        let length = self.route.length();
+        if length > 0.0 {
+            self.distance / length
+        } else {
+            1.0
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.mode == PathMode::Once && self.distance >= self.route.length()
+    }
+
+    // Moves along by `dt` and returns the position and the direction of travel
+    pub fn advance(&mut self, dt: f32) -> (Vec2, Vec2) {
+        let length = self.route.length();
+        let step = self.speed * dt;
+        match self.mode {
+            PathMode::Once => self.distance = (self.distance + step).min(length),
+            PathMode::Loop if length > 0.0 => {
+                self.distance = (self.distance + step).rem_euclid(length)
+            }
+            PathMode::Loop => {}
+            PathMode::PingPong if length > 0.0 => {
+                // Walks a route twice as long and folds the way back onto it
+                let travelled = if self.forward {
+                    self.distance
+                } else {
+                    length * 2.0 - self.distance
+                };
+                let travelled = (travelled + step).rem_euclid(length * 2.0);
+                self.forward = travelled <= length;
+                self.distance = if self.forward {
+                    travelled
+                } else {
+                    length * 2.0 - travelled
+                };
+            }
+            PathMode::PingPong => {}
+        }
+        let (position, tangent) = self.route.sample(self.distance);
+        (position, if self.forward { tangent } else { -tangent })
+    }
+}
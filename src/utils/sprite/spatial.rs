@@ -0,0 +1,213 @@
+use glam::*;
+use std::collections::HashMap;
+use std::ops::Deref;
+
+use crate::utils::{Hitbox, Sprite, SpriteSnapshot};
+
+// The size of a grid cell in world units. Most sprites cover one to four cells.
+const CELL_SIZE: f32 = 128.0;
+// Hitboxes that would cover more cells than this (huge or very stretched sprites) go in a
+// separate list that every query checks
+const MAX_CELLS: f32 = 64.0;
+
+struct Entry {
+    // (snapshot, hitbox) indices, or (body, 0) for physics bodies
+    key: (usize, usize),
+    min: Vec2,
+    max: Vec2,
+}
+
+// A uniform grid over the hitboxes of every sprite and clone, so that queries only look at
+// the hitboxes near them. Entries are numbered in draw order. The physics uses one over the
+// bounds of its bodies too.
+pub struct SpatialGrid {
+    entries: Vec<Entry>,
+    cells: HashMap<IVec2, Vec<usize>>,
+    large: Vec<usize>,
+    // The cells that have anything in them
+    cell_min: IVec2,
+    cell_max: IVec2,
+}
+
+fn cell(point: Vec2) -> IVec2 {
+    (point / CELL_SIZE).floor().as_ivec2()
+}
+
+impl SpatialGrid {
+    fn empty() -> Self {
+        Self {
+            entries: vec![],
+            cells: HashMap::new(),
+            large: vec![],
+            cell_min: IVec2::MAX,
+            cell_max: IVec2::MIN,
+        }
+    }
+
+    pub fn new(snapshots: &[SpriteSnapshot]) -> Self {
+        let mut grid = Self::empty();
+        for (s, snapshot) in snapshots.iter().enumerate() {
+            for (h, hitbox) in snapshot.hitboxes.iter().enumerate() {
+                let (min, max) = hitbox.bounds();
+                grid.insert((s, h), min, max);
+            }
+        }
+        grid
+    }
+
+    // A grid over plain boxes, which queries return as (index, 0)
+    pub fn from_bounds(bounds: &[(Vec2, Vec2)]) -> Self {
+        let mut grid = Self::empty();
+        for (i, (min, max)) in bounds.iter().enumerate() {
+            grid.insert((i, 0), *min, *max);
+        }
+        grid
+    }
+
+    fn insert(&mut self, key: (usize, usize), min: Vec2, max: Vec2) {
+        let id = self.entries.len();
+        self.entries.push(Entry { key, min, max });
+        let span = ((max - min) / CELL_SIZE).ceil() + 1.0;
+        if !min.is_finite() || !max.is_finite() || span.x * span.y > MAX_CELLS {
+            self.large.push(id);
+            return;
+        }
+        let (a, b) = (cell(min), cell(max));
+        for y in a.y..=b.y {
+            for x in a.x..=b.x {
+                self.cells.entry(ivec2(x, y)).or_default().push(id);
+            }
+        }
+        self.cell_min = self.cell_min.min(a);
+        self.cell_max = self.cell_max.max(b);
+    }
+
+    // The hitboxes whose bounds overlap `min..max`, as (snapshot, hitbox) indices in draw order
+    pub fn query(&self, min: Vec2, max: Vec2) -> Vec<(usize, usize)> {
+        let mut found = self.large.clone();
+        let (a, b) = (cell(min).max(self.cell_min), cell(max).min(self.cell_max));
+        for y in a.y..=b.y {
+            for x in a.x..=b.x {
+                if let Some(ids) = self.cells.get(&ivec2(x, y)) {
+                    found.extend(ids);
+                }
+            }
+        }
+        found.sort_unstable();
+        found.dedup();
+        found
+            .into_iter()
+            .map(|id| &self.entries[id])
+            .filter(|e| e.min.cmple(max).all() && e.max.cmpge(min).all())
+            .map(|e| e.key)
+            .collect()
+    }
+
+    // The hitbox whose center is closest to `point`, out of the ones `accept` allows. Searches
+    // outwards ring by ring, and stops once nothing further out can be closer.
+    pub fn nearest(
+        &self,
+        point: Vec2,
+        mut accept: impl FnMut(usize, usize) -> bool,
+    ) -> Option<(usize, usize)> {
+        let mut best: Option<(f32, usize)> = None;
+        let mut check = |id: usize, best: &mut Option<(f32, usize)>| {
+            let entry = &self.entries[id];
+            let distance = point.distance((entry.min + entry.max) / 2.0);
+            if best.is_none_or(|(d, _)| distance < d) && accept(entry.key.0, entry.key.1) {
+                *best = Some((distance, id));
+            }
+        };
+        for &id in &self.large {
+            check(id, &mut best);
+        }
+        if self.cell_min.cmple(self.cell_max).all() {
+            let center = cell(point);
+            let outside = (self.cell_min.saturating_sub(center))
+                .max(center.saturating_sub(self.cell_max))
+                .max(IVec2::ZERO);
+            let farthest = (center.saturating_sub(self.cell_min))
+                .abs()
+                .max(self.cell_max.saturating_sub(center).abs());
+            for ring in outside.max_element()..=farthest.max_element() {
+                for cell in self.ring(center, ring) {
+                    for &id in self.cells.get(&cell).into_iter().flatten() {
+                        check(id, &mut best);
+                    }
+                }
+                if best.is_some_and(|(d, _)| d <= ring as f32 * CELL_SIZE) {
+                    break;
+                }
+            }
+        }
+        best.map(|(_, id)| self.entries[id].key)
+    }
+
+    // The cells `ring` steps away from `center`, leaving out the ones outside of the grid
+    fn ring(&self, center: IVec2, ring: i32) -> Vec<IVec2> {
+        let (min, max) = (
+            center.saturating_sub(IVec2::splat(ring)),
+            center.saturating_add(IVec2::splat(ring)),
+        );
+        let mut cells = vec![];
+        for y in [min.y, max.y] {
+            if (self.cell_min.y..=self.cell_max.y).contains(&y) {
+                for x in min.x.max(self.cell_min.x)..=max.x.min(self.cell_max.x) {
+                    cells.push(ivec2(x, y));
+                }
+            }
+            if ring == 0 {
+                return cells;
+            }
+        }
+        for x in [min.x, max.x] {
+            if (self.cell_min.x..=self.cell_max.x).contains(&x) {
+                for y in (min.y + 1).max(self.cell_min.y)..=(max.y - 1).min(self.cell_max.y) {
+                    cells.push(ivec2(x, y));
+                }
+            }
+        }
+        cells
+    }
+}
+
+// Every sprite as it was at the start of the frame, which is what scripts see of the other
+// sprites. Built once per frame.
+pub struct Snapshots {
+    sprites: Vec<SpriteSnapshot>,
+    names: HashMap<String, usize>,
+    pub grid: SpatialGrid,
+}
+
+impl Snapshots {
+    pub fn new(sprites: &[Sprite]) -> Self {
+        let sprites: Vec<SpriteSnapshot> = sprites.iter().map(|s| s.into()).collect();
+        let mut names = HashMap::new();
+        for (i, snapshot) in sprites.iter().enumerate() {
+            names.entry(snapshot.name.clone()).or_insert(i);
+        }
+        Self {
+            grid: SpatialGrid::new(&sprites),
+            sprites,
+            names,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SpriteSnapshot> {
+        self.names.get(name).map(|i| &self.sprites[*i])
+    }
+
+    // Turns the indices from the grid back into the sprite and the hitbox
+    pub fn hitbox(&self, (snapshot, hitbox): (usize, usize)) -> (&SpriteSnapshot, &Hitbox) {
+        let snapshot = &self.sprites[snapshot];
+        (snapshot, &snapshot.hitboxes[hitbox])
+    }
+}
+
+impl Deref for Snapshots {
+    type Target = [SpriteSnapshot];
+
+    fn deref(&self) -> &Self::Target {
+        &self.sprites
+    }
+}
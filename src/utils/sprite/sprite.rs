@@ -13,7 +13,6 @@
 use glam::*;
 use indexmap::IndexMap;
 use kira::sound::static_sound::{StaticSoundData, StaticSoundHandle};
-use kira::{AudioManager, DefaultBackend};
 use std::collections::HashMap;
 use std::f32::consts::*;
 use std::path::PathBuf;
@@ -21,16 +20,32 @@ use std::path::PathBuf;
 use crate::utils::core::*;
 use crate::utils::*;
 
+// The largest width or height SVG costumes are rasterised at
+const MAX_VECTOR_SIZE: f32 = 4096.0;
+
+// Leaves out a costume that was removed and moves the ones after it down by one
+fn without_costume<T>(costumes: HashMap<usize, T>, index: usize) -> HashMap<usize, T> {
+    costumes
+        .into_iter()
+        .filter(|(i, _)| *i != index)
+        .map(|(i, value)| (if i > index { i - 1 } else { i }, value))
+        .collect()
+}
+
+// The script ids that tween and path callbacks run as, so errors and the watchdog can tell them
+// apart
+pub const TWEEN_CALLBACK: usize = usize::MAX;
+pub const PATH_CALLBACK: usize = usize::MAX - 1;
+
 pub struct State<'a> {
     pub start: std::time::Instant,
     pub dt: f32,
     pub sprite: &'a mut Sprite,
     pub project: &'a mut Project,
-    pub snapshots: &'a [SpriteSnapshot],
-    pub window: &'a mut glfw::Window,
+    pub snapshots: &'a Snapshots,
+    pub window: &'a mut dyn WindowBackend,
     pub input_manager: &'a mut InputManager,
-    pub glfw: &'a mut glfw::Glfw,
-    pub audio_manager: &'a mut AudioManager<DefaultBackend>,
+    pub audio_manager: &'a mut dyn AudioBackend,
     pub shader_program: &'a ShaderProgram,
     pub projection: &'a mut Mat4,
     pub font: &'a BitmapFont,
@@ -47,6 +62,7 @@ pub struct SpriteSnapshot {
     pub direction: f32,
     pub completed_broadcasts: Vec<usize>,
     pub tags: Vec<String>,
+    pub hitboxes: Vec<Hitbox>,
 }
 
 impl SpriteSnapshot {
@@ -85,6 +101,11 @@ impl From<&Sprite> for SpriteSnapshot {
             direction: sprite.direction,
             completed_broadcasts: sprite.completed_broadcasts.clone(),
             tags: sprite.tags.clone(),
+            hitboxes: {
+                let mut hitboxes = vec![];
+                collision::hitboxes(sprite, &mut hitboxes);
+                hitboxes
+            },
         }
     }
 }
@@ -124,62 +145,23 @@ pub(super) struct Dialogue {
     pub(super) think: bool,
 }
 
-#[derive(Debug)]
-pub struct Sprite {
-    pub name: String,
-    pub costumes: Vec<GPUTexture>,
-    pub sounds: HashMap<String, StaticSoundData>,
-    pub center: Vec2,
-    pub size: Vec2,
-    pub direction: f32,
-    pub rotation_style: RotationStyle,
-    pub scale: f32,
-    pub layer: isize,
-    pub variables: HashMap<String, Value>,
-    pub effects: IndexMap<String, f32>,
-    pub sound_filters: IndexMap<String, f32>,
-    pub draw_color: Vec4,
-    pub functions: HashMap<String, Callable>,
-    pub clone_id: Option<usize>,
-    pub stop_request: Option<StopRequest>,
-    pub tags: Vec<String>,
-    pub(super) visible: bool,
-    pub(super) clones: Vec<Sprite>,
-    pub(super) dialogue: Option<Dialogue>,
-    pub(super) edge_bounce: bool,
-    pub(super) current_costume: usize,
-    pub(super) time_waiting: u32,
-    pub(super) glide: Option<Glide>,
-    pub(super) delete_pending: bool,
-    pub(super) sound_handles: HashMap<String, StaticSoundHandle>,
-    pub(super) skip_further_execution_of_frame: bool,
-    pub(super) uv: [Vec2; 2],
-    clone_setup: Vec<Statement>,
-    clone_update: Vec<Vec<Statement>>,
+// Everything a sprite gets out of its code. Kept separate from `Sprite` so the code can be
+// swapped out (hot reload) without touching the rest of the sprite's state.
+pub struct SpriteCode {
     setup_ast: Vec<Statement>,
     update_ast: Vec<Vec<Statement>>,
-    setup_finished: bool,
+    functions: HashMap<String, Callable>,
     broadcast_recievers: HashMap<String, Vec<Statement>>,
-    boolean_recievers: Vec<(Expression, Vec<Statement>, bool)>,
-    completed_broadcasts: Vec<usize>,
+    pub(super) boolean_recievers: Vec<(Expression, Vec<Statement>, bool)>,
+    clone_setup: Vec<Statement>,
+    clone_update: Vec<Vec<Statement>>,
+    tests: Vec<(String, Vec<Statement>)>,
 }
 
-impl Sprite {
-    pub fn new(
-        name: String,
-        costumes: Vec<GPUTexture>,
-        sounds: HashMap<String, StaticSoundData>,
-        ast: Vec<Statement>,
-        tags: Vec<String>,
-        w: f32,
-        h: f32,
-        x: f32,
-        y: f32,
-        visibility: bool,
-        layer: isize,
-        direction: f32,
-        base_dir: String,
-    ) -> Self {
+impl SpriteCode {
+    // `debugging` parses imported modules with locations for the debugger, like the sprite's own
+    // code
+    pub fn compile(ast: Vec<Statement>, base_dir: &str, debugging: bool) -> Self {
         let mut setup_ast = vec![];
         let mut update_ast = vec![];
         let mut broadcast_recievers = HashMap::new();
@@ -187,8 +169,12 @@ impl Sprite {
         let mut functions = HashMap::new();
         let mut clone_setup = vec![];
         let mut clone_update = vec![];
+        let mut tests = vec![];
         for statement in ast {
             match statement {
+                Statement::Test { name, body } => {
+                    tests.push((name, body));
+                }
                 Statement::Setup { body } => {
                     setup_ast.extend(body);
                 }
@@ -228,10 +214,22 @@ impl Sprite {
                         path: PathBuf,
                         visited: &mut Vec<String>,
                         setup_ast: &mut Vec<Statement>,
+                        debugging: bool,
                     ) -> HashMap<String, Function> {
-                        let md = path.metadata().unwrap();
+                        // A missing module must not take the game down, it may be
+                        // in the middle of being saved while hot reloading
+                        let Ok(md) = path.metadata() else {
+                            console::error(
+                                None,
+                                format!("Failed to load module: {}", &path.display()),
+                            );
+                            return HashMap::new();
+                        };
                         if md.is_dir() {
-                            println!("Importing directory as module: {}", &path.display());
+                            console::info(
+                                None,
+                                format!("Importing directory as module: {}", &path.display()),
+                            );
                             let children = std::fs::read_dir(&path)
                                 .unwrap_or_else(|_| {
                                     panic!("Failed to read directory: {}", &path.display());
@@ -240,18 +238,25 @@ impl Sprite {
                                 .collect::<Vec<_>>();
                             let mut functions = HashMap::new();
                             for child in children {
-                                let imported_functions = import_module(child, visited, setup_ast);
+                                let imported_functions =
+                                    import_module(child, visited, setup_ast, debugging);
                                 functions.extend(imported_functions);
                             }
                             return functions;
                         }
                         let code = std::fs::read_to_string(&path).unwrap_or_else(|_| {
-                            println!("Failed to load module: {}", &path.display());
+                            console::error(
+                                None,
+                                format!("Failed to load module: {}", &path.display()),
+                            );
                             String::new()
                         });
                         let mut tokenizer = Tokenizer::new(code);
                         let tokens = tokenizer.tokenize_full();
                         let mut parser = Parser::new(tokens);
+                        if debugging {
+                            parser.set_file(&path);
+                        }
                         let ast = parser.parse();
                         let mut functions = HashMap::new();
                         for statement in ast {
@@ -298,12 +303,19 @@ impl Sprite {
                                 }
                                 Statement::Import { path } => {
                                     if visited.contains(&path) {
-                                        println!("Circular import detected: {}, skipping", path);
+                                        console::warning(
+                                            None,
+                                            format!("Circular import detected: {}, skipping", path),
+                                        );
                                         return functions;
                                     }
                                     visited.push(path.clone());
-                                    let imported_functions =
-                                        import_module(PathBuf::from(&path), visited, setup_ast);
+                                    let imported_functions = import_module(
+                                        PathBuf::from(&path),
+                                        visited,
+                                        setup_ast,
+                                        debugging,
+                                    );
                                     functions.extend(imported_functions);
                                 }
                                 _ => {}
@@ -313,9 +325,10 @@ impl Sprite {
                     }
                     let mut visited: Vec<String> = vec![];
                     let imported_functions = import_module(
-                        PathBuf::from(&base_dir).join(&path),
+                        PathBuf::from(base_dir).join(&path),
                         &mut visited,
                         &mut setup_ast,
+                        debugging,
                     );
                     for (name, function) in imported_functions {
                         functions.insert(name, Callable::Function(function));
@@ -324,9 +337,100 @@ impl Sprite {
                 _ => {}
             }
         }
+        Self {
+            setup_ast,
+            update_ast,
+            functions,
+            broadcast_recievers,
+            boolean_recievers,
+            clone_setup,
+            clone_update,
+            tests,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Sprite {
+    pub name: String,
+    pub costumes: Vec<Costume>,
+    pub sounds: HashMap<String, StaticSoundData>,
+    pub center: Vec2,
+    pub size: Vec2,
+    pub direction: f32,
+    pub rotation_style: RotationStyle,
+    pub scale: f32,
+    pub layer: isize,
+    pub variables: HashMap<String, Value>,
+    pub effects: IndexMap<String, f32>,
+    pub sound_filters: IndexMap<String, f32>,
+    pub draw_color: Vec4,
+    pub functions: HashMap<String, Callable>,
+    pub clone_id: Option<usize>,
+    pub stop_request: Option<StopRequest>,
+    pub tags: Vec<String>,
+    pub tests: Vec<(String, Vec<Statement>)>,
+    pub body: Option<Body>,
+    pub animations: HashMap<String, Animation>,
+    pub(super) visible: bool,
+    pub(super) clones: Vec<Sprite>,
+    pub(super) dialogue: Option<Dialogue>,
+    pub(super) edge_bounce: bool,
+    pub(super) current_costume: usize,
+    pub(super) time_waiting: u32,
+    pub(super) glide: Option<Glide>,
+    pub(super) tweens: Vec<PropertyTween>,
+    pub(super) path: Option<PathFollow>,
+    pub(super) animation: Option<AnimationPlayback>,
+    // SVG costumes by number, with the size they were last rasterised at. Clones share the
+    // costumes of the original sprite, so only it keeps these.
+    vector_costumes: HashMap<usize, (VectorImage, UVec2)>,
+    // Costumes by number with pixels changed by `set_pixel()`, until they are uploaded
+    pixel_edits: HashMap<usize, CPUTexture>,
+    pub(super) delete_pending: bool,
+    pub(super) sound_handles: HashMap<String, StaticSoundHandle>,
+    pub(super) skip_further_execution_of_frame: bool,
+    pub(super) uv: [Vec2; 2],
+    clone_setup: Vec<Statement>,
+    clone_update: Vec<Vec<Statement>>,
+    setup_ast: Vec<Statement>,
+    update_ast: Vec<Vec<Statement>>,
+    pub(super) setup_finished: bool,
+    broadcast_recievers: HashMap<String, Vec<Statement>>,
+    pub(super) boolean_recievers: Vec<(Expression, Vec<Statement>, bool)>,
+    completed_broadcasts: Vec<usize>,
+}
+
+impl Sprite {
+    pub fn new(
+        name: String,
+        costumes: Vec<Costume>,
+        sounds: HashMap<String, StaticSoundData>,
+        ast: Vec<Statement>,
+        tags: Vec<String>,
+        w: f32,
+        h: f32,
+        x: f32,
+        y: f32,
+        visibility: bool,
+        layer: isize,
+        direction: f32,
+        base_dir: String,
+        debugging: bool,
+    ) -> Self {
+        let SpriteCode {
+            setup_ast,
+            update_ast,
+            functions,
+            broadcast_recievers,
+            boolean_recievers,
+            clone_setup,
+            clone_update,
+            tests,
+        } = SpriteCode::compile(ast, &base_dir, debugging);
         let mut costumes = costumes;
         if costumes.is_empty() {
-            costumes.push(CPUTexture::new(100, 100).upload_to_gpu());
+            costumes.push(Costume::single(&CPUTexture::new(100, 100)));
         }
         Self {
             name,
@@ -350,6 +454,11 @@ impl Sprite {
             time_waiting: 0,
             dialogue: None,
             glide: None,
+            tweens: vec![],
+            path: None,
+            animation: None,
+            vector_costumes: HashMap::new(),
+            pixel_edits: HashMap::new(),
             draw_color: Vec3::splat(0.0).extend(1.0),
             edge_bounce: false,
             clones: vec![],
@@ -359,6 +468,9 @@ impl Sprite {
             delete_pending: false,
             stop_request: None,
             tags,
+            tests,
+            body: None,
+            animations: HashMap::new(),
             broadcast_recievers,
             boolean_recievers,
             sound_handles: HashMap::new(),
@@ -368,6 +480,262 @@ impl Sprite {
         }
     }
 
+    // Swaps in freshly compiled code. Variables, position, costumes and everything else stay as
+    // they are, and `setup` is not run again. `when` conditions are re-armed.
+    pub fn reload_code(&mut self, code: SpriteCode) {
+        if !self.setup_finished {
+            self.setup_ast = code.setup_ast;
+        }
+        self.update_ast = code.update_ast;
+        self.functions = code.functions;
+        self.broadcast_recievers = code.broadcast_recievers;
+        self.boolean_recievers = code.boolean_recievers;
+        self.clone_setup = code.clone_setup;
+        self.clone_update = code.clone_update;
+        self.tests = code.tests;
+
+        for clone in &mut self.clones {
+            clone.update_ast = self.clone_update.clone();
+            clone.functions = self.functions.clone();
+            clone.broadcast_recievers = self.broadcast_recievers.clone();
+            clone.boolean_recievers = self.boolean_recievers.clone();
+            clone.clone_setup = self.clone_setup.clone();
+            clone.clone_update = self.clone_update.clone();
+        }
+    }
+
+    // Swaps a costume for another. Clones share their parent's costumes, so they are updated
+    // along with it.
+    pub fn replace_costume(&mut self, index: usize, costume: &Costume) {
+        if index < self.costumes.len() {
+            self.costumes[index] = costume.clone();
+        }
+        for clone in &mut self.clones {
+            clone.replace_costume(index, costume);
+        }
+    }
+
+    // Re-uploads a costume's pixels in place
+    pub fn update_costume(&mut self, index: usize, texture: &CPUTexture, atlas: &mut Atlas) {
+        if let Some(costume) = self.costumes.get(index) {
+            let costume = costume.replaced(texture, atlas);
+            self.replace_costume(index, &costume);
+        }
+    }
+
+    pub fn add_costume(&mut self, costume: Costume) {
+        for clone in &mut self.clones {
+            clone.add_costume(costume.clone());
+        }
+        self.costumes.push(costume);
+    }
+
+    // Removes a costume from the sprite and its clones. The costumes after it move down by one,
+    // along with the animations and SVG costumes that show them.
+    pub fn remove_costume(&mut self, index: usize) {
+        if index >= self.costumes.len() {
+            return;
+        }
+        for clone in &mut self.clones {
+            clone.remove_costume(index);
+        }
+        self.costumes.remove(index);
+        if self.current_costume > index || self.current_costume == self.costumes.len() {
+            self.current_costume = self.current_costume.saturating_sub(1);
+        }
+        for animation in self.animations.values_mut() {
+            animation.remove_costume(index);
+        }
+        self.vector_costumes = without_costume(std::mem::take(&mut self.vector_costumes), index);
+        self.pixel_edits = without_costume(std::mem::take(&mut self.pixel_edits), index);
+    }
+
+    // The pixel at `x, y` from the top left corner, with the changes that aren't uploaded yet
+    pub fn costume_pixel(&self, index: usize, x: u32, y: u32) -> Option<U8Vec4> {
+        match self.pixel_edits.get(&index) {
+            Some(edits) => (x < edits.width && y < edits.height)
+                .then(|| edits.data[(y * edits.width + x) as usize]),
+            None => self.costumes.get(index)?.mask.get(x, y),
+        }
+    }
+
+    // A copy of the costume's pixels, with the changes that aren't uploaded yet
+    pub fn costume_pixels(&self, index: usize) -> Option<CPUTexture> {
+        match self.pixel_edits.get(&index) {
+            Some(edits) => Some(edits.clone()),
+            None => Some(self.costumes.get(index)?.mask.to_texture()),
+        }
+    }
+
+    // Changes a pixel without uploading it, so many can be changed at once. Returns false if the
+    // pixel is outside of the costume.
+    pub fn set_costume_pixel(&mut self, index: usize, x: u32, y: u32, color: U8Vec4) -> bool {
+        if self.costume_pixel(index, x, y).is_none() {
+            return false;
+        }
+        let edits = self
+            .pixel_edits
+            .entry(index)
+            .or_insert_with(|| self.costumes[index].mask.to_texture());
+        edits.data[(y * edits.width + x) as usize] = color;
+        true
+    }
+
+    // Uploads new pixels for a costume, or the ones changed with `set_costume_pixel`. A costume
+    // loaded from a file is shared with every sprite that uses the file, so the first upload
+    // gives the sprite a copy of its own.
+    pub fn upload_costume_pixels(
+        &mut self,
+        index: usize,
+        texture: Option<CPUTexture>,
+        atlas: &mut Atlas,
+    ) {
+        let Some(texture) = texture.or_else(|| self.pixel_edits.remove(&index)) else {
+            return;
+        };
+        let Some(costume) = self.costumes.get(index) else {
+            return;
+        };
+        self.pixel_edits.remove(&index);
+        // SVG costumes would be rasterised again over the new pixels
+        self.vector_costumes.remove(&index);
+        if costume.editable {
+            self.update_costume(index, &texture, atlas);
+        } else {
+            let costume = Costume::editable(&texture, atlas).named(&costume.name);
+            self.replace_costume(index, &costume);
+        }
+    }
+
+    // The number of the first costume with that name
+    pub fn costume_index(&self, name: &str) -> Option<usize> {
+        self.costumes.iter().position(|c| c.name == name)
+    }
+
+    // Sounds are added to and removed from the clones as well
+    pub fn add_sound(&mut self, name: &str, sound: StaticSoundData) {
+        for clone in &mut self.clones {
+            clone.add_sound(name, sound.clone());
+        }
+        self.sounds.insert(name.to_string(), sound);
+    }
+
+    pub fn remove_sound(&mut self, name: &str) -> bool {
+        for clone in &mut self.clones {
+            clone.remove_sound(name);
+        }
+        if let Some(mut handle) = self.sound_handles.remove(name) {
+            handle.stop(kira::Tween::default());
+        }
+        self.sounds.remove(name).is_some()
+    }
+
+    pub fn set_animations(&mut self, animations: HashMap<String, Animation>) {
+        for clone in &mut self.clones {
+            clone.set_animations(animations.clone());
+        }
+        self.animations = animations;
+    }
+
+    pub fn set_vector_costumes(&mut self, vectors: Vec<(usize, VectorImage)>) {
+        self.vector_costumes = vectors
+            .into_iter()
+            .map(|(index, image)| {
+                let size = image.size().ceil().as_uvec2();
+                (index, (image, size))
+            })
+            .collect();
+    }
+
+    // The largest number of screen pixels per costume pixel that the sprite or any of its clones
+    // shows `costume` at
+    fn largest_zoom(&self, costume: usize, pixels_per_unit: f32) -> Option<Vec2> {
+        let own = (self.visible && self.current_costume == costume).then(|| {
+            let shown = (self.uv[1] - self.uv[0]).abs().max(Vec2::splat(0.001));
+            self.size * self.scale.abs() * pixels_per_unit / shown
+        });
+        self.clones
+            .iter()
+            .filter_map(|clone| clone.largest_zoom(costume, pixels_per_unit))
+            .chain(own)
+            .reduce(Vec2::max)
+    }
+
+    // Rasterises SVG costumes again when they are drawn a lot bigger or smaller than they were
+    // rasterised at, so they stay sharp
+    fn update_vector_costumes(&mut self, window: &dyn WindowBackend, atlas: &mut Atlas) {
+        // The world is twice as big as the window in both directions
+        let pixels_per_unit =
+            window.get_framebuffer_size().0 as f32 / (window.get_size().0 as f32 * 2.0).max(1.0);
+        let mut vectors = std::mem::take(&mut self.vector_costumes);
+        for (index, (image, size)) in &mut vectors {
+            let Some(zoom) = self.largest_zoom(*index, pixels_per_unit) else {
+                continue;
+            };
+            let target = zoom
+                .ceil()
+                .clamp(Vec2::ONE, Vec2::splat(MAX_VECTOR_SIZE))
+                .as_uvec2();
+            let change = target.as_vec2() / size.as_vec2().max(Vec2::ONE);
+            if change.cmpgt(Vec2::splat(0.8)).all() && change.cmplt(Vec2::splat(1.25)).all() {
+                continue;
+            }
+            match image.rasterize(target) {
+                Ok(texture) => {
+                    self.update_costume(*index, &texture, atlas);
+                    *size = target;
+                }
+                Err(e) => console::error(Some(&self.name), e),
+            }
+        }
+        self.vector_costumes = vectors;
+    }
+
+    // Keeps playing when the animation is already playing, unless `restart` is true.
+    // Returns false if there is no animation with that name.
+    pub fn play_animation(&mut self, name: &str, restart: bool) -> bool {
+        let Some(animation) = self.animations.get(name) else {
+            return false;
+        };
+        if !restart && self.animation.as_ref().is_some_and(|a| a.name == name) {
+            return true;
+        }
+        if let Some(first) = animation.frames.first() {
+            self.set_costume(*first);
+        }
+        self.animation = Some(AnimationPlayback::new(name));
+        true
+    }
+
+    // This sprite or one of its clones (at any depth) by name
+    pub fn find(&self, name: &str) -> Option<&Sprite> {
+        if self.name == name {
+            return Some(self);
+        }
+        self.clones.iter().find_map(|clone| clone.find(name))
+    }
+
+    // Applies the sound filters to every sound this sprite is playing
+    pub fn update_sound_handles(&mut self) {
+        for handle in self.sound_handles.values_mut() {
+            for (filter, value) in &self.sound_filters {
+                match filter.as_str() {
+                    "volume" => handle.set_volume(
+                        percentage_to_decibels(*value / 100.0),
+                        kira::Tween::default(),
+                    ),
+                    "pitch" => {
+                        handle.set_playback_rate(*value as f64 / 100.0, kira::Tween::default())
+                    }
+                    "pan" => {
+                        handle.set_panning((*value / 100.0) * 2.0 - 1.0, kira::Tween::default())
+                    }
+                    _ => continue,
+                }
+            }
+        }
+    }
+
     pub fn new_clone(&self) -> Self {
         let name = format!("{} (clone {})", self.name, self.clones.len() + 1);
         let setup_ast = self.clone_setup.clone();
@@ -399,6 +767,11 @@ impl Sprite {
             time_waiting: 0,
             dialogue: None,
             glide: None,
+            tweens: vec![],
+            path: None,
+            animation: self.animation.clone(),
+            vector_costumes: HashMap::new(),
+            pixel_edits: HashMap::new(),
             draw_color: self.draw_color,
             edge_bounce: self.edge_bounce,
             clones: vec![],
@@ -408,6 +781,9 @@ impl Sprite {
             delete_pending: false,
             stop_request: None,
             tags: self.tags.clone(),
+            tests: vec![],
+            body: self.body.as_ref().map(Body::for_clone),
+            animations: self.animations.clone(),
             broadcast_recievers: self.broadcast_recievers.clone(),
             boolean_recievers: self.boolean_recievers.clone(),
             sound_handles: HashMap::new(),
@@ -427,7 +803,7 @@ impl Sprite {
         self.direction = dx.atan2(dy).to_degrees();
     }
 
-    pub fn goto_cursor(&mut self, window: &glfw::Window) {
+    pub fn goto_cursor(&mut self, window: &dyn WindowBackend) {
         let (x, y) = window.get_cursor_pos();
         self.goto(
             x as f32 * 2. - window.get_size().0 as f32,
@@ -435,7 +811,7 @@ impl Sprite {
         );
     }
 
-    pub fn point_cursor(&mut self, window: &glfw::Window) {
+    pub fn point_cursor(&mut self, window: &dyn WindowBackend) {
         let (x, y) = window.get_cursor_pos();
         let (x, y) = (
             x as f32 * 2. - window.get_size().0 as f32,
@@ -446,13 +822,13 @@ impl Sprite {
         self.direction = dx.atan2(dy).to_degrees();
     }
 
-    pub fn move_by(&mut self, step: f32, window: &glfw::Window) {
+    pub fn move_by(&mut self, step: f32, window: &dyn WindowBackend) {
         self.center.x += -step * self.direction.to_radians().cos();
         self.center.y += -step * self.direction.to_radians().sin();
         self.handle_edge_bounce(window);
     }
 
-    pub fn handle_edge_bounce(&mut self, window: &glfw::Window) {
+    pub fn handle_edge_bounce(&mut self, window: &dyn WindowBackend) {
         if self.edge_bounce {
             let screen_width = window.get_size().0 as f32;
             let screen_height = window.get_size().1 as f32;
@@ -469,7 +845,7 @@ impl Sprite {
         if index < self.costumes.len() {
             self.current_costume = index;
         } else {
-            println!("Costume index out of bounds");
+            console::warning(Some(&self.name), "Costume index out of bounds");
         }
     }
 
@@ -497,7 +873,7 @@ impl Sprite {
         if let Some(var) = self.variables.get_mut(name) {
             *var = value;
         } else {
-            println!("Variable '{}' not found", name);
+            console::warning(Some(&self.name), format!("Variable '{}' not found", name));
         }
     }
 
@@ -517,7 +893,7 @@ impl Sprite {
                 "PI" => Value::Number(PI),
                 "E" => Value::Number(E),
                 _ => {
-                    println!("Variable '{}' not found", name);
+                    console::warning(Some(&self.name), format!("Variable '{}' not found", name));
                     Value::Null
                 }
             }
@@ -541,6 +917,26 @@ impl Sprite {
     }
 
     pub fn execute_statement(statement: &Statement, state: &mut State<'_>) {
+        if let Statement::Location { file, line } = statement {
+            if state.project.debugger.is_some() {
+                debugger::on_location(state, file, *line);
+            }
+            return;
+        }
+
+        if !state.project.watchdog.tick() {
+            return;
+        }
+
+        Sprite::run_statement(statement, state);
+        // The next statement is a new stop for the debugger, even when it is on the same line,
+        // like the body of a one-line loop on its next iteration
+        if let Some(debugger) = &mut state.project.debugger {
+            debugger.statement_finished();
+        }
+    }
+
+    fn run_statement(statement: &Statement, state: &mut State<'_>) {
         match statement {
             Statement::Assignment {
                 is_global,
@@ -550,15 +946,59 @@ impl Sprite {
                 let value = crate::utils::resolve_expression(value, state);
                 crate::utils::assign_expression(identifier, value, state, *is_global)
                     .unwrap_or_else(|e| {
-                        println!("Error assigning variable '{}': {}", identifier, e);
+                        let message = format!("Error assigning variable '{}': {}", identifier, e);
+                        console::error(Some(&state.sprite.name), message.clone());
+                        debugger::on_error(state, &message);
                     });
             }
             Statement::Nop => {}
+            Statement::Debugger => debugger::on_debugger_statement(state),
             Statement::Assert { condition } => {
-                if !crate::utils::resolve_expression(condition, state).to_boolean() {
-                    println!("assert {:?}: Failed", condition);
+                // For comparisons both sides are kept around so a failure can say what was
+                // expected and what the script actually got
+                let (passed, expected, actual) = match condition {
+                    Expression::Binary {
+                        left,
+                        operator,
+                        right,
+                    } if matches!(operator.as_str(), "==" | "!=" | "<" | ">" | "<=" | ">=") => {
+                        let left = crate::utils::resolve_expression(left, state);
+                        let right = crate::utils::resolve_expression(right, state);
+                        let passed = binary_operation(&left, operator, &right).to_boolean();
+                        let expected = if operator == "==" {
+                            right.to_string()
+                        } else {
+                            format!("{} {}", operator, right.to_string())
+                        };
+                        (passed, expected, left.to_string())
+                    }
+                    _ => {
+                        let value = crate::utils::resolve_expression(condition, state);
+                        (value.to_boolean(), "true".to_string(), value.to_string())
+                    }
+                };
+                if passed {
+                    if !state.project.testing {
+                        console::info(
+                            Some(&state.sprite.name),
+                            format!("assert {:?}: Passed", condition),
+                        );
+                    }
                 } else {
-                    println!("assert {:?}: Passed", condition);
+                    if !state.project.testing {
+                        console::error(
+                            Some(&state.sprite.name),
+                            format!(
+                                "assert {:?}: Failed (expected {}, got {})",
+                                condition, expected, actual
+                            ),
+                        );
+                    }
+                    state.project.assert_failures.push(AssertFailure {
+                        condition: condition.to_string(),
+                        expected,
+                        actual,
+                    });
                 }
             }
             Statement::Match {
@@ -609,6 +1049,10 @@ impl Sprite {
             }
             Statement::While { condition, body } => {
                 while crate::utils::resolve_expression(condition, state).to_boolean() {
+                    // An empty loop body would never tick the watchdog on its own
+                    if !state.project.watchdog.tick() {
+                        break;
+                    }
                     for statement in body {
                         Sprite::execute_statement(statement, state);
                     }
@@ -620,6 +1064,9 @@ impl Sprite {
                 body,
             } => {
                 for value in crate::utils::resolve_expression(iterable, state).to_list() {
+                    if !state.project.watchdog.tick() {
+                        break;
+                    }
                     let mut new_local_vars = state.local_vars.to_vec();
                     new_local_vars.push((identifier.clone(), value));
                     let mut new_state = State {
@@ -630,7 +1077,6 @@ impl Sprite {
                         snapshots: state.snapshots,
                         window: state.window,
                         input_manager: state.input_manager,
-                        glfw: state.glfw,
                         audio_manager: state.audio_manager,
                         shader_program: state.shader_program,
                         projection: state.projection,
@@ -654,17 +1100,7 @@ impl Sprite {
                         .map(|arg| resolve_expression(arg, state))
                         .collect::<Vec<_>>();
 
-                    match func_val {
-                        Value::Closure(callable) => {
-                            let _ = callable.call(state, &args).unwrap_or_else(|e| {
-                                println!("Error calling function: {}", e);
-                                Value::Null
-                            });
-                        }
-                        _ => {
-                            println!("Attempted to call non-function: {:?}", func_val);
-                        }
-                    }
+                    call_value(function, &func_val, &args, state);
                 }
             }
             _ => {}
@@ -699,6 +1135,69 @@ impl Sprite {
         0
     }
 
+    pub fn script_name(&self, script_id: usize) -> String {
+        if script_id == TWEEN_CALLBACK {
+            return "tween callback".to_string();
+        }
+        if script_id == PATH_CALLBACK {
+            return "path callback".to_string();
+        }
+        let update_len = self.update_ast.len();
+        let broadcast_len = self.broadcast_recievers.len();
+        if script_id == 0 {
+            "setup".to_string()
+        } else if script_id <= update_len {
+            format!("update #{}", script_id)
+        } else if script_id <= update_len + broadcast_len {
+            let index = script_id - update_len - 1;
+            let name = self.broadcast_recievers.keys().nth(index).cloned();
+            format!("when \"{}\"", name.unwrap_or_default())
+        } else {
+            let index = script_id - update_len - broadcast_len - 1;
+            match self.boolean_recievers.get(index) {
+                Some((condition, _, _)) => format!("when {}", condition),
+                None => format!("script #{}", script_id),
+            }
+        }
+    }
+
+    // Returns true if the script was suspended, so it has to run again on the next frame instead
+    // of being marked as done
+    fn handle_watchdog(&mut self, project: &mut Project, script_id: usize) -> bool {
+        let Some(reason) = project.watchdog.take_error() else {
+            return false;
+        };
+        let script = self.script_name(script_id);
+        match project.watchdog.action {
+            // Callbacks only run once, so there is nothing to resume
+            LimitAction::Suspend if script_id == TWEEN_CALLBACK || script_id == PATH_CALLBACK => {
+                console::warning(
+                    Some(&self.name),
+                    format!("Watchdog: script '{}' {}, stopping it", script, reason),
+                );
+                false
+            }
+            LimitAction::Suspend => {
+                console::warning(
+                    Some(&self.name),
+                    format!(
+                        "Watchdog: script '{}' {}, suspending it until the next frame",
+                        script, reason
+                    ),
+                );
+                true
+            }
+            LimitAction::Stop => {
+                console::error(
+                    Some(&self.name),
+                    format!("Watchdog: script '{}' {}, stopping it", script, reason),
+                );
+                self.stop_script(script_id);
+                false
+            }
+        }
+    }
+
     pub fn stop_script(&mut self, script_id: usize) {
         if script_id == 0 {
             self.setup_ast.clear();
@@ -780,30 +1279,168 @@ impl Sprite {
         }
     }
 
+    // Broadcasts this sprite (or one of its clones) listens to that were sent but not handled yet
+    pub fn pending_broadcasts(&self, project: &Project) -> usize {
+        let own = self
+            .broadcast_recievers
+            .keys()
+            .filter_map(|message| project.get_broadcast(message))
+            .filter(|broadcast| !self.completed_broadcasts.contains(&broadcast.id))
+            .count();
+        own + self
+            .clones
+            .iter()
+            .map(|clone| clone.pending_broadcasts(project))
+            .sum::<usize>()
+    }
+
     pub fn stop_self(&mut self) {
         self.setup_ast.clear();
         self.update_ast.clear();
         self.broadcast_recievers.clear();
         self.boolean_recievers.clear();
+        self.tweens.clear();
+        self.path = None;
+        self.animation = None;
         self.clones.iter_mut().for_each(|clone| {
             clone.stop_self();
         });
     }
 
+    // Moves every tween along and returns the callbacks of the ones that finished
+    fn step_tweens(&mut self, dt: f32, project: &mut Project) -> Vec<(usize, Value, Vec<Value>)> {
+        let mut tweens = std::mem::take(&mut self.tweens);
+        for tween in &mut tweens {
+            let value = tween.advance(dt);
+            tween.property.set(self, project, value);
+        }
+        let (finished, running): (Vec<_>, Vec<_>) =
+            tweens.into_iter().partition(PropertyTween::finished);
+        self.tweens = running;
+        finished
+            .into_iter()
+            .filter_map(|t| {
+                let args = vec![Value::Number(t.id as f32)];
+                Some((TWEEN_CALLBACK, t.on_complete?, args))
+            })
+            .collect()
+    }
+
+    // Moves the sprite along the path it is following and returns the callback once it ends
+    fn step_path(&mut self, dt: f32) -> Option<(usize, Value, Vec<Value>)> {
+        let follow = self.path.as_mut()?;
+        let (position, tangent) = follow.advance(dt);
+        self.center = position;
+        if follow.orient && tangent != Vec2::ZERO {
+            self.direction = tangent.y.atan2(tangent.x).to_degrees();
+        }
+        if !follow.finished() {
+            return None;
+        }
+        let callback = self.path.take()?.on_complete?;
+        Some((PATH_CALLBACK, callback, vec![]))
+    }
+
+    // Shows the costume the playing animation is at
+    fn step_animation(&mut self, dt: f32) {
+        let Some(playback) = &mut self.animation else {
+            return;
+        };
+        let Some(animation) = self.animations.get(&playback.name) else {
+            self.animation = None;
+            return;
+        };
+        if let Some(costume) = playback.advance(animation, dt)
+            && costume < self.costumes.len()
+        {
+            self.current_costume = costume;
+        }
+    }
+
+    // Runs tween and path callbacks, each as the script id of its kind. They run after their
+    // tween or path is gone, so they can start new ones.
+    fn run_callbacks(
+        &mut self,
+        callbacks: Vec<(usize, Value, Vec<Value>)>,
+        start: std::time::Instant,
+        dt: f32,
+        project: &mut Project,
+        snapshots: &Snapshots,
+        window: &mut dyn WindowBackend,
+        input_manager: &mut InputManager,
+        audio_manager: &mut dyn AudioBackend,
+        shader_program: &ShaderProgram,
+        projection: &mut Mat4,
+        font: &BitmapFont,
+    ) {
+        for (script_id, callback, args) in callbacks {
+            let Value::Closure(callback) = callback else {
+                continue;
+            };
+            let label = self.script_name(script_id);
+            project.begin_script();
+            project.profile_begin("script", || format!("{}: {}", self.name, label));
+            let result = callback.call(
+                &mut State {
+                    start,
+                    dt,
+                    sprite: self,
+                    project,
+                    snapshots,
+                    window,
+                    input_manager,
+                    audio_manager,
+                    shader_program,
+                    projection,
+                    font,
+                    local_vars: &mut [],
+                    script_id,
+                },
+                &args,
+            );
+            project.profile_end();
+            if let Err(e) = result {
+                console::error(Some(&self.name), format!("Error in {}: {}", label, e));
+            }
+            self.handle_watchdog(project, script_id);
+        }
+    }
+
     pub fn step(
         &mut self,
         start: std::time::Instant,
         dt: f32,
         project: &mut Project,
-        snapshots: &[SpriteSnapshot],
-        window: &mut glfw::Window,
+        snapshots: &Snapshots,
+        window: &mut dyn WindowBackend,
         input_manager: &mut InputManager,
-        glfw: &mut glfw::Glfw,
-        audio_manager: &mut AudioManager<DefaultBackend>,
+        audio_manager: &mut dyn AudioBackend,
         shader_program: &ShaderProgram,
         projection: &mut Mat4,
         font: &BitmapFont,
     ) {
+        self.step_animation(dt);
+        if !self.vector_costumes.is_empty() {
+            self.update_vector_costumes(window, project.assets.atlas());
+        }
+        let mut callbacks = self.step_tweens(dt, project);
+        callbacks.extend(self.step_path(dt));
+        if !callbacks.is_empty() {
+            self.run_callbacks(
+                callbacks,
+                start,
+                dt,
+                project,
+                snapshots,
+                window,
+                input_manager,
+                audio_manager,
+                shader_program,
+                projection,
+                font,
+            );
+        }
+
         if let Some(glide) = &mut self.glide {
             let t = 1.0 - (glide.remaining as f32 / glide.duration as f32);
             if glide.remaining > 0 {
@@ -823,6 +1460,8 @@ impl Sprite {
         }
 
         if !self.setup_finished {
+            project.begin_script();
+            project.profile_begin("script", || format!("{}: setup", self.name));
             for statement in self.setup_ast.clone() {
                 if self.time_waiting > 0 {
                     self.time_waiting -= 1;
@@ -846,7 +1485,6 @@ impl Sprite {
                         snapshots,
                         window,
                         input_manager,
-                        glfw,
                         audio_manager,
                         shader_program,
                         projection,
@@ -855,14 +1493,25 @@ impl Sprite {
                         script_id: 0,
                     },
                 );
+                if project.watchdog.tripped() {
+                    break;
+                }
                 if self.skip_further_execution_of_frame {
                     self.skip_further_execution_of_frame = false;
                     break;
                 }
             }
-            self.setup_finished = true;
+            project.profile_end();
+            // A suspended setup starts over on the next frame
+            if !self.handle_watchdog(project, 0) {
+                self.setup_finished = true;
+            }
         } else {
-            for ast in self.update_ast.clone() {
+            for (index, ast) in self.update_ast.clone().into_iter().enumerate() {
+                project.begin_script();
+                project.profile_begin("script", || {
+                    format!("{}: {}", self.name, self.script_name(index + 1))
+                });
                 for (i, statement) in ast.iter().enumerate() {
                     if self.time_waiting > 0 {
                         self.time_waiting -= 1;
@@ -886,7 +1535,6 @@ impl Sprite {
                             snapshots,
                             window,
                             input_manager,
-                            glfw,
                             audio_manager,
                             shader_program,
                             projection,
@@ -895,11 +1543,16 @@ impl Sprite {
                             script_id: i + 1,
                         },
                     );
+                    if project.watchdog.tripped() {
+                        break;
+                    }
                     if self.skip_further_execution_of_frame {
                         self.skip_further_execution_of_frame = false;
                         break;
                     }
                 }
+                project.profile_end();
+                self.handle_watchdog(project, index + 1);
             }
         }
 
@@ -908,6 +1561,14 @@ impl Sprite {
                 if self.completed_broadcasts.contains(&broadcasted.id) {
                     continue;
                 }
+                project.begin_script();
+                project.profile_begin("script", || {
+                    format!(
+                        "{}: {}",
+                        self.name,
+                        self.script_name(i + self.update_ast.len() + 1)
+                    )
+                });
                 for statement in body {
                     if self.time_waiting > 0 {
                         self.time_waiting -= 1;
@@ -932,7 +1593,6 @@ impl Sprite {
                             snapshots,
                             window,
                             input_manager,
-                            glfw,
                             audio_manager,
                             shader_program,
                             projection,
@@ -941,12 +1601,19 @@ impl Sprite {
                             script_id: i + update_ast_len + 1,
                         },
                     );
+                    if project.watchdog.tripped() {
+                        break;
+                    }
                     if self.skip_further_execution_of_frame {
                         self.skip_further_execution_of_frame = false;
                         break;
                     }
                 }
-                self.completed_broadcasts.push(broadcasted.id);
+                project.profile_end();
+                // A suspended receiver runs again on the next frame
+                if !self.handle_watchdog(project, i + self.update_ast.len() + 1) {
+                    self.completed_broadcasts.push(broadcasted.id);
+                }
             }
         }
 
@@ -955,6 +1622,8 @@ impl Sprite {
         let mut called_s = vec![];
         for (i, (expr, body, _)) in self.boolean_recievers.clone().iter().enumerate() {
             let update_broadcast_len = self.update_ast.len() + self.broadcast_recievers.len();
+            project.begin_script();
+            project.profile_begin("poll", || format!("{}: when {}", self.name, expr));
             let value = crate::utils::resolve_expression(
                 &expr,
                 &mut State {
@@ -965,7 +1634,6 @@ impl Sprite {
                     snapshots,
                     window,
                     input_manager,
-                    glfw,
                     audio_manager,
                     shader_program,
                     projection,
@@ -974,7 +1642,10 @@ impl Sprite {
                     script_id: i + update_broadcast_len + 1,
                 },
             );
-            if value.to_boolean() {
+            project.profile_end();
+            let called = value.to_boolean();
+            if called {
+                project.profile_begin("script", || format!("{}: when {}", self.name, expr));
                 for statement in body {
                     if self.time_waiting > 0 {
                         self.time_waiting -= 1;
@@ -1000,7 +1671,6 @@ impl Sprite {
                             snapshots,
                             window,
                             input_manager,
-                            glfw,
                             audio_manager,
                             shader_program,
                             projection,
@@ -1009,11 +1679,18 @@ impl Sprite {
                             script_id: i + update_broadcast_len + 1,
                         },
                     );
+                    if project.watchdog.tripped() {
+                        break;
+                    }
                     if self.skip_further_execution_of_frame {
                         self.skip_further_execution_of_frame = false;
                         break;
                     }
                 }
+                project.profile_end();
+            }
+            // A suspended receiver is checked again on the next frame
+            if !self.handle_watchdog(project, i + update_broadcast_len + 1) && called {
                 called_s.push(i);
             }
         }
@@ -1071,7 +1748,6 @@ impl Sprite {
                 snapshots,
                 window,
                 input_manager,
-                glfw,
                 audio_manager,
                 shader_program,
                 projection,
@@ -0,0 +1,56 @@
+use glam::*;
+
+use crate::utils::{RotationStyle, Sprite};
+
+// Where a sprite is on the screen, how big it is, the angle it's drawn at and whether it's
+// mirrored, according to its rotation style. Drawing, collisions, rigid bodies and the debug
+// overlays all go through this, so a sprite touches exactly what is on the screen.
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteTransform {
+    pub center: Vec2,
+    pub size: Vec2,
+    pub angle: f32,
+    pub flip: bool,
+}
+
+impl SpriteTransform {
+    pub fn new(sprite: &Sprite) -> Self {
+        let (angle, flip) = match sprite.rotation_style {
+            RotationStyle::AllAround => (sprite.direction.to_radians(), false),
+            RotationStyle::LeftRight => (0.0, sprite.direction.to_radians().cos() < 0.0),
+            RotationStyle::DontRotate => (0.0, false),
+        };
+        Self {
+            center: sprite.center,
+            size: sprite.size * sprite.scale,
+            angle,
+            flip,
+        }
+    }
+
+    // Maps the sprite's rectangle (spanning -0.5..0.5) to the screen
+    pub fn affine(&self) -> Affine2 {
+        Affine2::from_scale_angle_translation(self.size, self.angle, self.center)
+    }
+
+    // Mirrored by swapping the texture coordinates, since a negative scale would turn the quad
+    // around and get it culled
+    pub fn uv(&self, uv: [Vec2; 2]) -> [Vec2; 2] {
+        if self.flip {
+            [vec2(uv[1].x, uv[0].y), vec2(uv[0].x, uv[1].y)]
+        } else {
+            uv
+        }
+    }
+
+    pub fn corners(&self) -> [Vec2; 4] {
+        let affine = self.affine();
+        [
+            vec2(-0.5, -0.5),
+            vec2(0.5, -0.5),
+            vec2(0.5, 0.5),
+            vec2(-0.5, 0.5),
+        ]
+        .map(|corner| affine.transform_point2(corner))
+    }
+}
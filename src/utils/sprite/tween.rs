@@ -0,0 +1,288 @@
+use glam::*;
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::utils::{Project, Sprite, Value, cubic_bezier};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Curve {
+    Quad,
+    Cubic,
+    Quart,
+    Quint,
+    Sine,
+    Expo,
+    Circ,
+    Back,
+    Elastic,
+    Bounce,
+}
+
+impl Curve {
+    // The "in" version of the curve, the others are built from it
+    fn ease_in(self, t: f32) -> f32 {
+        match self {
+            Self::Quad => t * t,
+            Self::Cubic => t.powi(3),
+            Self::Quart => t.powi(4),
+            Self::Quint => t.powi(5),
+            Self::Sine => 1.0 - (t * PI / 2.0).cos(),
+            Self::Expo if t <= 0.0 => 0.0,
+            Self::Expo => 2f32.powf(10.0 * t - 10.0),
+            Self::Circ => 1.0 - (1.0 - t * t).max(0.0).sqrt(),
+            Self::Back => {
+                let c1 = 1.70158;
+                (c1 + 1.0) * t.powi(3) - c1 * t * t
+            }
+            Self::Elastic if t <= 0.0 || t >= 1.0 => t,
+            Self::Elastic => {
+                -2f32.powf(10.0 * t - 10.0) * ((10.0 * t - 10.75) * (2.0 * PI / 3.0)).sin()
+            }
+            Self::Bounce => 1.0 - bounce_out(1.0 - t),
+        }
+    }
+}
+
+fn bounce_out(t: f32) -> f32 {
+    let (n, d) = (7.5625, 2.75);
+    if t < 1.0 / d {
+        n * t * t
+    } else if t < 2.0 / d {
+        let t = t - 1.5 / d;
+        n * t * t + 0.75
+    } else if t < 2.5 / d {
+        let t = t - 2.25 / d;
+        n * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / d;
+        n * t * t + 0.984375
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    In(Curve),
+    Out(Curve),
+    InOut(Curve),
+    // The two control points of a CSS-style cubic bezier from (0, 0) to (1, 1)
+    Bezier(Vec2, Vec2),
+}
+
+impl Easing {
+    pub fn parse(value: &Value) -> Result<Self, String> {
+        let name = match value {
+            Value::String(name) => name.to_lowercase(),
+            Value::List(list) => {
+                return match list.as_slice() {
+                    [
+                        Value::Number(x1),
+                        Value::Number(y1),
+                        Value::Number(x2),
+                        Value::Number(y2),
+                    ] => Ok(Self::Bezier(
+                        vec2(x1.clamp(0.0, 1.0), *y1),
+                        vec2(x2.clamp(0.0, 1.0), *y2),
+                    )),
+                    _ => Err(
+                        "A bezier easing must be a list of four numbers: x1, y1, x2, y2"
+                            .to_string(),
+                    ),
+                };
+            }
+            _ => return Err("An easing must be a name or a list of four numbers".to_string()),
+        };
+        // The same curves as `glide()`
+        match name.as_str() {
+            "linear" => return Ok(Self::Linear),
+            "ease" => return Ok(Self::Bezier(vec2(0.25, 0.1), vec2(0.25, 1.0))),
+            "ease-in" => return Ok(Self::Bezier(vec2(0.42, 0.0), vec2(1.0, 1.0))),
+            "ease-out" => return Ok(Self::Bezier(vec2(0.0, 0.0), vec2(0.58, 1.0))),
+            "ease-in-out" => return Ok(Self::Bezier(vec2(0.42, 0.0), vec2(0.58, 1.0))),
+            _ => {}
+        }
+        let unknown = || format!("Unknown easing '{}'", name);
+        let (mode, curve) = name
+            .strip_prefix("ease-in-out-")
+            .map(|curve| (Self::InOut as fn(Curve) -> Self, curve))
+            .or_else(|| name.strip_prefix("ease-in-").map(|c| (Self::In as _, c)))
+            .or_else(|| name.strip_prefix("ease-out-").map(|c| (Self::Out as _, c)))
+            .ok_or_else(unknown)?;
+        let curve = match curve {
+            "quad" => Curve::Quad,
+            "cubic" => Curve::Cubic,
+            "quart" => Curve::Quart,
+            "quint" => Curve::Quint,
+            "sine" => Curve::Sine,
+            "expo" => Curve::Expo,
+            "circ" => Curve::Circ,
+            "back" => Curve::Back,
+            "elastic" => Curve::Elastic,
+            "bounce" => Curve::Bounce,
+            _ => return Err(unknown()),
+        };
+        Ok(mode(curve))
+    }
+
+    // How far along the value is at `t`, both from 0 to 1. Back and elastic go past both ends.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => t,
+            Self::In(curve) => curve.ease_in(t),
+            Self::Out(curve) => 1.0 - curve.ease_in(1.0 - t),
+            Self::InOut(curve) if t < 0.5 => curve.ease_in(t * 2.0) / 2.0,
+            Self::InOut(curve) => 1.0 - curve.ease_in(2.0 - t * 2.0) / 2.0,
+            Self::Bezier(a, b) => {
+                // Finds where the curve is at `t` horizontally, then reads its height there
+                let (mut low, mut high) = (0.0, 1.0);
+                let mut s = t;
+                for _ in 0..32 {
+                    let x = cubic_bezier(s, 0.0, a.x, b.x, 1.0);
+                    if (x - t).abs() < 1e-5 {
+                        break;
+                    }
+                    if x < t {
+                        low = s;
+                    } else {
+                        high = s;
+                    }
+                    s = (low + high) / 2.0;
+                }
+                cubic_bezier(s, 0.0, a.y, b.y, 1.0)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TweenProperty {
+    X,
+    Y,
+    Scale,
+    Direction,
+    Effect(String),
+    SoundFilter(String),
+    // The draw color channel, 0 to 3
+    Color(usize),
+    Variable(String),
+}
+
+impl TweenProperty {
+    pub fn parse(property: &str) -> Result<Self, String> {
+        match property {
+            "x" => Ok(Self::X),
+            "y" => Ok(Self::Y),
+            "scale" => Ok(Self::Scale),
+            "direction" => Ok(Self::Direction),
+            "color.r" => Ok(Self::Color(0)),
+            "color.g" => Ok(Self::Color(1)),
+            "color.b" => Ok(Self::Color(2)),
+            "color.a" => Ok(Self::Color(3)),
+            _ => {
+                if let Some(effect) = property.strip_prefix("effect.") {
+                    Ok(Self::Effect(effect.to_string()))
+                } else if let Some(filter) = property.strip_prefix("sound_filter.") {
+                    Ok(Self::SoundFilter(filter.to_string()))
+                } else if let Some(variable) = property.strip_prefix("var.") {
+                    Ok(Self::Variable(variable.to_string()))
+                } else {
+                    Err(format!("Can't tween '{}'", property))
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, sprite: &Sprite, project: &Project) -> f32 {
+        match self {
+            Self::X => sprite.center.x,
+            Self::Y => sprite.center.y,
+            Self::Scale => sprite.scale,
+            Self::Direction => sprite.direction,
+            Self::Effect(effect) => sprite.effects.get(effect).copied().unwrap_or(0.0),
+            Self::SoundFilter(filter) => sprite
+                .sound_filters
+                .get(filter)
+                .copied()
+                .unwrap_or(if filter == "pan" { 50.0 } else { 100.0 }),
+            Self::Color(channel) => sprite.draw_color[*channel] * 255.0,
+            Self::Variable(name) => sprite
+                .variables
+                .get(name)
+                .or_else(|| project.global_variables.get(name))
+                .map_or(0.0, Value::to_number),
+        }
+    }
+
+    // Variables are set on the sprite unless only a global one with that name exists
+    pub fn set(&self, sprite: &mut Sprite, project: &mut Project, value: f32) {
+        match self {
+            Self::X => sprite.center.x = value,
+            Self::Y => sprite.center.y = value,
+            Self::Scale => sprite.scale = value,
+            Self::Direction => sprite.direction = value,
+            Self::Effect(effect) => {
+                sprite.effects.insert(effect.clone(), value);
+            }
+            Self::SoundFilter(filter) => {
+                sprite.sound_filters.insert(filter.clone(), value);
+                sprite.update_sound_handles();
+            }
+            Self::Color(channel) => sprite.draw_color[*channel] = (value / 255.0).clamp(0.0, 1.0),
+            Self::Variable(name) => {
+                let variable = match project.global_variables.get_mut(name) {
+                    Some(global) if !sprite.variables.contains_key(name) => global,
+                    _ => sprite.variables.entry(name.clone()).or_insert(Value::Null),
+                };
+                *variable = Value::Number(value);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PropertyTween {
+    pub id: usize,
+    pub property: TweenProperty,
+    pub from: f32,
+    pub to: f32,
+    pub duration: f32,
+    pub elapsed: f32,
+    pub easing: Easing,
+    pub on_complete: Option<Value>,
+}
+
+impl PropertyTween {
+    pub fn new(property: TweenProperty, from: f32, to: f32, duration: f32, easing: Easing) -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            property,
+            from,
+            to,
+            duration,
+            elapsed: 0.0,
+            easing,
+            on_complete: None,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    // Moves the tween along by `dt` and returns the value the property has now
+    pub fn advance(&mut self, dt: f32) -> f32 {
+        self.elapsed += dt;
+        let t = if self.duration > 0.0 {
+            self.elapsed / self.duration
+        } else {
+            1.0
+        };
+        if t >= 1.0 {
+            return self.to;
+        }
+        self.from + (self.to - self.from) * self.easing.apply(t)
+    }
+}
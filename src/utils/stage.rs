@@ -1,17 +1,84 @@
 use crate::utils::core::*;
+use crate::utils::{Animation, AnimationPlayback, Costume, WindowBackend};
 use glam::*;
-use glfw::Window;
+
+// The backdrop and stamp quads, which only change along with the window or the backdrop's size
+struct StageQuads {
+    backdrop_size: Vec2,
+    // Where the backdrop is on its atlas page
+    backdrop_region: Vec4,
+    screen_size: Vec2,
+    backdrop: Mesh<Vertex>,
+    stamps: Mesh<Vertex>,
+}
+
+impl StageQuads {
+    fn new(size: Vec2, region: Vec4, screen: Vec2) -> Self {
+        let (sw, sh) = (screen.x, screen.y);
+        let uv = |u: f32, v: f32| region.xy() + vec2(u, v) * region.zw();
+        let x = -size.x / 2.0;
+        let y = -size.y / 2.0;
+        let backdrop_quad = [
+            Vertex {
+                position: vec2(x, y),
+                uv: uv(0.0, 1.0),
+            }, // Top-left
+            Vertex {
+                position: vec2(x + size.x, y),
+                uv: uv(1.0, 1.0),
+            }, // Top-right
+            Vertex {
+                position: vec2(x + size.x, y + size.y),
+                uv: uv(1.0, 0.0),
+            }, // Bottom-right
+            Vertex {
+                position: vec2(x, y + size.y),
+                uv: uv(0.0, 0.0),
+            }, // Bottom-left
+        ];
+        let stamp_quad = [
+            Vertex {
+                position: vec2(-sw, -sh),
+                uv: vec2(0.0, 0.0),
+            }, // Bottom-left
+            Vertex {
+                position: vec2(sw, -sh),
+                uv: vec2(1.0, 0.0),
+            }, // Bottom-right
+            Vertex {
+                position: vec2(sw, sh),
+                uv: vec2(1.0, 1.0),
+            }, // Top-right
+            Vertex {
+                position: vec2(-sw, sh),
+                uv: vec2(0.0, 1.0),
+            }, // Top-left
+        ];
+        let indices = [0, 1, 2, 0, 2, 3];
+        Self {
+            backdrop_size: size,
+            backdrop_region: region,
+            screen_size: screen,
+            backdrop: Mesh::new(&backdrop_quad, &indices, DrawMode::Triangles),
+            stamps: Mesh::new(&stamp_quad, &indices, DrawMode::Triangles),
+        }
+    }
+}
 
 pub struct Stage {
-    pub backdrops: Vec<GPUTexture>,
+    pub backdrops: Vec<Costume>,
     pub stamp_buffer: Framebuffer,
+    // The frames of animated backdrops, which play on their own while they are shown
+    pub animations: Vec<Animation>,
+    playback: Option<(usize, AnimationPlayback)>,
+    quads: Option<StageQuads>,
     current_backdrop: usize,
     last_screen_width: i32,
     last_screen_height: i32,
 }
 
 impl Stage {
-    pub fn new(backdrops: Vec<GPUTexture>, window: &Window) -> Self {
+    pub fn new(backdrops: Vec<Costume>, window: &dyn WindowBackend) -> Self {
         Self {
             backdrops,
             stamp_buffer: Framebuffer::new(
@@ -19,18 +86,78 @@ impl Stage {
                 window.get_size().1 as u32,
                 false,
             ),
+            animations: vec![],
+            playback: None,
+            quads: None,
             current_backdrop: 0,
             last_screen_width: 0,
             last_screen_height: 0,
         }
     }
 
+    pub fn add_backdrop(&mut self, backdrop: Costume) {
+        self.backdrops.push(backdrop);
+    }
+
+    pub fn replace_backdrop(&mut self, index: usize, backdrop: Costume) {
+        if let Some(old) = self.backdrops.get_mut(index) {
+            *old = backdrop;
+        }
+    }
+
+    // Removes a backdrop. The backdrops after it move down by one, along with the animations that
+    // show them.
+    pub fn remove_backdrop(&mut self, index: usize) {
+        if index >= self.backdrops.len() {
+            return;
+        }
+        self.backdrops.remove(index);
+        if self.current_backdrop > index || self.current_backdrop == self.backdrops.len() {
+            self.current_backdrop = self.current_backdrop.saturating_sub(1);
+        }
+        for animation in &mut self.animations {
+            animation.remove_costume(index);
+        }
+        self.animations.retain(|a| !a.frames.is_empty());
+        self.playback = None;
+    }
+
+    // The number of the first backdrop with that name
+    pub fn backdrop_index(&self, name: &str) -> Option<usize> {
+        self.backdrops.iter().position(|b| b.name == name)
+    }
+
+    // The color of the backdrop at a point in world coordinates (stamps are not included).
+    // Outside of the backdrop, the screen is cleared to black.
+    pub fn color_at(&self, point: Vec2, window: &dyn WindowBackend) -> U8Vec4 {
+        let Some(backdrop) = self.backdrops.get(self.current_backdrop) else {
+            return U8Vec4::new(0, 0, 0, 255);
+        };
+        let size = self.backdrop_size(window);
+        let uv = (point + size / 2.0) / size;
+        if uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0 {
+            return U8Vec4::new(0, 0, 0, 255);
+        }
+        backdrop.mask.sample(vec2(uv.x, 1.0 - uv.y))
+    }
+
+    // The backdrop is scaled to fit the window while keeping its aspect ratio
+    fn backdrop_size(&self, window: &dyn WindowBackend) -> Vec2 {
+        let texture = &self.backdrops[self.current_backdrop].texture;
+        let tw = texture.width() as f32;
+        let th = texture.height() as f32;
+        let sw = window.get_size().0 as f32;
+        let sh = window.get_size().1 as f32;
+        if tw / th > sw / sh {
+            vec2(sw, th * (sw / tw)) * 2.0
+        } else {
+            vec2(tw * (sh / th), sh) * 2.0
+        }
+    }
+
     pub fn clear_stamps(&self) {
         self.stamp_buffer.bind();
-        unsafe {
-            gl::ClearColor(0.0, 0.0, 0.0, 0.0);
-            gl::Clear(gl::COLOR_BUFFER_BIT);
-        }
+        with_renderer(|r| r.clear(Vec4::ZERO));
         Framebuffer::unbind();
     }
 
@@ -56,75 +183,66 @@ impl Stage {
         self.current_backdrop
     }
 
-    pub fn draw(&mut self, window: &Window, shader_program: &ShaderProgram, projection: &Mat4) {
+    // Moves an animated backdrop along. Switching to any of its frames plays it from there.
+    pub fn step(&mut self, dt: f32) {
+        let current = self.current_backdrop;
+        let Some(index) = self
+            .animations
+            .iter()
+            .position(|a| a.frames.contains(&current))
+        else {
+            self.playback = None;
+            return;
+        };
+        let animation = &self.animations[index];
+        let playing = self
+            .playback
+            .as_ref()
+            .is_some_and(|(i, p)| *i == index && animation.frames.get(p.frame) == Some(&current));
+        if !playing {
+            let mut playback = AnimationPlayback::new("");
+            playback.frame = animation
+                .frames
+                .iter()
+                .position(|f| *f == current)
+                .unwrap_or(0);
+            self.playback = Some((index, playback));
+        }
+        if let Some((_, playback)) = &mut self.playback
+            && let Some(backdrop) = playback.advance(animation, dt)
+        {
+            self.current_backdrop = backdrop;
+        }
+    }
+
+    pub fn draw(
+        &mut self,
+        window: &dyn WindowBackend,
+        shader_program: &ShaderProgram,
+        projection: &Mat4,
+    ) {
         let sw = window.get_size().0;
         let sh = window.get_size().1;
 
         if sw != self.last_screen_width || sh != self.last_screen_height {
-            unsafe {
-                gl::Viewport(0, 0, sw, sh);
-            }
+            with_renderer(|r| r.set_viewport(sw, sh));
             self.last_screen_width = sw;
             self.last_screen_height = sh;
             self.stamp_buffer = Framebuffer::new(sw as u32, sh as u32, false);
         }
 
-        let texture = &self.backdrops[self.current_backdrop];
-        let tw = texture.width() as f32;
-        let th = texture.height() as f32;
-        let sw = sw as f32;
-        let sh = sh as f32;
-        let size = if tw / th > sw / sh {
-            vec2(sw, th * (sw / tw)) * 2.0
-        } else {
-            vec2(tw * (sh / th), sh) * 2.0
-        };
-        let x = -size.x / 2.0;
-        let y = -size.y / 2.0;
-        let backdrop_quad = [
-            Vertex {
-                position: vec2(x, y),
-                uv: vec2(0.0, 1.0),
-            }, // Top-left
-            Vertex {
-                position: vec2(x + size.x, y),
-                uv: vec2(1.0, 1.0),
-            }, // Top-right
-            Vertex {
-                position: vec2(x + size.x, y + size.y),
-                uv: vec2(1.0, 0.0),
-            }, // Bottom-right
-            Vertex {
-                position: vec2(x, y + size.y),
-                uv: vec2(0.0, 0.0),
-            }, // Bottom-left
-        ];
-        let stamp_quad = [
-            Vertex {
-                position: vec2(-sw, -sh),
-                uv: vec2(0.0, 0.0),
-            }, // Bottom-left
-            Vertex {
-                position: vec2(sw, -sh),
-                uv: vec2(1.0, 0.0),
-            }, // Bottom-right
-            Vertex {
-                position: vec2(sw, sh),
-                uv: vec2(1.0, 1.0),
-            }, // Top-right
-            Vertex {
-                position: vec2(-sw, sh),
-                uv: vec2(0.0, 1.0),
-            }, // Top-left
-        ];
-        let indices = [0, 1, 2, 0, 2, 3];
-        let backdrop_mesh = Mesh::new(&backdrop_quad, &indices, DrawMode::Triangles);
-        let stamp_mesh = Mesh::new(&stamp_quad, &indices, DrawMode::Triangles);
-
-        unsafe {
-            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
-            gl::Clear(gl::COLOR_BUFFER_BIT);
+        let size = self.backdrop_size(window);
+        let region = self.backdrops[self.current_backdrop].texture.rect();
+        let screen = vec2(sw as f32, sh as f32);
+        if self.quads.as_ref().is_none_or(|q| {
+            q.backdrop_size != size || q.backdrop_region != region || q.screen_size != screen
+        }) {
+            self.quads = Some(StageQuads::new(size, region, screen));
         }
+        let texture = &self.backdrops[self.current_backdrop].texture;
+        let quads = self.quads.as_ref().unwrap();
+
+        with_renderer(|r| r.clear(vec4(0.0, 0.0, 0.0, 1.0)));
 
         shader_program.use_program();
         shader_program.set_uniform("u_color", vec4(1.0, 1.0, 1.0, 1.0));
@@ -134,7 +252,7 @@ impl Stage {
         shader_program.set_uniform("u_effect_values", &[] as &[f32]);
         shader_program.set_uniform("u_effects_count", 0);
         texture.bind();
-        backdrop_mesh.draw();
+        quads.backdrop.draw();
         shader_program.set_uniform("u_color", vec4(1.0, 1.0, 1.0, 1.0));
         shader_program.set_uniform("u_projection", *projection);
         shader_program.set_uniform("u_model", Mat4::IDENTITY);
@@ -142,6 +260,6 @@ impl Stage {
         shader_program.set_uniform("u_effect_values", &[] as &[f32]);
         shader_program.set_uniform("u_effects_count", 0);
         self.stamp_buffer.texture().bind();
-        stamp_mesh.draw();
+        quads.stamps.draw();
     }
 }
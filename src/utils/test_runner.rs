@@ -0,0 +1,413 @@
+use glam::*;
+use kira::backend::mock::MockBackend;
+use kira::{AudioManager, AudioManagerSettings};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::utils::core::ShaderProgram;
+use crate::utils::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum TestFormat {
+    Pretty,
+    Tap,
+    Junit,
+}
+
+struct TestCase {
+    sprite: String,
+    name: String,
+    body: Vec<Statement>,
+    // Functions defined in a `*_test.crst` file, added to the sprite under test
+    functions: HashMap<String, Callable>,
+}
+
+struct TestResult {
+    case: TestCase,
+    failures: Vec<AssertFailure>,
+    error: Option<String>,
+    // Errors the scripts ran into, like calling a function that doesn't exist
+    script_errors: Vec<String>,
+    time: Duration,
+}
+
+impl TestResult {
+    fn passed(&self) -> bool {
+        self.failures.is_empty() && self.error.is_none() && self.script_errors.is_empty()
+    }
+}
+
+// Runs every `test "name" { ... }` block of the project and returns the process exit code.
+// Each test gets a freshly loaded project, so nothing leaks from one test into the next. The
+// sprite under test runs its `setup` script first, then the body of the test. Any error logged
+// while they run fails the test, even without a failed assert.
+pub fn run_tests(
+    project_file: &str,
+    format: TestFormat,
+    filter: Option<&str>,
+    output: Option<&str>,
+    shader_program: &ShaderProgram,
+) -> i32 {
+    let cases = discover_tests(project_file)
+        .into_iter()
+        .filter(|case| filter.is_none_or(|f| format!("{}::{}", case.sprite, case.name).contains(f)))
+        .collect::<Vec<_>>();
+
+    let results = cases
+        .into_iter()
+        .map(|case| run_test(project_file, case, shader_program))
+        .collect::<Vec<_>>();
+
+    let report = match format {
+        TestFormat::Pretty => pretty_report(&results),
+        TestFormat::Tap => tap_report(&results),
+        TestFormat::Junit => junit_report(&results),
+    };
+    match output {
+        Some(path) => std::fs::write(path, report)
+            .unwrap_or_else(|e| panic!("Failed to write test report to {}: {}", path, e)),
+        None => print!("{}", report),
+    }
+
+    if results.iter().all(TestResult::passed) {
+        0
+    } else {
+        1
+    }
+}
+
+fn discover_tests(project_file: &str) -> Vec<TestCase> {
+    let window = HeadlessWindow::new(1024, 576, None);
+    let runtime = new_runtime(project_file, &window);
+
+    let mut cases = vec![];
+    for sprite in &runtime.project.sprites {
+        for (name, body) in &sprite.tests {
+            cases.push(TestCase {
+                sprite: sprite.name.clone(),
+                name: name.clone(),
+                body: body.clone(),
+                functions: HashMap::new(),
+            });
+        }
+    }
+
+    // `player_test.crst` holds tests for the sprite named `player`. A test file that doesn't
+    // match any sprite runs its tests on an empty sprite named after the file.
+    let dir = Path::new(project_file).parent().unwrap();
+    let mut files = vec![];
+    find_test_files(dir, &mut files);
+    files.sort();
+    for file in files {
+        let stem = file.file_stem().unwrap().to_string_lossy().to_string();
+        let sprite_name = stem.strip_suffix("_test").unwrap_or(&stem).to_string();
+        let code = std::fs::read_to_string(&file).unwrap_or_else(|e| {
+            println!("Failed to read test file {}: {}", file.display(), e);
+            String::new()
+        });
+        let mut tokenizer = Tokenizer::new(code);
+        let tokens = tokenizer.tokenize_full();
+        let mut parser = Parser::new(tokens);
+        let ast = parser.parse();
+        let test_sprite = test_sprite(&sprite_name, ast, file.parent().unwrap());
+        for (name, body) in test_sprite.tests {
+            cases.push(TestCase {
+                sprite: sprite_name.clone(),
+                name,
+                body,
+                functions: test_sprite.functions.clone(),
+            });
+        }
+    }
+
+    cases
+}
+
+fn find_test_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            find_test_files(&path, files);
+        } else if path.to_string_lossy().ends_with("_test.crst") {
+            files.push(path);
+        }
+    }
+}
+
+fn test_sprite(name: &str, ast: Vec<Statement>, base_dir: &Path) -> Sprite {
+    Sprite::new(
+        name.to_string(),
+        vec![],
+        HashMap::new(),
+        ast,
+        vec![],
+        100.0,
+        100.0,
+        0.0,
+        0.0,
+        true,
+        0,
+        0.0,
+        base_dir.to_string_lossy().to_string(),
+        false,
+    )
+}
+
+fn new_runtime(project_file: &str, window: &dyn WindowBackend) -> Runtime {
+    let audio_manager = AudioManager::<MockBackend>::new(AudioManagerSettings::default())
+        .expect("Failed to create audio manager");
+    let mut runtime = Runtime::new(project_file, vec![], window, Box::new(audio_manager), None);
+    runtime.project.testing = true;
+    runtime
+}
+
+fn run_test(project_file: &str, case: TestCase, shader_program: &ShaderProgram) -> TestResult {
+    let start = Instant::now();
+    let mut window = HeadlessWindow::new(1024, 576, None);
+    let mut runtime = new_runtime(project_file, &window);
+    let mut input_manager = InputManager::new();
+    let (width, height) = window.get_size();
+    let mut projection = Mat4::orthographic_rh_gl(
+        -width as f32,
+        width as f32,
+        -height as f32,
+        height as f32,
+        -1.0,
+        1.0,
+    );
+
+    let mut sprites = std::mem::take(&mut runtime.project.sprites);
+    let index = match sprites.iter().position(|s| s.name == case.sprite) {
+        Some(index) => index,
+        None => {
+            let base_dir = Path::new(project_file).parent().unwrap();
+            sprites.push(test_sprite(&case.sprite, vec![], base_dir));
+            sprites.len() - 1
+        }
+    };
+    let snapshots = Snapshots::new(&sprites);
+    let sprite = &mut sprites[index];
+    sprite.functions.extend(case.functions.clone());
+
+    // The first step only runs `setup`, which leaves the sprite the way it is on the first
+    // frame of a real run
+    console::capture_errors();
+    window.poll_events(&mut input_manager);
+    sprite.step(
+        start,
+        1.0 / 60.0,
+        &mut runtime.project,
+        &snapshots,
+        &mut window,
+        &mut input_manager,
+        runtime.audio_manager.as_mut(),
+        shader_program,
+        &mut projection,
+        &runtime.font,
+    );
+    runtime.project.begin_script();
+    for statement in &case.body {
+        Sprite::execute_statement(
+            statement,
+            &mut State {
+                start,
+                dt: 1.0 / 60.0,
+                sprite,
+                project: &mut runtime.project,
+                snapshots: &snapshots,
+                window: &mut window,
+                input_manager: &mut input_manager,
+                audio_manager: runtime.audio_manager.as_mut(),
+                shader_program,
+                projection: &mut projection,
+                font: &runtime.font,
+                local_vars: &mut [],
+                script_id: 0,
+            },
+        );
+        if runtime.project.watchdog.tripped() {
+            break;
+        }
+    }
+    let error = runtime.project.watchdog.take_error();
+
+    TestResult {
+        case,
+        failures: std::mem::take(&mut runtime.project.assert_failures),
+        error,
+        script_errors: console::take_captured_errors(),
+        time: start.elapsed(),
+    }
+}
+
+fn failure_lines(result: &TestResult) -> Vec<String> {
+    let mut lines = vec![];
+    for failure in &result.failures {
+        lines.push(format!("assert {} failed", failure.condition));
+        lines.push(format!("  expected: {}", failure.expected));
+        lines.push(format!("  actual:   {}", failure.actual));
+    }
+    if let Some(error) = &result.error {
+        lines.push(format!("test {}", error));
+    }
+    for error in &result.script_errors {
+        lines.push(format!("error: {}", error));
+    }
+    lines
+}
+
+fn pretty_report(results: &[TestResult]) -> String {
+    let mut report = format!("\nrunning {} tests\n", results.len());
+    for result in results {
+        report += &format!(
+            "test {}::{} ... {}\n",
+            result.case.sprite,
+            result.case.name,
+            if result.passed() { "ok" } else { "FAILED" }
+        );
+    }
+
+    let failed = results.iter().filter(|r| !r.passed()).collect::<Vec<_>>();
+    if !failed.is_empty() {
+        report += "\nfailures:\n";
+        for result in &failed {
+            report += &format!("\n---- {}::{} ----\n", result.case.sprite, result.case.name);
+            for line in failure_lines(result) {
+                report += &format!("{}\n", line);
+            }
+        }
+    }
+
+    report += &format!(
+        "\ntest result: {}. {} passed; {} failed\n",
+        if failed.is_empty() { "ok" } else { "FAILED" },
+        results.len() - failed.len(),
+        failed.len()
+    );
+    report
+}
+
+fn tap_report(results: &[TestResult]) -> String {
+    let mut report = format!("TAP version 13\n1..{}\n", results.len());
+    for (i, result) in results.iter().enumerate() {
+        let name = format!("{}::{}", result.case.sprite, result.case.name);
+        if result.passed() {
+            report += &format!("ok {} - {}\n", i + 1, name);
+            continue;
+        }
+        report += &format!("not ok {} - {}\n  ---\n", i + 1, name);
+        if let Some(failure) = result.failures.first() {
+            report += &format!("  message: {:?}\n", format!("assert {}", failure.condition));
+            report += &format!("  expected: {:?}\n", failure.expected);
+            report += &format!("  actual: {:?}\n", failure.actual);
+        }
+        if let Some(error) = result.error.as_ref().or(result.script_errors.first()) {
+            report += &format!("  error: {:?}\n", error);
+        }
+        report += "  ...\n";
+    }
+    report
+}
+
+fn junit_report(results: &[TestResult]) -> String {
+    fn escape(s: &str) -> String {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    let failed = results.iter().filter(|r| !r.passed()).count();
+    let mut report = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites name=\"crust\" tests=\"{}\" failures=\"{}\">\n",
+        results.len(),
+        failed
+    );
+
+    let mut sprites: Vec<&str> = vec![];
+    for result in results {
+        if !sprites.contains(&result.case.sprite.as_str()) {
+            sprites.push(&result.case.sprite);
+        }
+    }
+    for sprite in sprites {
+        let suite = results
+            .iter()
+            .filter(|r| r.case.sprite == sprite)
+            .collect::<Vec<_>>();
+        report += &format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n",
+            escape(sprite),
+            suite.len(),
+            suite.iter().filter(|r| !r.passed()).count()
+        );
+        for result in suite {
+            report += &format!(
+                "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+                escape(sprite),
+                escape(&result.case.name),
+                result.time.as_secs_f32()
+            );
+            if result.passed() {
+                report += "/>\n";
+                continue;
+            }
+            let error = result.error.as_ref().or(result.script_errors.first());
+            let message = match (result.failures.first(), error) {
+                (Some(failure), _) => format!("assert {} failed", failure.condition),
+                (None, Some(error)) => error.clone(),
+                (None, None) => String::new(),
+            };
+            report += &format!(
+                ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+                escape(&message),
+                escape(&failure_lines(result).join("\n"))
+            );
+        }
+        report += "  </testsuite>\n";
+    }
+    report += "</testsuites>\n";
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_script_error_fails() {
+        let dir = std::env::temp_dir().join(format!("crust-test-runner-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("project.toml"),
+            "[[sprites]]\nname = \"player\"\ncode = \"player.crst\"\ncostumes = []\nx = 0\ny = 0\nw = 100\nh = 100\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("player.crst"),
+            "test \"throws\" {\n    missing_function()\n}\n\ntest \"passes\" {\n    assert 1 == 1\n}\n",
+        )
+        .unwrap();
+
+        let project_file = dir.join("project.toml").to_string_lossy().to_string();
+        let report_file = dir.join("report.txt").to_string_lossy().to_string();
+        let shader_program = ShaderProgram::new("", "");
+        let code = run_tests(
+            &project_file,
+            TestFormat::Pretty,
+            None,
+            Some(&report_file),
+            &shader_program,
+        );
+        let report = std::fs::read_to_string(&report_file).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(code, 1);
+        assert!(report.contains("test player::throws ... FAILED"));
+        assert!(report.contains("test player::passes ... ok"));
+    }
+}
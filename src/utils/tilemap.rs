@@ -0,0 +1,770 @@
+use base64::Engine;
+use glam::*;
+use serde_json::Value as Json;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use crate::utils::Value;
+use crate::utils::core::*;
+
+// The top bits of a tile id say how the tile is flipped, the rest is the id itself
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+const TILE_ID: u32 = 0x0FFF_FFFF;
+
+pub struct Tileset {
+    first_id: u32,
+    tile_count: u32,
+    columns: u32,
+    tile_size: UVec2,
+    spacing: u32,
+    margin: u32,
+    image_size: UVec2,
+    texture: GPUTexture,
+    tile_properties: HashMap<u32, HashMap<String, Value>>,
+}
+
+pub struct TileLayer {
+    pub name: String,
+    pub visible: bool,
+    pub opacity: f32,
+    // Where the layer is drawn between the sprites, like a sprite's layer
+    pub layer: isize,
+    pub properties: HashMap<String, Value>,
+    tiles: Vec<u32>,
+    // One batch of quads per tileset, rebuilt after the tiles change
+    meshes: Option<Vec<(usize, Mesh<Vertex>)>>,
+}
+
+pub struct MapObject {
+    pub id: u32,
+    pub name: String,
+    pub class: String,
+    pub layer: String,
+    // In map pixels, from the top-left corner of the map with y pointing down, like in Tiled
+    pub position: Vec2,
+    pub size: Vec2,
+    pub rotation: f32,
+    pub tile: u32,
+    pub points: Vec<Vec2>,
+    pub properties: HashMap<String, Value>,
+}
+
+// A map made in Tiled. Tiles are addressed by column and row, starting from the top-left corner.
+pub struct Tilemap {
+    pub name: String,
+    pub columns: u32,
+    pub rows: u32,
+    pub tile_size: UVec2,
+    // The top-left corner of the map in world coordinates
+    pub origin: Vec2,
+    // World units per map pixel
+    pub scale: f32,
+    pub properties: HashMap<String, Value>,
+    pub layers: Vec<TileLayer>,
+    pub objects: Vec<MapObject>,
+    tilesets: Vec<Tileset>,
+}
+
+impl Tilemap {
+    // Loads a `.tmx` or `.json`/`.tmj` map. Without a position, the map is centered.
+    pub fn load(
+        name: &str,
+        path: &Path,
+        scale: f32,
+        position: Option<Vec2>,
+        layer: isize,
+    ) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let mut map = if path.extension().is_some_and(|e| e == "tmx") {
+            let document = roxmltree::Document::parse(&content)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+            xml::map(document.root_element(), dir, layer)
+        } else {
+            let json: Json = serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+            json::map(&json, dir, layer)
+        }
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let tiles = (map.columns * map.rows) as usize;
+        if let Some(layer) = map.layers.iter().find(|l| l.tiles.len() != tiles) {
+            return Err(format!(
+                "{}: the layer '{}' has {} tiles instead of {}",
+                path.display(),
+                layer.name,
+                layer.tiles.len(),
+                tiles
+            ));
+        }
+        map.name = name.to_string();
+        map.scale = scale;
+        map.tilesets.sort_by_key(|t| t.first_id);
+        let size = map.cell_size() * uvec2(map.columns, map.rows).as_vec2();
+        map.origin = position.unwrap_or(vec2(-size.x, size.y) / 2.0);
+        Ok(map)
+    }
+
+    // The size of a tile in world units
+    pub fn cell_size(&self) -> Vec2 {
+        self.tile_size.as_vec2() * self.scale
+    }
+
+    pub fn world_to_tile(&self, point: Vec2) -> IVec2 {
+        let cell = (point - self.origin) * vec2(1.0, -1.0) / self.cell_size();
+        cell.floor().as_ivec2()
+    }
+
+    // The center of a tile in world coordinates
+    pub fn tile_to_world(&self, tile: IVec2) -> Vec2 {
+        let cell = tile.as_vec2() + 0.5;
+        self.origin + cell * self.cell_size() * vec2(1.0, -1.0)
+    }
+
+    // The world rectangle of a tile, as (min, max)
+    pub fn tile_bounds(&self, tile: IVec2) -> (Vec2, Vec2) {
+        let half = self.cell_size() / 2.0;
+        let center = self.tile_to_world(tile);
+        (center - half, center + half)
+    }
+
+    fn index(&self, tile: IVec2) -> Option<usize> {
+        let in_bounds = tile.x >= 0
+            && tile.y >= 0
+            && (tile.x as u32) < self.columns
+            && (tile.y as u32) < self.rows;
+        in_bounds.then(|| tile.y as usize * self.columns as usize + tile.x as usize)
+    }
+
+    // The tile id at a tile position, without the flip bits. 0 is an empty tile.
+    pub fn tile(&self, layer: usize, tile: IVec2) -> Option<u32> {
+        let index = self.index(tile)?;
+        Some(self.layers[layer].tiles[index] & TILE_ID)
+    }
+
+    pub fn set_tile(&mut self, layer: usize, tile: IVec2, id: u32) -> Result<(), String> {
+        let index = self
+            .index(tile)
+            .ok_or_else(|| format!("Tile {}, {} is outside of the map", tile.x, tile.y))?;
+        if id & TILE_ID != 0 && self.tileset(id).is_none() {
+            return Err(format!("There is no tile with the id {}", id & TILE_ID));
+        }
+        let layer = &mut self.layers[layer];
+        layer.tiles[index] = id;
+        layer.meshes = None;
+        Ok(())
+    }
+
+    fn tileset(&self, id: u32) -> Option<usize> {
+        let id = id & TILE_ID;
+        self.tilesets
+            .iter()
+            .rposition(|t| t.first_id <= id)
+            .filter(|i| id - self.tilesets[*i].first_id < self.tilesets[*i].tile_count)
+    }
+
+    // The custom properties set on a tile in its tileset
+    pub fn tile_properties(&self, id: u32) -> Option<&HashMap<String, Value>> {
+        let tileset = &self.tilesets[self.tileset(id)?];
+        tileset
+            .tile_properties
+            .get(&((id & TILE_ID) - tileset.first_id))
+    }
+
+    // Whether a tile counts for tile collisions: either the tile or its whole layer has the
+    // property set to true
+    pub fn is_solid(&self, layer: usize, tile: IVec2, property: &str) -> bool {
+        let Some(id) = self.tile(layer, tile).filter(|id| *id != 0) else {
+            return false;
+        };
+        let set = |properties: Option<&HashMap<String, Value>>| {
+            properties
+                .and_then(|p| p.get(property))
+                .is_some_and(Value::to_boolean)
+        };
+        set(Some(&self.layers[layer].properties)) || set(self.tile_properties(id))
+    }
+
+    // The world rectangles of the solid tiles that overlap `min..max`, on any layer
+    pub fn solid_tiles(&self, min: Vec2, max: Vec2, property: &str) -> Vec<(Vec2, Vec2)> {
+        let (a, b) = (
+            self.world_to_tile(vec2(min.x, max.y)),
+            self.world_to_tile(vec2(max.x, min.y)),
+        );
+        let (a, b) = (
+            a.max(IVec2::ZERO),
+            b.min(uvec2(self.columns, self.rows).as_ivec2() - 1),
+        );
+        let mut tiles = vec![];
+        for y in a.y..=b.y {
+            for x in a.x..=b.x {
+                let tile = ivec2(x, y);
+                if (0..self.layers.len()).any(|layer| self.is_solid(layer, tile, property)) {
+                    tiles.push(self.tile_bounds(tile));
+                }
+            }
+        }
+        tiles
+    }
+
+    // The center and size of an object in world units
+    pub fn object_bounds(&self, object: &MapObject) -> (Vec2, Vec2) {
+        // Tile objects are placed by their bottom-left corner, everything else by the top-left
+        let top_left = if object.tile != 0 {
+            object.position - vec2(0.0, object.size.y)
+        } else {
+            object.position
+        };
+        (
+            self.to_world(top_left + object.size / 2.0),
+            object.size * self.scale,
+        )
+    }
+
+    // A point in map pixels to world coordinates
+    pub fn to_world(&self, point: Vec2) -> Vec2 {
+        self.origin + point * self.scale * vec2(1.0, -1.0)
+    }
+
+    fn build_meshes(&self, layer: &TileLayer) -> Vec<(usize, Mesh<Vertex>)> {
+        let mut batches: Vec<(Vec<Vertex>, Vec<u32>)> =
+            self.tilesets.iter().map(|_| (vec![], vec![])).collect();
+        for (i, &id) in layer.tiles.iter().enumerate() {
+            let Some(t) = self.tileset(id).filter(|_| id & TILE_ID != 0) else {
+                continue;
+            };
+            let tileset = &self.tilesets[t];
+            let local = (id & TILE_ID) - tileset.first_id;
+            let pixel = tileset.margin
+                + uvec2(local % tileset.columns, local / tileset.columns)
+                    * (tileset.tile_size + tileset.spacing);
+            let uv_min = pixel.as_vec2() / tileset.image_size.as_vec2();
+            let uv_size = tileset.tile_size.as_vec2() / tileset.image_size.as_vec2();
+
+            // Tiles bigger than the grid stick out at the top and right, like in Tiled
+            let cell = uvec2(i as u32 % self.columns, i as u32 / self.columns);
+            let bottom_left = self.to_world(((cell + UVec2::Y) * self.tile_size).as_vec2());
+            let size = tileset.tile_size.as_vec2() * self.scale;
+
+            // Corners as (x right, y down) within the tile. Tiled flips diagonally first, then
+            // horizontally, then vertically, so the texture is looked up in reverse.
+            let uv = |mut corner: Vec2| {
+                if id & FLIPPED_VERTICALLY != 0 {
+                    corner.y = 1.0 - corner.y;
+                }
+                if id & FLIPPED_HORIZONTALLY != 0 {
+                    corner.x = 1.0 - corner.x;
+                }
+                if id & FLIPPED_DIAGONALLY != 0 {
+                    corner = corner.yx();
+                }
+                uv_min + corner * uv_size
+            };
+            let (vertices, indices) = &mut batches[t];
+            let base = vertices.len() as u32;
+            for (offset, corner) in [
+                (vec2(0.0, 0.0), vec2(0.0, 1.0)),
+                (vec2(size.x, 0.0), vec2(1.0, 1.0)),
+                (size, vec2(1.0, 0.0)),
+                (vec2(0.0, size.y), vec2(0.0, 0.0)),
+            ] {
+                vertices.push(Vertex {
+                    position: bottom_left + offset,
+                    uv: uv(corner),
+                });
+            }
+            indices.extend([0, 1, 2, 0, 2, 3].map(|i| base + i));
+        }
+        batches
+            .into_iter()
+            .enumerate()
+            .filter(|(_, (vertices, _))| !vertices.is_empty())
+            .map(|(t, (vertices, indices))| {
+                (t, Mesh::new(&vertices, &indices, DrawMode::Triangles))
+            })
+            .collect()
+    }
+
+    // Draws one tile layer with a draw call per tileset
+    pub fn draw_layer(&mut self, index: usize, shader: &ShaderProgram, projection: &Mat4) {
+        if !self.layers[index].visible {
+            return;
+        }
+        if self.layers[index].meshes.is_none() {
+            let meshes = self.build_meshes(&self.layers[index]);
+            self.layers[index].meshes = Some(meshes);
+        }
+        let layer = &self.layers[index];
+        shader.use_program();
+        shader.set_uniform("u_color", vec4(1.0, 1.0, 1.0, layer.opacity));
+        shader.set_uniform("u_projection", *projection);
+        shader.set_uniform("u_model", Mat4::IDENTITY);
+        shader.set_uniform("u_effects", &[] as &[i32]);
+        shader.set_uniform("u_effect_values", &[] as &[f32]);
+        shader.set_uniform("u_effects_count", 0);
+        for (tileset, mesh) in layer.meshes.iter().flatten() {
+            self.tilesets[*tileset].texture.bind();
+            mesh.draw();
+        }
+    }
+}
+
+// Every tile layer of every map as (layer, map, tile layer), in the order they are drawn
+pub fn tile_layer_order(tilemaps: &[Tilemap]) -> Vec<(isize, usize, usize)> {
+    let mut order = tilemaps
+        .iter()
+        .enumerate()
+        .flat_map(|(m, map)| {
+            map.layers
+                .iter()
+                .enumerate()
+                .map(move |(l, layer)| (layer.layer, m, l))
+        })
+        .collect::<Vec<_>>();
+    order.sort_by_key(|(layer, _, _)| *layer);
+    order
+}
+
+fn property_value(kind: &str, value: &str) -> Value {
+    match kind {
+        "int" | "float" => Value::Number(value.parse().unwrap_or(0.0)),
+        "bool" => Value::Boolean(value == "true"),
+        _ => Value::String(value.to_string()),
+    }
+}
+
+// Tile data saved as base64, optionally compressed, holds the ids as little-endian u32s
+fn decode_tiles(data: &str, compression: Option<&str>) -> Result<Vec<u32>, String> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|e| format!("Invalid tile data: {}", e))?;
+    let mut decoded = vec![];
+    match compression {
+        None | Some("") => decoded = bytes,
+        Some("zlib") => {
+            flate2::read::ZlibDecoder::new(bytes.as_slice())
+                .read_to_end(&mut decoded)
+                .map_err(|e| format!("Invalid tile data: {}", e))?;
+        }
+        Some("gzip") => {
+            flate2::read::GzDecoder::new(bytes.as_slice())
+                .read_to_end(&mut decoded)
+                .map_err(|e| format!("Invalid tile data: {}", e))?;
+        }
+        Some(other) => {
+            return Err(format!(
+                "'{}' compressed layers are not supported, use CSV, zlib or gzip",
+                other
+            ));
+        }
+    }
+    Ok(decoded
+        .chunks_exact(4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
+fn load_tileset_image(path: &Path) -> Result<(GPUTexture, UVec2), String> {
+    let texture = CPUTexture::load_from_file(&path.to_string_lossy())
+        .map_err(|e| format!("{} ({})", e, path.display()))?;
+    let size = uvec2(texture.width, texture.height);
+    Ok((texture.upload_to_gpu(), size))
+}
+
+impl Tileset {
+    // Older files leave out the columns and the tile count, so they're worked out from the image
+    fn finish(mut self) -> Result<Self, String> {
+        let usable = self
+            .image_size
+            .saturating_sub(UVec2::splat(self.margin * 2))
+            + self.spacing;
+        let fits = usable / (self.tile_size + self.spacing).max(UVec2::ONE);
+        if self.columns == 0 {
+            self.columns = fits.x;
+        }
+        if self.tile_count == 0 {
+            self.tile_count = fits.x * fits.y;
+        }
+        if self.columns == 0 || self.tile_size.min_element() == 0 {
+            return Err("A tileset has no tiles".to_string());
+        }
+        Ok(self)
+    }
+}
+
+fn check_map(orientation: &str, infinite: bool) -> Result<(), String> {
+    if orientation != "orthogonal" {
+        return Err(format!(
+            "{} maps are not supported, only orthogonal ones",
+            orientation
+        ));
+    }
+    if infinite {
+        return Err(
+            "Infinite maps are not supported, turn off 'Infinite' in the map properties"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+mod xml {
+    use super::*;
+    use roxmltree::Node;
+
+    fn attr<T: std::str::FromStr>(node: Node, name: &str) -> Option<T> {
+        node.attribute(name)?.parse().ok()
+    }
+
+    fn children<'a, 'input>(
+        node: Node<'a, 'input>,
+        tag: &'a str,
+    ) -> impl Iterator<Item = Node<'a, 'input>> {
+        node.children().filter(move |c| c.has_tag_name(tag))
+    }
+
+    fn properties(node: Node) -> HashMap<String, Value> {
+        children(node, "properties")
+            .flat_map(|p| children(p, "property"))
+            .filter_map(|p| {
+                let value = p.attribute("value").or(p.text()).unwrap_or("");
+                let kind = p.attribute("type").unwrap_or("string");
+                Some((
+                    p.attribute("name")?.to_string(),
+                    property_value(kind, value),
+                ))
+            })
+            .collect()
+    }
+
+    pub fn map(node: Node, dir: &Path, layer: isize) -> Result<Tilemap, String> {
+        check_map(
+            node.attribute("orientation").unwrap_or("orthogonal"),
+            node.attribute("infinite") == Some("1"),
+        )?;
+        let mut map = Tilemap {
+            name: String::new(),
+            columns: attr(node, "width").unwrap_or(0),
+            rows: attr(node, "height").unwrap_or(0),
+            tile_size: uvec2(
+                attr(node, "tilewidth").unwrap_or(0),
+                attr(node, "tileheight").unwrap_or(0),
+            ),
+            origin: Vec2::ZERO,
+            scale: 1.0,
+            properties: properties(node),
+            layers: vec![],
+            objects: vec![],
+            tilesets: vec![],
+        };
+        for tileset in children(node, "tileset") {
+            let first_id = attr(tileset, "firstgid").unwrap_or(1);
+            map.tilesets.push(match tileset.attribute("source") {
+                Some(source) => {
+                    let path = dir.join(source);
+                    let content = std::fs::read_to_string(&path)
+                        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                    let document = roxmltree::Document::parse(&content)
+                        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+                    let dir = path.parent().unwrap_or(dir);
+                    self::tileset(document.root_element(), first_id, dir)?
+                }
+                None => self::tileset(tileset, first_id, dir)?,
+            });
+        }
+        layers(node, &mut map, layer, true)?;
+        Ok(map)
+    }
+
+    fn tileset(node: Node, first_id: u32, dir: &Path) -> Result<Tileset, String> {
+        let image = children(node, "image")
+            .next()
+            .and_then(|i| i.attribute("source"))
+            .ok_or("Tilesets made of separate images are not supported")?;
+        let tile_properties = children(node, "tile")
+            .filter_map(|tile| Some((attr(tile, "id")?, properties(tile))))
+            .collect();
+        let (texture, image_size) = load_tileset_image(&dir.join(image))?;
+        Tileset {
+            first_id,
+            tile_count: attr(node, "tilecount").unwrap_or(0),
+            columns: attr(node, "columns").unwrap_or(0),
+            tile_size: uvec2(
+                attr(node, "tilewidth").unwrap_or(0),
+                attr(node, "tileheight").unwrap_or(0),
+            ),
+            spacing: attr(node, "spacing").unwrap_or(0),
+            margin: attr(node, "margin").unwrap_or(0),
+            image_size,
+            texture,
+            tile_properties,
+        }
+        .finish()
+    }
+
+    // Tile layers and object layers, with the ones inside groups flattened
+    fn layers(node: Node, map: &mut Tilemap, layer: isize, visible: bool) -> Result<(), String> {
+        for child in node.children().filter(Node::is_element) {
+            let name = child.attribute("name").unwrap_or("").to_string();
+            let visible = visible && child.attribute("visible") != Some("0");
+            match child.tag_name().name() {
+                "layer" => {
+                    let data = children(child, "data")
+                        .next()
+                        .ok_or("A layer has no data")?;
+                    let tiles = match data.attribute("encoding") {
+                        Some("csv") => data
+                            .text()
+                            .unwrap_or("")
+                            .split(',')
+                            .map(|id| id.trim().parse().unwrap_or(0))
+                            .collect(),
+                        Some("base64") => {
+                            decode_tiles(data.text().unwrap_or(""), data.attribute("compression"))?
+                        }
+                        _ => children(data, "tile")
+                            .map(|t| attr(t, "gid").unwrap_or(0))
+                            .collect(),
+                    };
+                    let properties = properties(child);
+                    map.layers.push(TileLayer {
+                        layer: properties
+                            .get("layer")
+                            .map_or(layer, |l| l.to_number() as isize),
+                        name,
+                        visible,
+                        opacity: attr(child, "opacity").unwrap_or(1.0),
+                        properties,
+                        tiles,
+                        meshes: None,
+                    });
+                }
+                "objectgroup" => {
+                    for object in children(child, "object") {
+                        let points = ["polygon", "polyline"]
+                            .into_iter()
+                            .flat_map(|tag| children(object, tag))
+                            .flat_map(|p| {
+                                p.attribute("points")
+                                    .unwrap_or("")
+                                    .split_whitespace()
+                                    .filter_map(|point| {
+                                        let (x, y) = point.split_once(',')?;
+                                        Some(vec2(x.parse().ok()?, y.parse().ok()?))
+                                    })
+                                    .collect::<Vec<_>>()
+                            })
+                            .collect();
+                        map.objects.push(MapObject {
+                            id: attr(object, "id").unwrap_or(0),
+                            name: object.attribute("name").unwrap_or("").to_string(),
+                            class: object
+                                .attribute("class")
+                                .or(object.attribute("type"))
+                                .unwrap_or("")
+                                .to_string(),
+                            layer: name.clone(),
+                            position: vec2(
+                                attr(object, "x").unwrap_or(0.0),
+                                attr(object, "y").unwrap_or(0.0),
+                            ),
+                            size: vec2(
+                                attr(object, "width").unwrap_or(0.0),
+                                attr(object, "height").unwrap_or(0.0),
+                            ),
+                            rotation: attr(object, "rotation").unwrap_or(0.0),
+                            tile: attr(object, "gid").unwrap_or(0) & TILE_ID,
+                            points,
+                            properties: properties(object),
+                        });
+                    }
+                }
+                "group" => layers(child, map, layer, visible)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+mod json {
+    use super::*;
+
+    fn number(json: &Json, key: &str) -> f64 {
+        json.get(key).and_then(Json::as_f64).unwrap_or(0.0)
+    }
+
+    fn string<'a>(json: &'a Json, key: &str) -> &'a str {
+        json.get(key).and_then(Json::as_str).unwrap_or("")
+    }
+
+    fn array<'a>(json: &'a Json, key: &str) -> &'a [Json] {
+        json.get(key)
+            .and_then(Json::as_array)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    fn properties(json: &Json) -> HashMap<String, Value> {
+        array(json, "properties")
+            .iter()
+            .map(|p| {
+                let value = match p.get("value") {
+                    Some(Json::Number(n)) => Value::Number(n.as_f64().unwrap_or(0.0) as f32),
+                    Some(Json::Bool(b)) => Value::Boolean(*b),
+                    Some(Json::String(s)) => property_value(string(p, "type"), s),
+                    Some(other) => Value::String(other.to_string()),
+                    None => Value::Null,
+                };
+                (string(p, "name").to_string(), value)
+            })
+            .collect()
+    }
+
+    pub fn map(json: &Json, dir: &Path, layer: isize) -> Result<Tilemap, String> {
+        check_map(
+            json.get("orientation")
+                .and_then(Json::as_str)
+                .unwrap_or("orthogonal"),
+            json.get("infinite").and_then(Json::as_bool) == Some(true),
+        )?;
+        let mut map = Tilemap {
+            name: String::new(),
+            columns: number(json, "width") as u32,
+            rows: number(json, "height") as u32,
+            tile_size: uvec2(
+                number(json, "tilewidth") as u32,
+                number(json, "tileheight") as u32,
+            ),
+            origin: Vec2::ZERO,
+            scale: 1.0,
+            properties: properties(json),
+            layers: vec![],
+            objects: vec![],
+            tilesets: vec![],
+        };
+        for tileset in array(json, "tilesets") {
+            let first_id = number(tileset, "firstgid") as u32;
+            map.tilesets
+                .push(match tileset.get("source").and_then(Json::as_str) {
+                    Some(source) => {
+                        let path = dir.join(source);
+                        let content = std::fs::read_to_string(&path)
+                            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                        let dir = path.parent().unwrap_or(dir);
+                        if path.extension().is_some_and(|e| e == "tsx") {
+                            return Err(format!(
+                                "{}: JSON maps need JSON tilesets, export the tileset as .tsj",
+                                path.display()
+                            ));
+                        }
+                        let json: Json = serde_json::from_str(&content)
+                            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+                        self::tileset(&json, first_id, dir)?
+                    }
+                    None => self::tileset(tileset, first_id, dir)?,
+                });
+        }
+        layers(array(json, "layers"), &mut map, layer, true)?;
+        Ok(map)
+    }
+
+    fn tileset(json: &Json, first_id: u32, dir: &Path) -> Result<Tileset, String> {
+        let image = json
+            .get("image")
+            .and_then(Json::as_str)
+            .ok_or("Tilesets made of separate images are not supported")?;
+        let tile_properties = array(json, "tiles")
+            .iter()
+            .map(|tile| (number(tile, "id") as u32, properties(tile)))
+            .collect();
+        let (texture, image_size) = load_tileset_image(&dir.join(image))?;
+        Tileset {
+            first_id,
+            tile_count: number(json, "tilecount") as u32,
+            columns: number(json, "columns") as u32,
+            tile_size: uvec2(
+                number(json, "tilewidth") as u32,
+                number(json, "tileheight") as u32,
+            ),
+            spacing: number(json, "spacing") as u32,
+            margin: number(json, "margin") as u32,
+            image_size,
+            texture,
+            tile_properties,
+        }
+        .finish()
+    }
+
+    fn layers(
+        layers: &[Json],
+        map: &mut Tilemap,
+        layer: isize,
+        visible: bool,
+    ) -> Result<(), String> {
+        for json in layers {
+            let name = string(json, "name").to_string();
+            let visible = visible && json.get("visible").and_then(Json::as_bool) != Some(false);
+            match string(json, "type") {
+                "tilelayer" => {
+                    let tiles = match json.get("data") {
+                        Some(Json::String(data)) => {
+                            decode_tiles(data, json.get("compression").and_then(Json::as_str))?
+                        }
+                        _ => array(json, "data")
+                            .iter()
+                            .map(|id| id.as_u64().unwrap_or(0) as u32)
+                            .collect(),
+                    };
+                    let properties = properties(json);
+                    map.layers.push(TileLayer {
+                        layer: properties
+                            .get("layer")
+                            .map_or(layer, |l| l.to_number() as isize),
+                        name,
+                        visible,
+                        opacity: json.get("opacity").and_then(Json::as_f64).unwrap_or(1.0) as f32,
+                        properties,
+                        tiles,
+                        meshes: None,
+                    });
+                }
+                "objectgroup" => {
+                    for object in array(json, "objects") {
+                        let points = ["polygon", "polyline"]
+                            .into_iter()
+                            .flat_map(|key| array(object, key))
+                            .map(|p| vec2(number(p, "x") as f32, number(p, "y") as f32))
+                            .collect();
+                        map.objects.push(MapObject {
+                            id: number(object, "id") as u32,
+                            name: string(object, "name").to_string(),
+                            class: object
+                                .get("class")
+                                .or(object.get("type"))
+                                .and_then(Json::as_str)
+                                .unwrap_or("")
+                                .to_string(),
+                            layer: name.clone(),
+                            position: vec2(number(object, "x") as f32, number(object, "y") as f32),
+                            size: vec2(
+                                number(object, "width") as f32,
+                                number(object, "height") as f32,
+                            ),
+                            rotation: number(object, "rotation") as f32,
+                            tile: number(object, "gid") as u32 & TILE_ID,
+                            points,
+                            properties: properties(object),
+                        });
+                    }
+                }
+                "group" => self::layers(array(json, "layers"), map, layer, visible)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitAction {
+    Suspend, // Abort the script for this frame, it starts over on the next one
+    Stop,    // Abort the script and never run it again
+}
+
+// Keeps runaway scripts (`while true {}`, unbounded recursion, ...) from freezing the whole
+// runtime. Every statement and loop iteration ticks the watchdog and every function call goes
+// one level deeper. Once a limit is hit the watchdog trips and the current script unwinds.
+#[derive(Debug)]
+pub struct Watchdog {
+    pub max_instructions: usize,
+    pub max_script_time: Option<Duration>,
+    pub max_call_depth: usize,
+    pub action: LimitAction,
+    instructions: usize,
+    call_depth: usize,
+    script_start: Instant,
+    tripped: Option<String>,
+}
+
+impl Default for Watchdog {
+    fn default() -> Self {
+        Self::new(
+            10_000_000,
+            Some(Duration::from_millis(500)),
+            256,
+            LimitAction::Stop,
+        )
+    }
+}
+
+impl Watchdog {
+    pub fn new(
+        max_instructions: usize,
+        max_script_time: Option<Duration>,
+        max_call_depth: usize,
+        action: LimitAction,
+    ) -> Self {
+        Self {
+            max_instructions,
+            max_script_time,
+            max_call_depth,
+            action,
+            instructions: 0,
+            call_depth: 0,
+            script_start: Instant::now(),
+            tripped: None,
+        }
+    }
+
+    pub fn begin_script(&mut self) {
+        self.instructions = 0;
+        self.call_depth = 0;
+        self.script_start = Instant::now();
+        self.tripped = None;
+    }
+
+    pub fn restart_clock(&mut self) {
+        self.script_start = Instant::now();
+    }
+
+    pub fn call_depth(&self) -> usize {
+        self.call_depth
+    }
+
+    pub fn tick(&mut self) -> bool {
+        if self.tripped.is_some() {
+            return false;
+        }
+        self.instructions += 1;
+        if self.instructions > self.max_instructions {
+            self.trip(format!(
+                "exceeded the budget of {} instructions per frame",
+                self.max_instructions
+            ));
+            return false;
+        }
+        // Checking the clock on every instruction is expensive, so only do it every so often
+        if let Some(max_time) = self.max_script_time
+            && self.instructions.is_multiple_of(1024)
+            && self.script_start.elapsed() > max_time
+        {
+            self.trip(format!(
+                "exceeded the time budget of {}ms per frame",
+                max_time.as_millis()
+            ));
+            return false;
+        }
+        true
+    }
+
+    pub fn enter_call(&mut self) -> Result<(), String> {
+        if self.call_depth >= self.max_call_depth {
+            let message = format!("exceeded the maximum call depth of {}", self.max_call_depth);
+            self.trip(message.clone());
+            return Err(message);
+        }
+        self.call_depth += 1;
+        Ok(())
+    }
+
+    pub fn exit_call(&mut self) {
+        self.call_depth = self.call_depth.saturating_sub(1);
+    }
+
+    pub fn tripped(&self) -> bool {
+        self.tripped.is_some()
+    }
+
+    pub fn take_error(&mut self) -> Option<String> {
+        self.tripped.take()
+    }
+
+    fn trip(&mut self, reason: String) {
+        if self.tripped.is_none() {
+            self.tripped = Some(reason);
+        }
+    }
+}